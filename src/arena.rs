@@ -0,0 +1,311 @@
+use crate::board::*;
+use crate::board::piece::*;
+use crate::rng::*;
+use crate::strategy::*;
+
+/// How a game between computer players ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl Outcome {
+    /// Points scored by white, 1 for a win and 0.5 for a draw.
+    pub fn white_score(&self) -> f64 {
+        match self {
+            Outcome::WhiteWins => 1.0,
+            Outcome::BlackWins => 0.0,
+            Outcome::Draw => 0.5,
+        }
+    }
+
+    /// The result as written in PGN.
+    pub fn result(&self) -> &'static str {
+        match self {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// Plays a game from `board` without any output and returns its result and
+/// moves. Besides mate, stalemate, threefold repetition and the fifty move
+/// rule, the game is drawn after `max_plies`.
+pub fn play_game(white: &dyn Strategy, black: &dyn Strategy, board: &Board, max_plies: usize) -> (Outcome, Vec<Move>) {
+    let mut board = board.clone();
+    let mut history = GameHistory::new(&board);
+    let mut moves = Vec::new();
+    while moves.len() < max_plies && board.halfmove_clock < 100 {
+        if board.legal_moves().is_empty() || history.is_threefold(&board) { break; }
+
+        let strategy = if board.side_to_move == PieceColor::White { white } else { black };
+        strategy.set_history(&history);
+        let mv = strategy.next_move(&board);
+        if let Err(e) = board.apply_move(mv) {
+            panic!("{e} in {}", board.to_fen());
+        }
+        history.push(&board);
+        moves.push(mv);
+    }
+
+    let outcome = if !board.is_checkmate() {
+        Outcome::Draw
+    } else if board.side_to_move == PieceColor::White {
+        Outcome::BlackWins
+    } else {
+        Outcome::WhiteWins
+    };
+    (outcome, moves)
+}
+
+/// The start position after `plies` random moves, for games which should
+/// not all be the same.
+pub fn random_opening(rng: &mut Rng, plies: usize) -> Board {
+    loop {
+        let mut board = Board::new();
+        for _ in 0..plies {
+            let moves = board.legal_moves();
+            if moves.is_empty() { break; }
+            board.apply_move(moves[rng.below(moves.len() as u64) as usize]).unwrap();
+        }
+        if !board.legal_moves().is_empty() { return board; }
+    }
+}
+
+/// The measured strength of a skill level in a calibration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelRating {
+    pub skill: Skill,
+    pub games: u32,
+    /// Points scored, 1 per win and 0.5 per draw.
+    pub score: f64,
+    /// Rating fitted to all results, with the same average as the nominal
+    /// ratings of the levels.
+    pub elo: f64,
+}
+
+/// Settings of a calibration match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    pub levels: Vec<Skill>,
+    /// Games between every two levels, with colours alternating.
+    pub games_per_pair: u32,
+    /// Random moves played before each pair of games.
+    pub opening_plies: usize,
+    pub max_plies: usize,
+    pub limits: SearchLimits,
+    pub seed: u64,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            levels: [0, 5, 10, 15, 20].into_iter().map(Skill::new).collect(),
+            games_per_pair: 4,
+            opening_plies: 4,
+            max_plies: 300,
+            limits: SearchLimits::depth(AlphaBetaStrategy::DEFAULT_DEPTH),
+            seed: 1,
+        }
+    }
+}
+
+impl Calibration {
+    /// Plays every level against every other one and rates them from the
+    /// results, calling `on_game` with the levels of white and black after
+    /// each game.
+    pub fn run(&self, mut on_game: impl FnMut(Skill, Skill, Outcome)) -> Vec<LevelRating> {
+        let players: Vec<AlphaBetaStrategy> = self.levels.iter().enumerate()
+            .map(|(i, &skill)| {
+                let mut strategy = AlphaBetaStrategy::with_limits(self.limits);
+                strategy.skill = skill;
+                strategy.set_seed(self.seed.wrapping_add(i as u64));
+                strategy
+            })
+            .collect();
+
+        let n = self.levels.len();
+        let mut rng = Rng::new(self.seed);
+        // points scored by the first level against the second, and games
+        let mut scores = vec![vec![0.0; n]; n];
+        let mut games = vec![vec![0u32; n]; n];
+        for i in 0..n {
+            for j in i + 1..n {
+                let mut opening = Board::new();
+                for game in 0..self.games_per_pair {
+                    // both colours play each opening
+                    if game % 2 == 0 { opening = random_opening(&mut rng, self.opening_plies); }
+                    let (white, black) = if game % 2 == 0 { (i, j) } else { (j, i) };
+                    players[white].clear_hash();
+                    players[black].clear_hash();
+                    let (outcome, _) = play_game(&players[white], &players[black], &opening, self.max_plies);
+                    on_game(self.levels[white], self.levels[black], outcome);
+
+                    scores[white][black] += outcome.white_score();
+                    scores[black][white] += 1.0 - outcome.white_score();
+                    games[white][black] += 1;
+                    games[black][white] += 1;
+                }
+            }
+        }
+
+        let anchor = self.levels.iter().map(|skill| skill.elo() as f64).sum::<f64>() / n.max(1) as f64;
+        let elos = fit_ratings(&scores, &games, anchor);
+        self.levels.iter().enumerate()
+            .map(|(i, &skill)| LevelRating {
+                skill,
+                games: games[i].iter().sum(),
+                score: scores[i].iter().sum(),
+                elo: elos[i],
+            })
+            .collect()
+    }
+}
+
+/// Settings of a match between two computer players.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    /// Games played, with colours alternating.
+    pub games: u32,
+    /// Random moves played before each pair of games.
+    pub opening_plies: usize,
+    pub max_plies: usize,
+    pub seed: u64,
+}
+
+impl Default for Match {
+    fn default() -> Self {
+        Self { games: 20, opening_plies: 4, max_plies: 300, seed: 1 }
+    }
+}
+
+/// The result of a match for the first player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchScore {
+    pub games: u32,
+    pub score: f64,
+    /// Estimated rating difference to the second player.
+    pub elo: f64,
+}
+
+impl Match {
+    /// Plays `first` against `second`, calling `on_game` with whether the
+    /// first player had white after each game.
+    pub fn run(&self, first: &AlphaBetaStrategy, second: &AlphaBetaStrategy, mut on_game: impl FnMut(bool, Outcome)) -> MatchScore {
+        let mut rng = Rng::new(self.seed);
+        let mut opening = Board::new();
+        let mut score = 0.0;
+        for game in 0..self.games {
+            if game % 2 == 0 { opening = random_opening(&mut rng, self.opening_plies); }
+            let first_white = game % 2 == 0;
+            let (white, black) = if first_white { (first, second) } else { (second, first) };
+            white.clear_hash();
+            black.clear_hash();
+            let (outcome, _) = play_game(white, black, &opening, self.max_plies);
+            on_game(first_white, outcome);
+            score += if first_white { outcome.white_score() } else { 1.0 - outcome.white_score() };
+        }
+
+        let games = self.games as f64;
+        let ratings = fit_ratings(&[vec![0.0, score], vec![games - score, 0.0]], &[vec![0, self.games], vec![self.games, 0]], 0.0);
+        MatchScore { games: self.games, score, elo: ratings[0] - ratings[1] }
+    }
+}
+
+/// Expected score against a player rated `difference` points lower.
+pub fn expected_score(difference: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-difference / 400.0))
+}
+
+/// Ratings under which the expected scores match the actual ones, averaging
+/// `anchor`. Every pairing counts an extra draw, so that players winning
+/// all their games still get a finite rating.
+pub fn fit_ratings(scores: &[Vec<f64>], games: &[Vec<u32>], anchor: f64) -> Vec<f64> {
+    let n = scores.len();
+    let mut ratings = vec![0.0; n];
+    for _ in 0..2000 {
+        for i in 0..n {
+            let (mut actual, mut expected, mut played) = (0.0, 0.0, 0.0);
+            for j in (0..n).filter(|&j| j != i && games[i][j] > 0) {
+                actual += scores[i][j] + 0.5;
+                expected += (games[i][j] + 1) as f64 * expected_score(ratings[i] - ratings[j]);
+                played += (games[i][j] + 1) as f64;
+            }
+            if played > 0.0 { ratings[i] += 200.0 * (actual - expected) / played; }
+        }
+    }
+
+    let shift = anchor - ratings.iter().sum::<f64>() / n.max(1) as f64;
+    ratings.iter().map(|rating| rating + shift).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_a_scripted_game_to_mate() {
+        let script = ScriptedStrategy::from_text("f3 e5 g4 Qh4#");
+        let (outcome, moves) = play_game(&script, &script, &Board::new(), 100);
+        assert_eq!(outcome, Outcome::BlackWins);
+        assert_eq!(moves.len(), 4);
+    }
+
+    #[test]
+    fn repetitions_are_drawn() {
+        let script = ScriptedStrategy::from_text("Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8 e4");
+        let (outcome, moves) = play_game(&script, &script, &Board::new(), 100);
+        assert_eq!((outcome, moves.len()), (Outcome::Draw, 8));
+    }
+
+    #[test]
+    fn long_games_are_drawn() {
+        let (white, black) = (RandomStrategy::new(5), RandomStrategy::new(6));
+        let (outcome, moves) = play_game(&white, &black, &Board::new(), 6);
+        assert_eq!((outcome, moves.len()), (Outcome::Draw, 6));
+    }
+
+    #[test]
+    fn fitted_ratings_follow_the_scores() {
+        // the first player scores 3 of 4 against the second
+        let scores = vec![vec![0.0, 3.0], vec![1.0, 0.0]];
+        let games = vec![vec![0, 4], vec![4, 0]];
+        let ratings = fit_ratings(&scores, &games, 1000.0);
+        assert!((ratings[0] + ratings[1] - 2000.0).abs() < 1e-6);
+        // with the extra draw the first scores 3.5 of 5
+        assert!((expected_score(ratings[0] - ratings[1]) - 0.7).abs() < 1e-3);
+    }
+
+    #[test]
+    fn match_alternates_colours() {
+        let settings = Match { games: 2, max_plies: 16, ..Match::default() };
+        let first = AlphaBetaStrategy::new(2);
+        let mut second = AlphaBetaStrategy::new(2);
+        second.options = SearchOptions::none();
+        let mut colours = Vec::new();
+        let result = settings.run(&first, &second, |first_white, _| colours.push(first_white));
+        assert_eq!(colours, vec![true, false]);
+        assert_eq!(result.games, 2);
+        assert_eq!(result.elo > 0.0, result.score > 1.0);
+    }
+
+    #[test]
+    fn calibration_rates_every_level() {
+        let calibration = Calibration {
+            levels: vec![Skill::new(0), Skill::new(4)],
+            games_per_pair: 2,
+            max_plies: 20,
+            limits: SearchLimits::depth(2),
+            ..Calibration::default()
+        };
+        let mut played = 0;
+        let ratings = calibration.run(|_, _, _| played += 1);
+        assert_eq!(played, 2);
+        assert_eq!(ratings.len(), 2);
+        assert!(ratings.iter().all(|rating| rating.games == 2));
+        assert!((ratings[0].score + ratings[1].score - 2.0).abs() < 1e-9);
+    }
+}
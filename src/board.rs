@@ -1,40 +1,89 @@
+pub mod castling;
 pub mod cell;
+pub mod history;
+pub mod movegen;
+pub mod moves;
 pub mod piece;
+pub mod square;
+pub mod zobrist;
 
+use castling::*;
 use cell::*;
 use piece::*;
 
-#[derive(Debug, PartialEq)]
+pub use history::GameHistory;
+pub use moves::Move;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Board {
-    pub cells: [BoardCell; 64]
+    pub cells: [BoardCell; 64],
+    pub side_to_move: PieceColor,
+    pub castling: CastlingRights,
+    pub en_passant: Option<usize>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+/// State needed to take back a move made with `Board::make`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Undo {
+    captured: Option<Piece>,
+    castling: CastlingRights,
+    en_passant: Option<usize>,
+    halfmove_clock: u32,
 }
 
 impl Board {
     pub fn new() -> Self {
-        if let Ok(board) = Self::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR") {
+        if let Ok(board) = Self::from_fen(START_FEN) {
             return board;
         }
 
+        Self::empty()
+    }
+
+    pub fn empty() -> Self {
+        let mut cells: [BoardCell; 64] = [Default::default(); 64];
+        for (i, cell) in cells.iter_mut().enumerate() {
+            cell.color = CellColor::from_index(i).unwrap();
+        }
+
         Self {
-            cells: [Default::default(); 64],
+            cells,
+            side_to_move: PieceColor::White,
+            castling: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
         }
     }
 
+    /// Parses a FEN string. Only the piece placement is required, the
+    /// remaining fields default to white to move without castling rights.
     pub fn from_fen(fen: &str) -> Result<Self, String> {
         let mut board = Self {
            cells: [Default::default(); 64],
+           ..Self::empty()
         };
 
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or(format!("Invalid fen string {}", fen))?;
+
         let mut idx: usize = 0;
-        for c in fen.chars() {
+        for c in placement.chars() {
             match c {
                 _ if c.is_numeric() => {
-                    for i in idx..idx+(c.to_digit(10).unwrap() as usize) {
+                    let count = c.to_digit(10).unwrap() as usize;
+                    if idx + count > 64 { return Err(format!("Invalid fen string {}", fen)); }
+                    for i in idx..idx+count {
                         board.cells[i].color = CellColor::from_index(i).unwrap();
-                        idx += 1;
                     }
+                    idx += count;
                 }, 
                 _ if c.is_alphabetic() => {
+                    if idx >= 64 { return Err(format!("Invalid fen string {}", fen)); }
                     board.cells[idx].color = CellColor::from_index(idx).unwrap();
                     board.cells[idx].piece = match Piece::from_fen_char(c) {
                         Some(x) => Some(x),
@@ -43,7 +92,7 @@ impl Board {
                     idx += 1;
                 }
                 '/' => {
-                    if idx%8 != 0 { return Err(format!("Invalid fen string {}", fen)); }
+                    if !idx.is_multiple_of(8) { return Err(format!("Invalid fen string {}", fen)); }
                 },
                 _ => return Err(format!("Invalid fen char {}", c)),
             };
@@ -51,9 +100,72 @@ impl Board {
 
         if idx != 64 { return Err(format!("Invalid fen string {}", fen)); }
 
+        if let Some(side) = fields.next() {
+            board.side_to_move = match side {
+                "w" => PieceColor::White,
+                "b" => PieceColor::Black,
+                _ => return Err(format!("Invalid side to move {}", side)),
+            };
+        }
+
+        if let Some(castling) = fields.next() {
+            board.castling = CastlingRights::from_fen(castling)?;
+        }
+
+        if let Some(ep) = fields.next() {
+            board.en_passant = match ep {
+                "-" => None,
+                _ => Some(square::parse_square(ep).ok_or(format!("Invalid en passant square {}", ep))?),
+            };
+        }
+
+        if let Some(halfmove) = fields.next() {
+            board.halfmove_clock = halfmove.parse().map_err(|_| format!("Invalid halfmove clock {}", halfmove))?;
+        }
+
+        if let Some(fullmove) = fields.next() {
+            board.fullmove_number = fullmove.parse().map_err(|_| format!("Invalid fullmove number {}", fullmove))?;
+        }
+
         Ok(board)
     }
 
+    /// Piece placement field of the FEN string.
+    pub fn placement_fen(&self) -> String {
+        let mut fen = String::new();
+        for row in 0..8 {
+            let mut empty = 0;
+            for col in 0..8 {
+                match self.cells[row*8+col].piece {
+                    Some(piece) => {
+                        if empty > 0 { fen.push_str(&empty.to_string()); }
+                        empty = 0;
+                        fen.push(piece.to_fen_char());
+                    },
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 { fen.push_str(&empty.to_string()); }
+            if row < 7 { fen.push('/'); }
+        }
+
+        fen
+    }
+
+    pub fn to_fen(&self) -> String {
+        format!("{} {} {} {} {} {}",
+            self.placement_fen(),
+            if self.side_to_move == PieceColor::White { "w" } else { "b" },
+            self.castling,
+            self.en_passant.map_or(String::from("-"), square::square_name),
+            self.halfmove_clock,
+            self.fullmove_number)
+    }
+
+    pub fn piece_at(&self, idx: usize) -> Option<Piece> {
+        self.cells[idx].piece
+    }
+
     pub fn make_move(&mut self, from: usize, to: usize) -> Result<(), String> {
         let valid_from = match from {
             _ if from >= 64 => return Err(String::from("Invalid from index {from}")),
@@ -69,6 +181,134 @@ impl Board {
 
         Ok(())
     }
+
+    /// Plays a move after checking that it is legal in the current position.
+    pub fn apply_move(&mut self, mv: Move) -> Result<(), String> {
+        if !self.is_legal(mv) {
+            return Err(format!("Illegal move {}", mv));
+        }
+
+        self.make(mv);
+        Ok(())
+    }
+
+    /// Plays a pseudo legal move, updating castling rights, en passant square
+    /// and clocks. The returned `Undo` takes the move back with `unmake`.
+    pub fn make(&mut self, mv: Move) -> Undo {
+        let piece = self.cells[mv.from].piece.expect("No piece on from square");
+        let mut captured = self.cells[mv.to].piece;
+        let undo_castling = self.castling;
+        let undo_en_passant = self.en_passant;
+        let undo_halfmove = self.halfmove_clock;
+
+        self.cells[mv.from].piece = None;
+
+        if piece.piece_type == PieceType::Pawn && Some(mv.to) == self.en_passant && captured.is_none() {
+            let captured_idx = if piece.color == PieceColor::White { mv.to + 8 } else { mv.to - 8 };
+            captured = self.cells[captured_idx].piece;
+            self.cells[captured_idx].piece = None;
+        }
+
+        self.cells[mv.to].piece = Some(match mv.promotion {
+            Some(piece_type) => Piece { piece_type, color: piece.color },
+            None => piece,
+        });
+
+        if piece.piece_type == PieceType::King && mv.from.abs_diff(mv.to) == 2 {
+            let (rook_from, rook_to) = if mv.to > mv.from { (mv.from + 3, mv.from + 1) } else { (mv.from - 4, mv.from - 1) };
+            self.cells[rook_to].piece = self.cells[rook_from].piece;
+            self.cells[rook_from].piece = None;
+        }
+
+        self.castling.0 &= movegen::CASTLING_MASK[mv.from] & movegen::CASTLING_MASK[mv.to];
+
+        self.en_passant = if piece.piece_type == PieceType::Pawn && mv.from.abs_diff(mv.to) == 16 {
+            Some((mv.from + mv.to) / 2)
+        } else {
+            None
+        };
+
+        if piece.piece_type == PieceType::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        if self.side_to_move == PieceColor::Black { self.fullmove_number += 1; }
+        self.side_to_move = self.side_to_move.opposite();
+
+        Undo {
+            captured,
+            castling: undo_castling,
+            en_passant: undo_en_passant,
+            halfmove_clock: undo_halfmove,
+        }
+    }
+
+    pub fn unmake(&mut self, mv: Move, undo: Undo) {
+        self.side_to_move = self.side_to_move.opposite();
+        if self.side_to_move == PieceColor::Black { self.fullmove_number -= 1; }
+
+        let moved = self.cells[mv.to].piece.expect("No piece on to square");
+        let piece = if mv.promotion.is_some() {
+            Piece { piece_type: PieceType::Pawn, color: moved.color }
+        } else {
+            moved
+        };
+
+        self.cells[mv.from].piece = Some(piece);
+        self.cells[mv.to].piece = None;
+
+        // The en passant square is always empty, so a pawn landing there
+        // diagonally captured en passant.
+        if piece.piece_type == PieceType::Pawn && Some(mv.to) == undo.en_passant
+            && square::col(mv.from) != square::col(mv.to) {
+            let captured_idx = if piece.color == PieceColor::White { mv.to + 8 } else { mv.to - 8 };
+            self.cells[captured_idx].piece = undo.captured;
+        } else {
+            self.cells[mv.to].piece = undo.captured;
+        }
+
+        if piece.piece_type == PieceType::King && mv.from.abs_diff(mv.to) == 2 {
+            let (rook_from, rook_to) = if mv.to > mv.from { (mv.from + 3, mv.from + 1) } else { (mv.from - 4, mv.from - 1) };
+            self.cells[rook_from].piece = self.cells[rook_to].piece;
+            self.cells[rook_to].piece = None;
+        }
+
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+    }
+
+    /// Passes the turn to the other side without moving, as searches do to
+    /// test whether a position is good even without a move.
+    pub fn make_null(&mut self) -> Undo {
+        let undo = Undo {
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+        };
+
+        self.en_passant = None;
+        self.halfmove_clock += 1;
+        if self.side_to_move == PieceColor::Black { self.fullmove_number += 1; }
+        self.side_to_move = self.side_to_move.opposite();
+        undo
+    }
+
+    pub fn unmake_null(&mut self, undo: Undo) {
+        self.side_to_move = self.side_to_move.opposite();
+        if self.side_to_move == PieceColor::Black { self.fullmove_number -= 1; }
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 use std::fmt;
@@ -82,7 +322,7 @@ impl fmt::Display for Board {
             // print new line for each row
             if i%8 == 0 { 
                 if i < 8 {
-                    writeln!(f, "")?; 
+                    writeln!(f)?; 
                 } else {
                     writeln!(f, " {}", 8-(i/8)+1)?; 
                 }
@@ -104,6 +344,8 @@ mod tests {
     fn new_board_creation() {
         let mut board = Board {
             cells: [Default::default(); 64],
+            castling: CastlingRights::all(),
+            ..Board::empty()
         };
 
         for (i, cell) in board.cells.iter_mut().enumerate() {
@@ -154,6 +396,7 @@ mod tests {
         // Thanks to http://bernd.bplaced.net/fengenerator/fengenerator.html
         let mut board = Board {
             cells: [Default::default(); 64],
+            ..Board::empty()
         };
 
         for (i, cell) in board.cells.iter_mut().enumerate() {
@@ -211,6 +454,70 @@ mod tests {
         assert_eq!(board.cells[36].piece, Piece::from_fen_char('k'));
     }
 
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+            "8/8/1R5P/8/8/3Pk1K1/r7/8 b - - 12 40",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid side to move")]
+    fn creation_of_board_from_invalid_side_to_move() {
+        Board::from_fen("8/8/8/8/8/8/8/8 x - - 0 1").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid fen string")]
+    fn creation_of_board_from_overflowing_fen_string() {
+        Board::from_fen("8/8/8/8/8/8/8/8/8").unwrap();
+    }
+
+    #[test]
+    fn make_and_unmake_special_moves() {
+        let fen = "r3k2r/pppq1ppp/8/3Pp3/8/8/PPP2pPP/R3K2R w KQkq e6 0 10";
+        let mut board = Board::from_fen(fen).unwrap();
+
+        for mv in ["e1g1", "e1c1", "d5e6", "a1a8"] {
+            let mv = Move::from_uci(mv).unwrap();
+            let undo = board.make(mv);
+            board.unmake(mv, undo);
+            assert_eq!(board.to_fen(), fen);
+        }
+
+        board.make(Move::from_uci("e1g1").unwrap());
+        assert_eq!(board.to_fen(), "r3k2r/pppq1ppp/8/3Pp3/8/8/PPP2pPP/R4RK1 b kq - 1 10");
+
+        let mut board = Board::from_fen(fen).unwrap();
+        board.make(Move::from_uci("d5e6").unwrap());
+        assert_eq!(board.to_fen(), "r3k2r/pppq1ppp/4P3/8/8/8/PPP2pPP/R3K2R b KQkq - 0 10");
+        board.make(Move::with_promotion(53, 62, PieceType::Knight));
+        assert_eq!(board.to_fen(), "r3k2r/pppq1ppp/4P3/8/8/8/PPP3PP/R3K1nR w KQkq - 0 11");
+    }
+
+    #[test]
+    fn null_move_passes_the_turn() {
+        let mut board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let fen = board.to_fen();
+        let undo = board.make_null();
+        assert_eq!(board.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 b - - 1 2");
+        board.unmake_null(undo);
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    #[test]
+    fn apply_move_rejects_illegal_moves() {
+        let mut board = Board::new();
+        assert!(board.apply_move(Move::from_uci("e2e5").unwrap()).is_err());
+        board.apply_move(Move::from_uci("e2e4").unwrap()).unwrap();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    }
+
     #[test]
     #[should_panic(expected = "Invalid from index")]
     fn make_move_with_invalid_from_index() {
@@ -0,0 +1,75 @@
+use std::fmt::Display;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CastlingRights(pub u8);
+
+impl CastlingRights {
+    pub const WHITE_KINGSIDE: u8 = 1;
+    pub const WHITE_QUEENSIDE: u8 = 2;
+    pub const BLACK_KINGSIDE: u8 = 4;
+    pub const BLACK_QUEENSIDE: u8 = 8;
+
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    pub fn all() -> Self {
+        Self(Self::WHITE_KINGSIDE | Self::WHITE_QUEENSIDE | Self::BLACK_KINGSIDE | Self::BLACK_QUEENSIDE)
+    }
+
+    pub fn has(&self, right: u8) -> bool {
+        self.0 & right != 0
+    }
+
+    pub fn from_fen(field: &str) -> Result<Self, String> {
+        if field == "-" { return Ok(Self::none()); }
+
+        let mut rights = Self::none();
+        for c in field.chars() {
+            rights.0 |= match c {
+                'K' => Self::WHITE_KINGSIDE,
+                'Q' => Self::WHITE_QUEENSIDE,
+                'k' => Self::BLACK_KINGSIDE,
+                'q' => Self::BLACK_QUEENSIDE,
+                _ => return Err(format!("Invalid castling rights {field}")),
+            };
+        }
+
+        Ok(rights)
+    }
+}
+
+impl Display for CastlingRights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == 0 { return write!(f, "-"); }
+
+        for (right, c) in [
+            (Self::WHITE_KINGSIDE, 'K'),
+            (Self::WHITE_QUEENSIDE, 'Q'),
+            (Self::BLACK_KINGSIDE, 'k'),
+            (Self::BLACK_QUEENSIDE, 'q'),
+        ] {
+            if self.has(right) { write!(f, "{c}")?; }
+        }
+
+        std::fmt::Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn castling_rights_round_trip() {
+        for s in ["-", "KQkq", "Kq", "Q", "kq"] {
+            assert_eq!(CastlingRights::from_fen(s).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid castling rights")]
+    fn invalid_castling_rights() {
+        CastlingRights::from_fen("KX").unwrap();
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use super::piece::*;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CellColor {
     White,
     Black,
@@ -12,23 +12,15 @@ impl CellColor  {
     pub fn from_index(idx: usize) -> Option<CellColor> {
         if idx > 63 { return None; }
 
-        if idx%2 == 0 {
-            if (idx/8)%2==0 { 
-                return Some(CellColor::White);
-            } else {
-                return Some(CellColor::Black);
-            }
-        } else { 
-            if (idx/8)%2==0 { 
-                return Some(CellColor::Black);
-            } else {
-                return Some(CellColor::White);
-            }
+        if (idx/8 + idx%8).is_multiple_of(2) {
+            Some(CellColor::White)
+        } else {
+            Some(CellColor::Black)
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct BoardCell {
     pub color: CellColor,
     pub piece: Option<Piece>,
@@ -0,0 +1,59 @@
+use super::*;
+
+/// The positions of a game so far by their Zobrist keys, the latest last,
+/// to recognise repetitions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameHistory {
+    keys: Vec<u64>,
+}
+
+impl GameHistory {
+    /// A history starting at `board`.
+    pub fn new(board: &Board) -> Self {
+        Self { keys: vec![board.zobrist_key()] }
+    }
+
+    /// Records `board`, the position after the latest move.
+    pub fn push(&mut self, board: &Board) {
+        self.keys.push(board.zobrist_key());
+    }
+
+    pub fn keys(&self) -> &[u64] {
+        &self.keys
+    }
+
+    /// How often `board` occurred, counting only the positions since the
+    /// last capture or pawn move as no earlier one can come back.
+    pub fn count(&self, board: &Board) -> usize {
+        let key = board.zobrist_key();
+        self.keys.iter().rev()
+            .take(board.halfmove_clock as usize + 1)
+            .filter(|&&earlier| earlier == key)
+            .count()
+    }
+
+    /// Whether `board`, the latest position, has now occurred three times.
+    pub fn is_threefold(&self, board: &Board) -> bool {
+        self.count(board) >= 3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knights_shuffling_repeat_the_start_three_times() {
+        let mut board = Board::new();
+        let mut history = GameHistory::new(&board);
+        for _ in 0..2 {
+            assert!(!history.is_threefold(&board));
+            for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                board.make(Move::from_uci(uci).unwrap());
+                history.push(&board);
+            }
+        }
+        assert_eq!(history.count(&board), 3);
+        assert!(history.is_threefold(&board));
+    }
+}
@@ -0,0 +1,384 @@
+use super::castling::*;
+use super::piece::*;
+use super::square::*;
+use super::*;
+
+// Move generation uses the classic 10x12 mailbox: stepping off the 8x8 board
+// lands on a -1 sentinel instead of wrapping around to the next row.
+const MAILBOX: [i32; 120] = [
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    -1,  0,  1,  2,  3,  4,  5,  6,  7, -1,
+    -1,  8,  9, 10, 11, 12, 13, 14, 15, -1,
+    -1, 16, 17, 18, 19, 20, 21, 22, 23, -1,
+    -1, 24, 25, 26, 27, 28, 29, 30, 31, -1,
+    -1, 32, 33, 34, 35, 36, 37, 38, 39, -1,
+    -1, 40, 41, 42, 43, 44, 45, 46, 47, -1,
+    -1, 48, 49, 50, 51, 52, 53, 54, 55, -1,
+    -1, 56, 57, 58, 59, 60, 61, 62, 63, -1,
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+];
+
+const MAILBOX64: [i32; 64] = [
+    21, 22, 23, 24, 25, 26, 27, 28,
+    31, 32, 33, 34, 35, 36, 37, 38,
+    41, 42, 43, 44, 45, 46, 47, 48,
+    51, 52, 53, 54, 55, 56, 57, 58,
+    61, 62, 63, 64, 65, 66, 67, 68,
+    71, 72, 73, 74, 75, 76, 77, 78,
+    81, 82, 83, 84, 85, 86, 87, 88,
+    91, 92, 93, 94, 95, 96, 97, 98,
+];
+
+pub const KNIGHT_OFFSETS: [i32; 8] = [-21, -19, -12, -8, 8, 12, 19, 21];
+pub const BISHOP_OFFSETS: [i32; 4] = [-11, -9, 9, 11];
+pub const ROOK_OFFSETS: [i32; 4] = [-10, -1, 1, 10];
+pub const KING_OFFSETS: [i32; 8] = [-11, -10, -9, -1, 1, 9, 10, 11];
+
+const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+/// Castling rights that survive a move from or to each cell.
+pub const CASTLING_MASK: [u8; 64] = {
+    let mut mask = [15u8; 64];
+    mask[0] = 15 & !CastlingRights::BLACK_QUEENSIDE;
+    mask[4] = 15 & !(CastlingRights::BLACK_KINGSIDE | CastlingRights::BLACK_QUEENSIDE);
+    mask[7] = 15 & !CastlingRights::BLACK_KINGSIDE;
+    mask[56] = 15 & !CastlingRights::WHITE_QUEENSIDE;
+    mask[60] = 15 & !(CastlingRights::WHITE_KINGSIDE | CastlingRights::WHITE_QUEENSIDE);
+    mask[63] = 15 & !CastlingRights::WHITE_KINGSIDE;
+    mask
+};
+
+/// Index of the cell `offset` mailbox steps away from `idx`, if it is on the board.
+pub fn step(idx: usize, offset: i32) -> Option<usize> {
+    let target = MAILBOX[(MAILBOX64[idx] + offset) as usize];
+    if target < 0 { None } else { Some(target as usize) }
+}
+
+fn pawn_push(color: PieceColor) -> i32 {
+    if color == PieceColor::White { -10 } else { 10 }
+}
+
+fn is_promotion_row(idx: usize, color: PieceColor) -> bool {
+    row(idx) == if color == PieceColor::White { 0 } else { 7 }
+}
+
+impl Board {
+    pub fn king_square(&self, color: PieceColor) -> Option<usize> {
+        self.cells.iter().position(|cell| {
+            cell.piece == Some(Piece { piece_type: PieceType::King, color })
+        })
+    }
+
+    fn has_piece(&self, idx: usize, color: PieceColor, types: &[PieceType]) -> bool {
+        match self.cells[idx].piece {
+            Some(piece) => piece.color == color && types.contains(&piece.piece_type),
+            None => false,
+        }
+    }
+
+    pub fn is_square_attacked(&self, idx: usize, by: PieceColor) -> bool {
+        // a pawn attacks diagonally forward, so look for attackers one row behind
+        let pawn_offsets = if by == PieceColor::White { [9, 11] } else { [-9, -11] };
+        for offset in pawn_offsets {
+            if let Some(from) = step(idx, offset) {
+                if self.has_piece(from, by, &[PieceType::Pawn]) { return true; }
+            }
+        }
+
+        for offset in KNIGHT_OFFSETS {
+            if let Some(from) = step(idx, offset) {
+                if self.has_piece(from, by, &[PieceType::Knight]) { return true; }
+            }
+        }
+
+        for offset in KING_OFFSETS {
+            if let Some(from) = step(idx, offset) {
+                if self.has_piece(from, by, &[PieceType::King]) { return true; }
+            }
+        }
+
+        for (offsets, sliders) in [
+            (&BISHOP_OFFSETS, [PieceType::Bishop, PieceType::Queen]),
+            (&ROOK_OFFSETS, [PieceType::Rook, PieceType::Queen]),
+        ] {
+            for &offset in offsets {
+                let mut current = idx;
+                while let Some(next) = step(current, offset) {
+                    if self.cells[next].piece.is_some() {
+                        if self.has_piece(next, by, &sliders) { return true; }
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Cells of all pieces of `by` attacking `idx`, ignoring pins.
+    pub fn attackers(&self, idx: usize, by: PieceColor) -> Vec<usize> {
+        let mut attackers = Vec::new();
+        let pawn_offsets = if by == PieceColor::White { [9, 11] } else { [-9, -11] };
+        for (offsets, types) in [
+            (&pawn_offsets[..], [PieceType::Pawn; 2]),
+            (&KNIGHT_OFFSETS[..], [PieceType::Knight; 2]),
+            (&KING_OFFSETS[..], [PieceType::King; 2]),
+        ] {
+            for &offset in offsets {
+                if let Some(from) = step(idx, offset) {
+                    if self.has_piece(from, by, &types) { attackers.push(from); }
+                }
+            }
+        }
+
+        for (offsets, sliders) in [
+            (&BISHOP_OFFSETS, [PieceType::Bishop, PieceType::Queen]),
+            (&ROOK_OFFSETS, [PieceType::Rook, PieceType::Queen]),
+        ] {
+            for &offset in offsets {
+                let mut current = idx;
+                while let Some(next) = step(current, offset) {
+                    if self.cells[next].piece.is_some() {
+                        if self.has_piece(next, by, &sliders) { attackers.push(next); }
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+
+        attackers
+    }
+
+    /// The piece `mv` takes, including a pawn captured en passant.
+    pub fn captured_piece(&self, mv: Move) -> Option<Piece> {
+        if let Some(piece) = self.piece_at(mv.to) { return Some(piece); }
+
+        let en_passant = matches!(self.piece_at(mv.from), Some(piece) if piece.piece_type == PieceType::Pawn)
+            && Some(mv.to) == self.en_passant
+            && col(mv.from) != col(mv.to);
+        en_passant.then_some(Piece { piece_type: PieceType::Pawn, color: self.side_to_move.opposite() })
+    }
+
+    pub fn is_in_check(&self, color: PieceColor) -> bool {
+        match self.king_square(color) {
+            Some(king) => self.is_square_attacked(king, color.opposite()),
+            None => false,
+        }
+    }
+
+    /// Whether the side to move is in check.
+    pub fn in_check(&self) -> bool {
+        self.is_in_check(self.side_to_move)
+    }
+
+    /// Appends all moves of the side to move that follow the piece movement
+    /// rules but may leave the own king in check.
+    pub fn pseudo_legal_moves(&self, moves: &mut Vec<Move>) {
+        let color = self.side_to_move;
+        for from in 0..64 {
+            let piece = match self.cells[from].piece {
+                Some(piece) if piece.color == color => piece,
+                _ => continue,
+            };
+
+            match piece.piece_type {
+                PieceType::Pawn => self.pawn_moves(from, color, moves),
+                PieceType::Knight => self.step_moves(from, color, &KNIGHT_OFFSETS, false, moves),
+                PieceType::Bishop => self.step_moves(from, color, &BISHOP_OFFSETS, true, moves),
+                PieceType::Rook => self.step_moves(from, color, &ROOK_OFFSETS, true, moves),
+                PieceType::Queen => self.step_moves(from, color, &KING_OFFSETS, true, moves),
+                PieceType::King => {
+                    self.step_moves(from, color, &KING_OFFSETS, false, moves);
+                    self.castling_moves(from, color, moves);
+                },
+            }
+        }
+    }
+
+    fn step_moves(&self, from: usize, color: PieceColor, offsets: &[i32], slides: bool, moves: &mut Vec<Move>) {
+        for &offset in offsets {
+            let mut current = from;
+            while let Some(to) = step(current, offset) {
+                match self.cells[to].piece {
+                    Some(piece) => {
+                        if piece.color != color { moves.push(Move::new(from, to)); }
+                        break;
+                    },
+                    None => moves.push(Move::new(from, to)),
+                }
+                if !slides { break; }
+                current = to;
+            }
+        }
+    }
+
+    fn push_pawn_move(from: usize, to: usize, color: PieceColor, moves: &mut Vec<Move>) {
+        if is_promotion_row(to, color) {
+            for promotion in PROMOTIONS {
+                moves.push(Move::with_promotion(from, to, promotion));
+            }
+        } else {
+            moves.push(Move::new(from, to));
+        }
+    }
+
+    fn pawn_moves(&self, from: usize, color: PieceColor, moves: &mut Vec<Move>) {
+        let push = pawn_push(color);
+        if let Some(to) = step(from, push) {
+            if self.cells[to].piece.is_none() {
+                Self::push_pawn_move(from, to, color, moves);
+
+                let start_row = if color == PieceColor::White { 6 } else { 1 };
+                if row(from) == start_row {
+                    if let Some(to) = step(to, push) {
+                        if self.cells[to].piece.is_none() { moves.push(Move::new(from, to)); }
+                    }
+                }
+            }
+        }
+
+        for side in [-1, 1] {
+            if let Some(to) = step(from, push + side) {
+                match self.cells[to].piece {
+                    Some(piece) if piece.color != color => Self::push_pawn_move(from, to, color, moves),
+                    None if self.en_passant == Some(to) => moves.push(Move::new(from, to)),
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    fn castling_moves(&self, from: usize, color: PieceColor, moves: &mut Vec<Move>) {
+        let (king_start, kingside, queenside) = match color {
+            PieceColor::White => (60, CastlingRights::WHITE_KINGSIDE, CastlingRights::WHITE_QUEENSIDE),
+            PieceColor::Black => (4, CastlingRights::BLACK_KINGSIDE, CastlingRights::BLACK_QUEENSIDE),
+        };
+        if from != king_start { return; }
+
+        let rook = Some(Piece { piece_type: PieceType::Rook, color });
+        let enemy = color.opposite();
+
+        if self.castling.has(kingside) && self.cells[from + 3].piece == rook
+            && self.cells[from + 1].piece.is_none() && self.cells[from + 2].piece.is_none()
+            && !self.is_square_attacked(from, enemy)
+            && !self.is_square_attacked(from + 1, enemy)
+            && !self.is_square_attacked(from + 2, enemy) {
+            moves.push(Move::new(from, from + 2));
+        }
+
+        if self.castling.has(queenside) && self.cells[from - 4].piece == rook
+            && self.cells[from - 1].piece.is_none() && self.cells[from - 2].piece.is_none()
+            && self.cells[from - 3].piece.is_none()
+            && !self.is_square_attacked(from, enemy)
+            && !self.is_square_attacked(from - 1, enemy)
+            && !self.is_square_attacked(from - 2, enemy) {
+            moves.push(Move::new(from, from - 2));
+        }
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut pseudo = Vec::with_capacity(64);
+        self.pseudo_legal_moves(&mut pseudo);
+
+        let mut board = self.clone();
+        let color = self.side_to_move;
+        pseudo.retain(|&mv| {
+            let undo = board.make(mv);
+            let legal = !board.is_in_check(color);
+            board.unmake(mv, undo);
+            legal
+        });
+
+        pseudo
+    }
+
+    pub fn is_legal(&self, mv: Move) -> bool {
+        self.legal_moves().contains(&mv)
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check() && self.legal_moves().is_empty()
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check() && self.legal_moves().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn perft(board: &mut Board, depth: u32) -> u64 {
+        if depth == 0 { return 1; }
+
+        let mut nodes = 0;
+        for mv in board.legal_moves() {
+            let undo = board.make(mv);
+            nodes += perft(board, depth - 1);
+            board.unmake(mv, undo);
+        }
+        nodes
+    }
+
+    #[test]
+    fn attackers_of_a_square() {
+        let board = Board::from_fen("4k3/8/2n5/3p4/4P3/5B2/8/3QK3 w - - 0 1").unwrap();
+        let d5 = parse_square("d5").unwrap();
+        let mut white = board.attackers(d5, PieceColor::White);
+        white.sort();
+        // the bishop on f3 is blocked by the pawn on e4
+        assert_eq!(white, vec![parse_square("e4").unwrap(), parse_square("d1").unwrap()]);
+        assert_eq!(board.attackers(d5, PieceColor::Black), vec![]);
+        assert_eq!(board.attackers(parse_square("e4").unwrap(), PieceColor::Black), vec![parse_square("d5").unwrap()]);
+    }
+
+    #[test]
+    fn captured_piece_en_passant() {
+        let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let pawn = Some(Piece { piece_type: PieceType::Pawn, color: PieceColor::Black });
+        assert_eq!(board.captured_piece(Move::from_uci("e5d6").unwrap()), pawn);
+        assert_eq!(board.captured_piece(Move::from_uci("e5e6").unwrap()), None);
+    }
+
+    #[test]
+    fn perft_start_position() {
+        let mut board = Board::new();
+        assert_eq!(perft(&mut board, 1), 20);
+        assert_eq!(perft(&mut board, 2), 400);
+        assert_eq!(perft(&mut board, 3), 8902);
+        assert_eq!(board, Board::new());
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(perft(&mut board, 1), 48);
+        assert_eq!(perft(&mut board, 2), 2039);
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    #[test]
+    fn perft_en_passant_and_promotions() {
+        let mut board = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(&mut board, 3), 2812);
+
+        let mut board = Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        assert_eq!(perft(&mut board, 2), 264);
+    }
+
+    #[test]
+    fn detects_checkmate_and_stalemate() {
+        let mate = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert!(mate.is_checkmate());
+
+        let stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(stalemate.is_stalemate());
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt::Display;
+
+use super::piece::*;
+use super::square::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: usize,
+    pub to: usize,
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    pub fn new(from: usize, to: usize) -> Self {
+        Self { from, to, promotion: None }
+    }
+
+    pub fn with_promotion(from: usize, to: usize, promotion: PieceType) -> Self {
+        Self { from, to, promotion: Some(promotion) }
+    }
+
+    /// Parses a move in coordinate notation such as `e2e4` or `e7e8q`.
+    pub fn from_uci(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if !(4..=5).contains(&s.len()) || !s.is_ascii() {
+            return Err(format!("Invalid move {s}"));
+        }
+
+        let from = parse_square(&s[0..2]).ok_or(format!("Invalid from square {}", &s[0..2]))?;
+        let to = parse_square(&s[2..4]).ok_or(format!("Invalid to square {}", &s[2..4]))?;
+        let promotion = match s[4..].chars().next().map(|c| c.to_ascii_lowercase()) {
+            None => None,
+            Some('q') => Some(PieceType::Queen),
+            Some('r') => Some(PieceType::Rook),
+            Some('b') => Some(PieceType::Bishop),
+            Some('n') => Some(PieceType::Knight),
+            Some(c) => return Err(format!("Invalid promotion piece {c}")),
+        };
+
+        Ok(Self { from, to, promotion })
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", square_name(self.from), square_name(self.to))?;
+        if let Some(promotion) = self.promotion {
+            let piece = Piece { piece_type: promotion, color: PieceColor::Black };
+            write!(f, "{}", piece.to_fen_char())?;
+        }
+
+        std::fmt::Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uci_round_trip() {
+        for s in ["e2e4", "g1f3", "e7e8q", "a2a1n"] {
+            assert_eq!(Move::from_uci(s).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn invalid_uci_moves() {
+        assert!(Move::from_uci("e2").is_err());
+        assert!(Move::from_uci("e2e9").is_err());
+        assert!(Move::from_uci("e7e8k").is_err());
+    }
+}
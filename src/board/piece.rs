@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PieceType {
     King,
     Queen,
@@ -10,13 +10,51 @@ pub enum PieceType {
     Pawn,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PieceColor {
     White,
     Black,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+impl PieceColor {
+    pub fn opposite(&self) -> Self {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            PieceColor::White => 0,
+            PieceColor::Black => 1,
+        }
+    }
+}
+
+impl PieceType {
+    pub const ALL: [PieceType; 6] = [
+        PieceType::King,
+        PieceType::Queen,
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Pawn,
+    ];
+
+    pub fn index(&self) -> usize {
+        match self {
+            PieceType::King => 0,
+            PieceType::Queen => 1,
+            PieceType::Rook => 2,
+            PieceType::Bishop => 3,
+            PieceType::Knight => 4,
+            PieceType::Pawn => 5,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: PieceColor,
@@ -40,6 +78,19 @@ impl Piece {
             _ => None,
         }
     }
+
+    pub fn to_fen_char(&self) -> char {
+        let c = match self.piece_type {
+            PieceType::King => 'k',
+            PieceType::Queen => 'q',
+            PieceType::Rook => 'r',
+            PieceType::Bishop => 'b',
+            PieceType::Knight => 'n',
+            PieceType::Pawn => 'p',
+        };
+
+        if self.color == PieceColor::White { c.to_ascii_uppercase() } else { c }
+    }
 }
 
 impl Display for Piece {
@@ -88,6 +139,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn piece_to_fen_char_round_trip() {
+        for c in ['K', 'k', 'Q', 'q', 'R', 'r', 'B', 'b', 'N', 'n', 'P', 'p'] {
+            assert_eq!(Piece::from_fen_char(c).unwrap().to_fen_char(), c);
+        }
+    }
+
     #[test]
     fn piece_from_unsupported_fen_char() {
         assert_eq!(Piece::from_fen_char('A'), None);
@@ -0,0 +1,87 @@
+// Cells are indexed from the top left corner of the printed board, so index 0
+// is a8 and index 63 is h1.
+
+pub fn row(idx: usize) -> usize {
+    idx / 8
+}
+
+pub fn col(idx: usize) -> usize {
+    idx % 8
+}
+
+/// Rank of the cell as written in algebraic notation (1-8).
+pub fn rank(idx: usize) -> usize {
+    8 - row(idx)
+}
+
+pub fn from_row_col(row: usize, col: usize) -> usize {
+    row * 8 + col
+}
+
+/// Index of the cell `dr` rows and `dc` columns away, if it is on the board.
+pub fn offset(idx: usize, dr: i32, dc: i32) -> Option<usize> {
+    let r = row(idx) as i32 + dr;
+    let c = col(idx) as i32 + dc;
+    if (0..8).contains(&r) && (0..8).contains(&c) {
+        Some(from_row_col(r as usize, c as usize))
+    } else {
+        None
+    }
+}
+
+pub fn file_char(idx: usize) -> char {
+    (b'a' + col(idx) as u8) as char
+}
+
+pub fn rank_char(idx: usize) -> char {
+    (b'0' + rank(idx) as u8) as char
+}
+
+pub fn square_name(idx: usize) -> String {
+    format!("{}{}", file_char(idx), rank_char(idx))
+}
+
+pub fn parse_square(name: &str) -> Option<usize> {
+    let mut chars = name.chars();
+    let file = chars.next()?.to_ascii_lowercase();
+    let rank = chars.next()?;
+    if chars.next().is_some() { return None; }
+
+    if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+
+    let col = file as usize - 'a' as usize;
+    let rank = rank as usize - '0' as usize;
+    Some(from_row_col(8 - rank, col))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_names_round_trip() {
+        assert_eq!(square_name(0), "a8");
+        assert_eq!(square_name(63), "h1");
+        assert_eq!(square_name(52), "e2");
+        for i in 0..64 {
+            assert_eq!(parse_square(&square_name(i)), Some(i));
+        }
+    }
+
+    #[test]
+    fn invalid_square_names() {
+        assert_eq!(parse_square("i1"), None);
+        assert_eq!(parse_square("a9"), None);
+        assert_eq!(parse_square("a"), None);
+        assert_eq!(parse_square("a1b"), None);
+    }
+
+    #[test]
+    fn offsets_stay_on_board() {
+        assert_eq!(offset(0, -1, 0), None);
+        assert_eq!(offset(7, 0, 1), None);
+        assert_eq!(offset(52, -2, 0), parse_square("e4"));
+    }
+}
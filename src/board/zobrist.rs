@@ -0,0 +1,144 @@
+use super::piece::*;
+use super::*;
+
+// Keys are generated at compile time with splitmix64 so they are the same
+// on every run, which keeps hashes stored on disk valid.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+struct Keys {
+    pieces: [[u64; 64]; 12],
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+    black_to_move: u64,
+}
+
+const KEYS: Keys = {
+    let mut keys = Keys { pieces: [[0; 64]; 12], castling: [0; 16], en_passant: [0; 8], black_to_move: 0 };
+    let mut state = 0x1234_5678_9ABC_DEF0;
+
+    let mut piece = 0;
+    while piece < 12 {
+        let mut idx = 0;
+        while idx < 64 {
+            let (next, key) = splitmix64(state);
+            state = next;
+            keys.pieces[piece][idx] = key;
+            idx += 1;
+        }
+        piece += 1;
+    }
+
+    let mut i = 0;
+    while i < 16 {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys.castling[i] = key;
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < 8 {
+        let (next, key) = splitmix64(state);
+        state = next;
+        keys.en_passant[i] = key;
+        i += 1;
+    }
+
+    keys.black_to_move = splitmix64(state).1;
+    keys
+};
+
+pub fn piece_key(piece: Piece, idx: usize) -> u64 {
+    KEYS.pieces[piece.color.index() * 6 + piece.piece_type.index()][idx]
+}
+
+pub fn castling_key(castling: CastlingRights) -> u64 {
+    KEYS.castling[castling.0 as usize & 15]
+}
+
+pub fn en_passant_key(idx: usize) -> u64 {
+    KEYS.en_passant[square::col(idx)]
+}
+
+pub fn black_to_move_key() -> u64 {
+    KEYS.black_to_move
+}
+
+impl Board {
+    /// Whether a pawn of the side to move stands next to the pawn which just
+    /// made a double step, so that en passant capture is possible.
+    pub fn en_passant_capturable(&self) -> bool {
+        let ep = match self.en_passant {
+            Some(ep) => ep,
+            None => return false,
+        };
+
+        let color = self.side_to_move;
+        let behind = if color == PieceColor::White { 1 } else { -1 };
+        let pawn = Some(Piece { piece_type: PieceType::Pawn, color });
+        [-1, 1].iter().any(|&dc| {
+            square::offset(ep, behind, dc).is_some_and(|idx| self.cells[idx].piece == pawn)
+        })
+    }
+
+    /// Zobrist hash of the position: piece placement, side to move, castling
+    /// rights and en passant square when a capture there is possible. The
+    /// move clocks are not part of it.
+    pub fn zobrist_key(&self) -> u64 {
+        let mut key = 0;
+        for (idx, cell) in self.cells.iter().enumerate() {
+            if let Some(piece) = cell.piece {
+                key ^= piece_key(piece, idx);
+            }
+        }
+
+        key ^= castling_key(self.castling);
+        if let Some(ep) = self.en_passant.filter(|_| self.en_passant_capturable()) {
+            key ^= en_passant_key(ep);
+        }
+        if self.side_to_move == PieceColor::Black { key ^= black_to_move_key(); }
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transpositions_have_the_same_key() {
+        let mut a = Board::new();
+        for mv in ["g1f3", "g8f6", "b1c3"] { a.make(Move::from_uci(mv).unwrap()); }
+        let mut b = Board::new();
+        for mv in ["b1c3", "g8f6", "g1f3"] { b.make(Move::from_uci(mv).unwrap()); }
+
+        assert_eq!(a.zobrist_key(), b.zobrist_key());
+        assert_ne!(a.zobrist_key(), Board::new().zobrist_key());
+    }
+
+    #[test]
+    fn en_passant_square_counts_only_when_capturable() {
+        let a = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2").unwrap();
+        let b = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        assert_eq!(a.zobrist_key(), b.zobrist_key());
+
+        let c = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let d = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_ne!(c.zobrist_key(), d.zobrist_key());
+    }
+
+    #[test]
+    fn key_ignores_move_clocks() {
+        let a = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let b = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 30 60").unwrap();
+        let c = Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(a.zobrist_key(), b.zobrist_key());
+        assert_ne!(a.zobrist_key(), c.zobrist_key());
+    }
+}
@@ -0,0 +1,402 @@
+mod random64;
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::board::*;
+use crate::board::castling::*;
+use crate::board::piece::*;
+use crate::board::square::*;
+use crate::rng::*;
+use crate::strategy::*;
+
+use random64::RANDOM64;
+
+// Kinds of the Random64 array: black pawn, white pawn, black knight, ...
+fn polyglot_kind(piece: Piece) -> usize {
+    let role = match piece.piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    };
+    2 * role + if piece.color == PieceColor::White { 1 } else { 0 }
+}
+
+// Polyglot numbers squares from a1 upwards, our cells go from a8.
+fn polyglot_square(idx: usize) -> usize {
+    (7 - row(idx)) * 8 + col(idx)
+}
+
+fn cell_of(polyglot_square: u16) -> usize {
+    from_row_col(7 - (polyglot_square / 8) as usize, (polyglot_square % 8) as usize)
+}
+
+/// Hash of the position as used to look it up in Polyglot books.
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for (idx, cell) in board.cells.iter().enumerate() {
+        if let Some(piece) = cell.piece {
+            key ^= RANDOM64[64 * polyglot_kind(piece) + polyglot_square(idx)];
+        }
+    }
+
+    for (i, right) in [
+        CastlingRights::WHITE_KINGSIDE,
+        CastlingRights::WHITE_QUEENSIDE,
+        CastlingRights::BLACK_KINGSIDE,
+        CastlingRights::BLACK_QUEENSIDE,
+    ].into_iter().enumerate() {
+        if board.castling.has(right) { key ^= RANDOM64[768 + i]; }
+    }
+
+    if board.en_passant_capturable() {
+        key ^= RANDOM64[772 + col(board.en_passant.unwrap())];
+    }
+    if board.side_to_move == PieceColor::White {
+        key ^= RANDOM64[780];
+    }
+    key
+}
+
+/// One 16 byte record of a Polyglot book.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    /// Move in Polyglot encoding, see `decode_move`.
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub const SIZE: usize = 16;
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// Turns a Polyglot move into a move on `board`. Polyglot writes castling as
+/// the king taking its own rook.
+pub fn decode_move(board: &Board, raw: u16) -> Move {
+    let from = cell_of((raw >> 6) & 63);
+    let mut to = cell_of(raw & 63);
+    let promotion = match (raw >> 12) & 7 {
+        1 => Some(PieceType::Knight),
+        2 => Some(PieceType::Bishop),
+        3 => Some(PieceType::Rook),
+        4 => Some(PieceType::Queen),
+        _ => None,
+    };
+
+    let king = matches!(board.piece_at(from), Some(piece) if piece.piece_type == PieceType::King);
+    let own_rook = matches!(board.piece_at(to), Some(piece)
+        if piece.piece_type == PieceType::Rook && Some(piece.color) == board.piece_at(from).map(|king| king.color));
+    if king && own_rook {
+        to = if to > from { from + 2 } else { from - 2 };
+    }
+    Move { from, to, promotion }
+}
+
+pub fn encode_move(board: &Board, mv: Move) -> u16 {
+    let mut to = mv.to;
+    let king = matches!(board.piece_at(mv.from), Some(piece) if piece.piece_type == PieceType::King);
+    if king && mv.from.abs_diff(mv.to) == 2 {
+        to = if mv.to > mv.from { mv.from + 3 } else { mv.from - 4 };
+    }
+    let promotion = match mv.promotion {
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    promotion << 12 | (polyglot_square(mv.from) as u16) << 6 | polyglot_square(to) as u16
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BookMove {
+    pub mv: Move,
+    pub weight: u16,
+}
+
+/// An opening book in the Polyglot `.bin` format: records sorted by key,
+/// looked up by binary search.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PolyglotBook {
+    entries: Vec<BookEntry>,
+}
+
+impl PolyglotBook {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Unable to read {}. Error {e}", path.display()))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.len().is_multiple_of(BookEntry::SIZE) {
+            return Err(String::from("Book size is not a multiple of 16 bytes"));
+        }
+
+        let entries: Vec<BookEntry> = bytes.chunks_exact(BookEntry::SIZE).map(BookEntry::from_bytes).collect();
+        if entries.windows(2).any(|pair| pair[0].key > pair[1].key) {
+            return Err(String::from("Book entries are not sorted by key"));
+        }
+        Ok(Self { entries })
+    }
+
+    /// A book of the given entries, sorted as the format requires.
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight)));
+        Self { entries }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(|entry| entry.to_bytes()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Legal book moves in the position, highest weight first.
+    pub fn moves(&self, board: &Board) -> Vec<BookMove> {
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let mut moves: Vec<BookMove> = self.entries[start..].iter()
+            .take_while(|entry| entry.key == key)
+            .map(|entry| BookMove { mv: decode_move(board, entry.raw_move), weight: entry.weight })
+            .filter(|book_move| board.is_legal(book_move.mv))
+            .collect();
+        moves.sort_by_key(|book_move| std::cmp::Reverse(book_move.weight));
+        moves
+    }
+
+    pub fn best_move(&self, board: &Board) -> Option<Move> {
+        self.moves(board).first().map(|book_move| book_move.mv)
+    }
+
+    /// A book move chosen with probability proportional to its weight.
+    pub fn weighted_move(&self, board: &Board, rng: &mut Rng) -> Option<Move> {
+        let moves = self.moves(board);
+        let total: u64 = moves.iter().map(|book_move| book_move.weight as u64).sum();
+        if total == 0 { return moves.first().map(|book_move| book_move.mv); }
+
+        let mut pick = rng.below(total);
+        for book_move in &moves {
+            if pick < book_move.weight as u64 { return Some(book_move.mv); }
+            pick -= book_move.weight as u64;
+        }
+        None
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BookSelection {
+    /// Always the move with the highest weight.
+    Best,
+    /// Random moves in proportion to their weights.
+    #[default]
+    Weighted,
+}
+
+/// Plays from an opening book while it has moves for the position and the
+/// game is young enough, and leaves the rest to another strategy.
+pub struct BookStrategy<S: Strategy> {
+    pub book: PolyglotBook,
+    pub strategy: S,
+    pub selection: BookSelection,
+    /// Plies from the start of the game after which the book is no longer
+    /// consulted.
+    pub max_ply: Option<u32>,
+    rng: Mutex<Rng>,
+}
+
+impl<S: Strategy> BookStrategy<S> {
+    pub fn new(book: PolyglotBook, strategy: S) -> Self {
+        Self { book, strategy, selection: BookSelection::default(), max_ply: None, rng: Mutex::new(Rng::from_time()) }
+    }
+
+    /// Reseeds the choice among weighted book moves.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Mutex::new(Rng::new(seed));
+    }
+
+    pub fn book_move(&self, board: &Board) -> Option<Move> {
+        let ply = (board.fullmove_number.max(1) - 1) * 2 + board.side_to_move.index() as u32;
+        if self.max_ply.is_some_and(|max_ply| ply >= max_ply) { return None; }
+
+        match self.selection {
+            BookSelection::Best => self.book.best_move(board),
+            BookSelection::Weighted => self.book.weighted_move(board, &mut self.rng.lock().unwrap()),
+        }
+    }
+}
+
+impl<S: Strategy> Strategy for BookStrategy<S> {
+    fn next_move(&self, board: &Board) -> Move {
+        self.book_move(board).unwrap_or_else(|| self.strategy.next_move(board))
+    }
+
+    fn try_next_move(&self, board: &Board) -> Result<Move, String> {
+        self.book_move(board).map_or_else(|| self.strategy.try_next_move(board), Ok)
+    }
+
+    fn next_move_with_limits(&self, board: &Board, limits: &SearchLimits) -> Move {
+        self.book_move(board).unwrap_or_else(|| self.strategy.next_move_with_limits(board, limits))
+    }
+
+    fn set_history(&self, history: &GameHistory) {
+        self.strategy.set_history(history);
+    }
+
+    fn stop(&self) {
+        self.strategy.stop();
+    }
+
+    fn start_pondering(&self, board: &Board) -> Option<Move> {
+        self.strategy.start_pondering(board)
+    }
+
+    fn ponder_hit(&self) {
+        self.strategy.ponder_hit();
+    }
+
+    fn ponder_miss(&self) {
+        self.strategy.ponder_miss();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn played(moves: &[&str]) -> Board {
+        let mut board = Board::new();
+        for uci in moves {
+            board.apply_move(Move::from_uci(uci).unwrap()).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn keys_match_the_polyglot_reference() {
+        // the examples from the Polyglot book format description
+        assert_eq!(polyglot_key(&Board::new()), 0x463b96181691fc9c);
+        assert_eq!(polyglot_key(&played(&["e2e4"])), 0x823c9b50fd114196);
+        assert_eq!(polyglot_key(&played(&["e2e4", "d7d5"])), 0x0756b94461c50fb0);
+        assert_eq!(polyglot_key(&played(&["e2e4", "d7d5", "e4e5"])), 0x662fafb965db29d4);
+        assert_eq!(polyglot_key(&played(&["e2e4", "d7d5", "e4e5", "f7f5"])), 0x22a48b5a8e47ff78);
+        assert_eq!(polyglot_key(&played(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"])), 0x652a607ca3f242c1);
+        assert_eq!(polyglot_key(&played(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"])), 0x00fdd303c946bdd9);
+        assert_eq!(polyglot_key(&played(&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"])), 0x3c8123ea7b067637);
+        assert_eq!(polyglot_key(&played(&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"])), 0x5c3f9b829b279560);
+    }
+
+    #[test]
+    fn moves_round_trip_including_castling() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for uci in ["e1g1", "e1c1", "a1a8", "e1e2"] {
+            let mv = Move::from_uci(uci).unwrap();
+            assert_eq!(decode_move(&board, encode_move(&board, mv)), mv);
+        }
+        // white short castling is stored as e1h1
+        assert_eq!(encode_move(&board, Move::from_uci("e1g1").unwrap()), (4 << 6) | 7);
+
+        let promotion = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mv = Move::from_uci("a7a8n").unwrap();
+        assert_eq!(decode_move(&promotion, encode_move(&promotion, mv)), mv);
+    }
+
+    fn small_book() -> PolyglotBook {
+        let start = Board::new();
+        let entry = |board: &Board, uci: &str, weight| BookEntry {
+            key: polyglot_key(board),
+            raw_move: encode_move(board, Move::from_uci(uci).unwrap()),
+            weight,
+            learn: 0,
+        };
+        let after_e4 = played(&["e2e4"]);
+        PolyglotBook::from_entries(vec![
+            entry(&start, "d2d4", 10),
+            entry(&after_e4, "c7c5", 5),
+            entry(&start, "e2e4", 30),
+            entry(&start, "g1f3", 0),
+        ])
+    }
+
+    #[test]
+    fn looks_up_moves_by_key() {
+        let book = PolyglotBook::from_bytes(&small_book().to_bytes()).unwrap();
+        assert_eq!(book.len(), 4);
+
+        let moves: Vec<String> = book.moves(&Board::new()).iter().map(|m| m.mv.to_string()).collect();
+        assert_eq!(moves, vec!["e2e4", "d2d4", "g1f3"]);
+        assert_eq!(book.best_move(&played(&["e2e4"])), Some(Move::from_uci("c7c5").unwrap()));
+        assert_eq!(book.best_move(&played(&["d2d4"])), None);
+    }
+
+    #[test]
+    fn weighted_choice_follows_weights() {
+        let book = small_book();
+        let mut rng = Rng::new(3);
+        let e4 = Move::from_uci("e2e4").unwrap();
+        let picks: Vec<Move> = (0..400).map(|_| book.weighted_move(&Board::new(), &mut rng).unwrap()).collect();
+        let e4_count = picks.iter().filter(|&&mv| mv == e4).count();
+        assert!((250..350).contains(&e4_count), "{e4_count}");
+        assert!(!picks.contains(&Move::from_uci("g1f3").unwrap()));
+    }
+
+    #[test]
+    fn rejects_broken_books() {
+        assert!(PolyglotBook::from_bytes(&[0; 15]).is_err());
+        let mut bytes = BookEntry { key: 2, raw_move: 0, weight: 1, learn: 0 }.to_bytes().to_vec();
+        bytes.extend(BookEntry { key: 1, raw_move: 0, weight: 1, learn: 0 }.to_bytes());
+        assert_eq!(PolyglotBook::from_bytes(&bytes).unwrap_err(), "Book entries are not sorted by key");
+    }
+
+    struct Fixed(Move);
+
+    impl Strategy for Fixed {
+        fn next_move(&self, _board: &Board) -> Move {
+            self.0
+        }
+    }
+
+    #[test]
+    fn book_strategy_falls_back_after_the_book() {
+        let fallback = Move::from_uci("b1c3").unwrap();
+        let mut strategy = BookStrategy::new(small_book(), Fixed(fallback));
+        strategy.selection = BookSelection::Best;
+
+        assert_eq!(strategy.next_move(&Board::new()), Move::from_uci("e2e4").unwrap());
+        assert_eq!(strategy.next_move(&played(&["e2e4"])), Move::from_uci("c7c5").unwrap());
+        assert_eq!(strategy.next_move(&played(&["d2d4"])), fallback);
+
+        strategy.max_ply = Some(1);
+        assert_eq!(strategy.next_move(&Board::new()), Move::from_uci("e2e4").unwrap());
+        assert_eq!(strategy.next_move(&played(&["e2e4"])), fallback);
+    }
+}
@@ -0,0 +1,489 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::arena::*;
+use crate::board::*;
+use crate::book::*;
+use crate::database::*;
+use crate::datagen::*;
+use crate::engine::*;
+use crate::epd::*;
+use crate::eval::*;
+use crate::notation::*;
+use crate::search::Clock;
+use crate::solver::*;
+use crate::strategy::*;
+use crate::syzygy::*;
+use crate::tune::*;
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+fn parse_millis(value: &str, name: &str) -> Result<Duration, String> {
+    value.parse::<u64>()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("Invalid {name} {value}"))
+}
+
+pub fn usage() -> String {
+    String::from("\
+Usage:
+  chess                                  play a game at the console
+  chess play [--white player] [--black player] [limits]
+                                         players are human, computer, mcts,
+                                         random, greedy, script or a
+                                         personality: attacker, solid,
+                                         pawn-grabber
+             [--personalities file]      computer personalities to choose
+                                         from instead of the built-in ones
+             [--uct] [--rollouts plies]  select by UCT instead of PUCT, value
+                                         positions by random games
+             [--script file]             moves (or the first PGN game) played
+                                         in turn by the script players
+             [--seed n]                  seed of the random and greedy players
+             [--ponder]                  let the computer think on its
+                                         opponent's time
+             [--notation name]           play with english, german, french or
+                                         figurine piece names (or e.g. KDTLS)
+             [--book file.bin] [--book-depth plies] [--book-best]
+                                         let the computer open from a Polyglot
+                                         book, by weight or its best moves
+  chess analyze [fen] [--multipv n] [limits]
+                                         show the best lines of a position at
+                                         every depth (up to 8 by default)
+  chess solve <fen> (--mate n | --selfmate n | --helpmate n)
+                                         print every key and the full solution
+                                         tree of a problem
+  chess epd-test <file> [limits]         run the computer on an EPD test suite
+                                         (one second per position by default)
+  chess db import <db> <pgn>...          add the games of PGN files to a database
+  chess db search <db> [--player name] [--white name] [--black name]
+                  [--eco code] [--result 1-0] [--fen fen] [--moves]
+                                         list the games matching all criteria
+  chess weights                          print the default evaluation weights
+  chess tune <positions> [--weights file] [--output file] [--iterations n]
+             [--rate r]                  fit the weights to lines of FEN and
+                                         game result (tuned.weights by default)
+  chess calibrate [--levels 0,5,10,15,20] [--games n] [--seed n] [limits]
+                                         play skill levels against each other
+                                         and estimate their ratings
+  chess datagen [--games n] [--workers n] [--random-plies n] [--seed n]
+                [--output file] [--text file] [limits]
+                                         play self-play games on all cores and
+                                         save their quiet positions with score
+                                         and result (datagen.bin by default)
+  chess match [--games n] [--seed n] [limits]
+                                         play the configured computer against
+                                         the default one, e.g. with --disable
+
+Limits for the computer, combined as in UCI (milliseconds):
+  --depth n  --nodes n  --movetime ms  --wtime ms --btime ms
+  --winc ms  --binc ms  --movestogo n  --hash mb (transposition table size)
+  --weights file (evaluation weights, see the weights command)
+  --nnue file (evaluate with a neural network instead, not for personalities)
+  --threads n (search threads, one by default)
+  --syzygy dirs (Syzygy tablebase directories, also adjudicating games)
+  --skill level (0 to 20, the full strength) or --elo rating (600 to 2000)
+  --disable names (comma separated: null-move, lmr, futility,
+            reverse-futility, aspiration, pvs)")
+}
+
+/// Runs the command given on the command line, without the program name.
+pub fn run(args: &[String]) -> Result<(), String> {
+    match args.first().map(|s| s.as_str()) {
+        Some("play") => play(&args[1..]),
+        Some("epd-test") => epd_test(&args[1..]),
+        Some("analyze") => analyze(&args[1..]),
+        Some("solve") => solve_problem(&args[1..]),
+        Some("db") => db(&args[1..]),
+        Some("tune") => tune_weights(&args[1..]),
+        Some("calibrate") => calibrate(&args[1..]),
+        Some("match") => play_match(&args[1..]),
+        Some("datagen") => datagen(&args[1..]),
+        Some("weights") => {
+            print!("{}", EvalWeights::default().to_config());
+            Ok(())
+        },
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", usage());
+            Ok(())
+        },
+        Some(command) => Err(format!("Unknown command {command}\n{}", usage())),
+        None => Err(usage()),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(args: &[String], name: &str) -> Result<Option<T>, String> {
+    option_value(args, &format!("--{name}"))
+        .map(|value| value.parse().map_err(|_| format!("Invalid {name} {value}")))
+        .transpose()
+}
+
+/// Search limits given with `--depth`, `--nodes`, `--movetime` and the clock
+/// options, or `default` when none is given.
+fn parse_limits(args: &[String], default: SearchLimits) -> Result<SearchLimits, String> {
+    let millis = |name: &str| option_value(args, &format!("--{name}"))
+        .map(|value| parse_millis(value, name))
+        .transpose();
+
+    let mut limits = SearchLimits {
+        movetime: millis("movetime")?,
+        nodes: parse_number(args, "nodes")?,
+        depth: parse_number(args, "depth")?,
+        clock: None,
+    };
+
+    let (wtime, btime) = (millis("wtime")?, millis("btime")?);
+    if wtime.is_some() || btime.is_some() {
+        limits.clock = Some(Clock {
+            white: wtime.or(btime).unwrap_or_default(),
+            black: btime.or(wtime).unwrap_or_default(),
+            white_increment: millis("winc")?.unwrap_or_default(),
+            black_increment: millis("binc")?.unwrap_or_default(),
+            moves_to_go: parse_number(args, "movestogo")?,
+        });
+    }
+
+    Ok(if limits.is_infinite() { default } else { limits })
+}
+
+/// The evaluation given with `--nnue` or `--weights`, if any.
+fn evaluator(args: &[String]) -> Result<Option<Arc<dyn Evaluator>>, String> {
+    if let Some(path) = option_value(args, "--nnue") {
+        return Ok(Some(Arc::new(NnueEvaluator::load(Path::new(path))?)));
+    }
+    option_value(args, "--weights")
+        .map(|path| Ok(Arc::new(ClassicalEvaluator::new(EvalWeights::load(Path::new(path))?)) as Arc<dyn Evaluator>))
+        .transpose()
+}
+
+fn tablebase(args: &[String]) -> Result<Option<Arc<Tablebase>>, String> {
+    option_value(args, "--syzygy").map(|paths| Tablebase::open(paths).map(Arc::new)).transpose()
+}
+
+/// The computer player configured by the limit, `--hash`, `--threads`,
+/// `--weights`, `--nnue`, `--skill`, `--elo`, `--disable` and `--syzygy`
+/// options.
+fn computer(args: &[String], default_limits: SearchLimits) -> Result<AlphaBetaStrategy, String> {
+    let mut strategy = AlphaBetaStrategy::with_limits(parse_limits(args, default_limits)?);
+    if let Some(size_mb) = parse_number(args, "hash")? {
+        strategy.set_hash_size(size_mb);
+    }
+    if let Some(threads) = parse_number(args, "threads")? {
+        strategy.threads = threads;
+    }
+    if let Some(evaluator) = evaluator(args)? {
+        strategy.set_evaluator(evaluator);
+    }
+    if let Some(level) = parse_number(args, "skill")? {
+        strategy.skill = Skill::new(level);
+    }
+    if let Some(elo) = parse_number(args, "elo")? {
+        strategy.skill = Skill::from_elo(elo);
+    }
+    if let Some(names) = option_value(args, "--disable") {
+        for name in names.split(',') {
+            strategy.options.disable(name.trim())?;
+        }
+    }
+    strategy.set_tablebase(tablebase(args)?);
+    Ok(strategy)
+}
+
+/// The Monte Carlo player configured by the `--uct`, `--rollouts` and limit
+/// options, where `--nodes` counts iterations.
+fn mcts(args: &[String]) -> Result<MctsStrategy, String> {
+    let mut strategy = MctsStrategy::new(if args.iter().any(|arg| arg == "--uct") { Selection::Uct } else { Selection::Puct });
+    strategy.limits = parse_limits(args, strategy.limits)?;
+    if let Some(max_plies) = parse_number(args, "rollouts")? {
+        strategy.leaf_value = LeafValue::Rollout { max_plies };
+    }
+    if let Some(evaluator) = evaluator(args)? {
+        strategy.set_evaluator(evaluator);
+    }
+    Ok(strategy)
+}
+
+/// `strategy` opening from the Polyglot book given by `--book`, if any.
+fn with_book(args: &[String], strategy: impl Strategy + 'static) -> Result<Box<dyn Strategy>, String> {
+    let Some(path) = option_value(args, "--book") else { return Ok(Box::new(strategy)); };
+    let mut strategy = BookStrategy::new(PolyglotBook::open(Path::new(path))?, strategy);
+    strategy.max_ply = parse_number(args, "book-depth")?;
+    if args.iter().any(|arg| arg == "--book-best") {
+        strategy.selection = BookSelection::Best;
+    }
+    Ok(Box::new(strategy))
+}
+
+fn play(args: &[String]) -> Result<(), String> {
+    let notation = match option_value(args, "--notation") {
+        Some(name) => PieceStyle::from_name(name)?,
+        None => PieceStyle::ENGLISH,
+    };
+
+    let human = HumanStrategy::new(notation);
+    let default_limits = SearchLimits::depth(AlphaBetaStrategy::DEFAULT_DEPTH);
+    let personalities = match option_value(args, "--personalities") {
+        Some(path) => Personality::load_all(Path::new(path))?,
+        None => Personality::builtin(),
+    };
+    // a computer of its own for each side played by a personality, which
+    // changes the weights given with --weights
+    let mut characters = Vec::new();
+    for side in ["--white", "--black"] {
+        let character = match option_value(args, side).and_then(|name| find_personality(&personalities, name)) {
+            Some(_) if option_value(args, "--nnue").is_some() => {
+                return Err(String::from("Personalities play with evaluation weights, not --nnue"));
+            },
+            Some(personality) => {
+                let base = option_value(args, "--weights").map(|path| EvalWeights::load(Path::new(path))).transpose()?;
+                Some(with_book(args, personality.strategy(computer(args, default_limits)?, &base.unwrap_or_default()))?)
+            },
+            None => None,
+        };
+        characters.push(character);
+    }
+
+    let computer = with_book(args, computer(args, default_limits)?)?;
+    let mcts = mcts(args)?;
+    let seed = parse_number(args, "seed")?;
+    let (random, greedy) = match seed {
+        Some(seed) => (RandomStrategy::new(seed), GreedyStrategy::new(seed)),
+        None => (RandomStrategy::default(), GreedyStrategy::default()),
+    };
+    let script = option_value(args, "--script").map(|path| ScriptedStrategy::open(Path::new(path))).transpose()?;
+
+    let player = |side: usize| -> Result<&dyn Strategy, String> {
+        match option_value(args, ["--white", "--black"][side]).unwrap_or("human") {
+            "human" => Ok(&human),
+            "computer" => Ok(computer.as_ref()),
+            "mcts" => Ok(&mcts),
+            "random" => Ok(&random),
+            "greedy" => Ok(&greedy),
+            "script" => script.as_ref().map(|s| s as &dyn Strategy).ok_or(String::from("The script player needs --script file")),
+            other => characters[side].as_deref().ok_or(format!("Unknown player {other}")),
+        }
+    };
+
+    let tablebase = tablebase(args)?;
+    let mut engine = ConsoleEngine::new(player(0)?, player(1)?);
+    if let Some(tablebase) = &tablebase {
+        engine.set_tablebase(tablebase);
+    }
+    engine.set_pondering(args.iter().any(|arg| arg == "--ponder"));
+    engine.game_loop();
+    Ok(())
+}
+
+fn analyze(args: &[String]) -> Result<(), String> {
+    let board = match args.first().filter(|arg| !arg.starts_with("--")) {
+        Some(fen) => Board::from_fen(fen)?,
+        None => Board::new(),
+    };
+    let multi_pv = parse_number(args, "multipv")?.unwrap_or(1);
+    let strategy = computer(args, SearchLimits::depth(8))?;
+
+    let mut shown_depth = 0;
+    let analysis = strategy.analyze(&board, &strategy.limits, multi_pv, |analysis| {
+        // show every completed depth
+        if analysis.depth == shown_depth { return; }
+        shown_depth = analysis.depth;
+        for (i, line) in analysis.lines.iter().enumerate() {
+            println!("{:>2}. depth {:>2}  {:>7}  {}", i + 1, line.depth, line.analysis_score().to_string(), line.to_san(&board));
+        }
+        println!();
+    });
+    println!("Depth {}, {} nodes in {} ms", analysis.depth, analysis.nodes, analysis.elapsed.as_millis());
+    Ok(())
+}
+
+fn solve_problem(args: &[String]) -> Result<(), String> {
+    let fen = args.first().filter(|arg| !arg.starts_with("--")).ok_or(usage())?;
+    let board = Board::from_fen(fen)?;
+    let (stipulation, moves) = [("mate", Stipulation::Mate), ("selfmate", Stipulation::Selfmate), ("helpmate", Stipulation::Helpmate)]
+        .into_iter()
+        .find_map(|(name, stipulation)| parse_number(args, name).transpose().map(|moves| moves.map(|moves| (stipulation, moves))))
+        .ok_or(String::from("Give the stipulation with --mate n, --selfmate n or --helpmate n"))??;
+
+    let mut solver = Solver::new(stipulation);
+    let solution = solver.solve(&board, moves);
+    print!("{}", solution.to_text());
+
+    let keys = solution.keys.iter().map(|key| to_san(&board, key.mv)).collect::<Vec<_>>().join(", ");
+    let summary = match solution.keys.len() {
+        0 => String::from("No solution"),
+        _ if stipulation == Stipulation::Helpmate => match solution.lines().len() {
+            1 => String::from("1 solution"),
+            n => format!("{n} solutions"),
+        },
+        1 => format!("Key {keys}"),
+        n => format!("Cooked: {n} keys {keys}"),
+    };
+    println!("{}{moves}: {summary} ({} positions)", stipulation.symbol(), solver.nodes());
+    Ok(())
+}
+
+fn epd_test(args: &[String]) -> Result<(), String> {
+    let path = args.first().filter(|arg| !arg.starts_with("--")).ok_or(usage())?;
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {path}. Error {e}"))?;
+    let positions = Epd::parse_all(&contents)?;
+
+    let strategy = computer(args, SearchLimits::movetime(Duration::from_secs(1)))?;
+    let report = run_test_suite(&positions, &strategy, &strategy.limits, |result| {
+        println!("{:<16} {:<8} {}", result.id, result.played_san, if result.solved { "solved" } else { "failed" });
+    })?;
+
+    println!("{}", report.summary());
+    Ok(())
+}
+
+fn tune_weights(args: &[String]) -> Result<(), String> {
+    let path = args.first().filter(|arg| !arg.starts_with("--")).ok_or(usage())?;
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {path}. Error {e}"))?;
+    let positions = parse_labeled_positions(&contents)?;
+
+    let start = match option_value(args, "--weights") {
+        Some(file) => EvalWeights::load(Path::new(file))?,
+        None => EvalWeights::default(),
+    };
+    let output = option_value(args, "--output").unwrap_or("tuned.weights");
+    let iterations = parse_number(args, "iterations")?.unwrap_or(500);
+    let rate = parse_number(args, "rate")?.unwrap_or(Tuner::DEFAULT_RATE);
+
+    println!("Tuning on {} positions", positions.len());
+    let weights = tune(&positions, &start, iterations, rate, |iteration, error| {
+        if iteration % 50 == 0 || iteration == iterations {
+            println!("Iteration {iteration}: error {error:.6}");
+        }
+    });
+
+    weights.save(Path::new(output))?;
+    println!("Wrote {output}");
+    Ok(())
+}
+
+fn calibrate(args: &[String]) -> Result<(), String> {
+    let mut calibration = Calibration::default();
+    if let Some(levels) = option_value(args, "--levels") {
+        calibration.levels = levels.split(',')
+            .map(|level| level.trim().parse().map(Skill::new).map_err(|_| format!("Invalid level {level}")))
+            .collect::<Result<_, _>>()?;
+    }
+    calibration.games_per_pair = parse_number(args, "games")?.unwrap_or(calibration.games_per_pair);
+    calibration.seed = parse_number(args, "seed")?.unwrap_or(calibration.seed);
+    calibration.limits = parse_limits(args, calibration.limits)?;
+
+    let ratings = calibration.run(|white, black, outcome| {
+        println!("Level {:>2} - level {:>2}  {}", white.level(), black.level(), outcome.result());
+    });
+
+    println!("Level  Nominal  Estimated  Score");
+    for rating in &ratings {
+        println!("{:>5}  {:>7}  {:>9.0}  {}/{}", rating.skill.level(), rating.skill.elo(), rating.elo, rating.score, rating.games);
+    }
+    Ok(())
+}
+
+fn play_match(args: &[String]) -> Result<(), String> {
+    let mut settings = Match::default();
+    settings.games = parse_number(args, "games")?.unwrap_or(settings.games);
+    settings.seed = parse_number(args, "seed")?.unwrap_or(settings.seed);
+    let limits = SearchLimits::depth(AlphaBetaStrategy::DEFAULT_DEPTH);
+    let tested = computer(args, limits)?;
+    let baseline = AlphaBetaStrategy::with_limits(tested.limits);
+
+    let result = settings.run(&tested, &baseline, |tested_white, outcome| {
+        println!("{}  {}", if tested_white { "tested - default " } else { "default - tested " }, outcome.result());
+    });
+    println!("Score {}/{}, {:+.0} Elo", result.score, result.games, result.elo);
+    Ok(())
+}
+
+fn datagen(args: &[String]) -> Result<(), String> {
+    let mut settings = DataGen::default();
+    settings.games = parse_number(args, "games")?.unwrap_or(settings.games);
+    settings.workers = parse_number(args, "workers")?.unwrap_or(settings.workers);
+    settings.random_plies = parse_number(args, "random-plies")?.unwrap_or(settings.random_plies);
+    settings.seed = parse_number(args, "seed")?.unwrap_or(settings.seed);
+    let limits = SearchLimits::nodes(5000);
+    // fail on bad options before starting the workers
+    computer(args, limits)?;
+
+    let output = option_value(args, "--output").unwrap_or("datagen.bin");
+    let create = |path: &str| std::fs::File::create(path)
+        .map(std::io::BufWriter::new)
+        .map_err(|e| format!("Unable to write {path}. Error {e}"));
+    let mut binary = create(output)?;
+    let mut text = option_value(args, "--text").map(create).transpose()?;
+
+    let mut written = 0;
+    let mut error = None;
+    settings.run(|| computer(args, limits).expect("Options checked before"), |game, outcome, positions| {
+        for position in &positions {
+            let result = binary.write_all(&position.to_bytes())
+                .and_then(|_| text.as_mut().map_or(Ok(()), |text| writeln!(text, "{}", position.to_text())));
+            if let Err(e) = result { error.get_or_insert(e); }
+        }
+        written += positions.len();
+        println!("Game {:>5}  {:<7}  {:>4} positions, {written} in all", game + 1, outcome.result(), positions.len());
+    });
+
+    if let Some(e) = error { return Err(format!("Unable to write the positions. Error {e}")); }
+    binary.flush().map_err(|e| format!("Unable to write {output}. Error {e}"))?;
+    if let Some(text) = &mut text { text.flush().map_err(|e| format!("Unable to write the text export. Error {e}"))?; }
+    println!("Wrote {written} positions to {output}");
+    Ok(())
+}
+
+fn db(args: &[String]) -> Result<(), String> {
+    let path = args.get(1).ok_or(usage())?;
+    let mut db = GameDatabase::open(Path::new(path))?;
+
+    match args.first().map(|s| s.as_str()) {
+        Some("import") => {
+            for file in args[2..].iter().filter(|arg| !arg.starts_with("--")) {
+                let text = std::fs::read_to_string(file).map_err(|e| format!("Unable to read {file}. Error {e}"))?;
+                let report = db.import_pgn(&text)?;
+                for error in &report.skipped {
+                    eprintln!("{file}: skipped {error}");
+                }
+                println!("{file}: imported {} games", report.imported);
+            }
+            println!("{} games in {path}", db.len());
+            Ok(())
+        },
+        Some("search") => {
+            let text = |name: &str| option_value(args, name).map(String::from);
+            let mut query = GameQuery {
+                player: text("--player"),
+                white: text("--white"),
+                black: text("--black"),
+                eco: text("--eco"),
+                result: text("--result"),
+                position: None,
+            };
+            if let Some(fen) = option_value(args, "--fen") {
+                query = query.with_fen(fen)?;
+            }
+
+            let games = db.search_games(&query)?;
+            for (id, game) in &games {
+                println!("#{:<6} {} - {}  {}  {}  {} moves", id,
+                    game.tag("White").unwrap_or("?"), game.tag("Black").unwrap_or("?"),
+                    game.result(), game.tag("ECO").unwrap_or("?"), game.moves.len());
+                if args.iter().any(|arg| arg == "--moves") {
+                    let board = game.start_board().unwrap_or_default();
+                    println!("        {}", number_moves(&board, &to_san_line(&board, &game.moves)));
+                }
+            }
+            println!("{} games found", games.len());
+            Ok(())
+        },
+        _ => Err(usage()),
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::board::*;
+use crate::pgn::*;
+
+// A database is an append-only PGN file holding one canonical record per
+// game, next to an append-only index file with one line per game:
+//
+//   offset <TAB> length <TAB> white <TAB> black <TAB> eco <TAB> result <TAB> keys
+//
+// where keys are the hex Zobrist keys of every position reached. The index
+// is loaded into memory on open and any games missing from it are indexed
+// again from the data file.
+
+#[derive(Clone, Debug, PartialEq)]
+struct IndexEntry {
+    offset: u64,
+    length: u64,
+    white: String,
+    black: String,
+    eco: String,
+    result: String,
+    keys: Vec<u64>,
+}
+
+impl IndexEntry {
+    fn from_game(game: &Game, offset: u64, length: u64) -> Result<Self, String> {
+        let clean = |value: Option<&str>| value.unwrap_or("?").replace(['\t', '\n'], " ");
+        let mut keys: Vec<u64> = game.boards()?.iter().map(|board| board.zobrist_key()).collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        Ok(Self {
+            offset,
+            length,
+            white: clean(game.tag("White")),
+            black: clean(game.tag("Black")),
+            eco: clean(game.tag("ECO")),
+            result: String::from(game.result()),
+            keys,
+        })
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 { return None; }
+
+        let keys = fields[6].split_whitespace()
+            .map(|key| u64::from_str_radix(key, 16).ok())
+            .collect::<Option<Vec<u64>>>()?;
+
+        Some(Self {
+            offset: fields[0].parse().ok()?,
+            length: fields[1].parse().ok()?,
+            white: String::from(fields[2]),
+            black: String::from(fields[3]),
+            eco: String::from(fields[4]),
+            result: String::from(fields[5]),
+            keys,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        let keys: Vec<String> = self.keys.iter().map(|key| format!("{key:016x}")).collect();
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            self.offset, self.length, self.white, self.black, self.eco, self.result, keys.join(" "))
+    }
+}
+
+/// Criteria for `GameDatabase::search`. All given criteria must match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameQuery {
+    /// Case insensitive part of the white or black player name.
+    pub player: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    /// ECO code or prefix, e.g. `C41` or `C4`.
+    pub eco: Option<String>,
+    pub result: Option<String>,
+    /// Zobrist key of a position the game must reach.
+    pub position: Option<u64>,
+}
+
+impl GameQuery {
+    pub fn with_fen(mut self, fen: &str) -> Result<Self, String> {
+        self.position = Some(Board::from_fen(fen)?.zobrist_key());
+        Ok(self)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Errors of the games which could not be read.
+    pub skipped: Vec<String>,
+}
+
+pub struct GameDatabase {
+    data_path: PathBuf,
+    index_path: PathBuf,
+    entries: Vec<IndexEntry>,
+    by_key: HashMap<u64, Vec<usize>>,
+    by_eco: HashMap<String, Vec<usize>>,
+    by_result: HashMap<String, Vec<usize>>,
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+impl GameDatabase {
+    /// Opens the database stored at `path`, creating it when it does not
+    /// exist. The index lives next to it with an `.idx` extension added.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut index_path = path.as_os_str().to_owned();
+        index_path.push(".idx");
+
+        let mut db = Self {
+            data_path: path.to_path_buf(),
+            index_path: PathBuf::from(index_path),
+            entries: Vec::new(),
+            by_key: HashMap::new(),
+            by_eco: HashMap::new(),
+            by_result: HashMap::new(),
+        };
+
+        let data = match std::fs::read(&db.data_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Unable to read {}. Error {e}", path.display())),
+        };
+
+        let index = std::fs::read_to_string(&db.index_path).unwrap_or_default();
+        let mut indexed_end = 0;
+        let mut valid_index = true;
+        for line in index.lines() {
+            match IndexEntry::parse(line) {
+                Some(entry) if entry.offset >= indexed_end && entry.offset + entry.length <= data.len() as u64 => {
+                    indexed_end = entry.offset + entry.length;
+                    db.insert(entry);
+                },
+                _ => {
+                    valid_index = false;
+                    break;
+                },
+            }
+        }
+
+        if !valid_index || indexed_end < data.len() as u64 {
+            db.reindex_tail(&data, indexed_end as usize, !valid_index)?;
+        }
+
+        Ok(db)
+    }
+
+    fn insert(&mut self, entry: IndexEntry) {
+        let id = self.entries.len();
+        for &key in &entry.keys {
+            self.by_key.entry(key).or_default().push(id);
+        }
+        self.by_eco.entry(entry.eco.clone()).or_default().push(id);
+        self.by_result.entry(entry.result.clone()).or_default().push(id);
+        self.entries.push(entry);
+    }
+
+    /// Indexes the games stored after `start` and appends them to the index.
+    fn reindex_tail(&mut self, data: &[u8], start: usize, rewrite: bool) -> Result<(), String> {
+        let text = String::from_utf8_lossy(&data[start..]);
+        let mut lines = String::new();
+        for (game, span) in read_games_with_spans(&text)? {
+            let game = game.map_err(|e| format!("Corrupt game in {}. Error {e}", self.data_path.display()))?;
+            let entry = IndexEntry::from_game(&game, (start + span.start) as u64, span.len() as u64)?;
+            lines.push_str(&entry.to_line());
+            self.insert(entry);
+        }
+
+        if rewrite {
+            let all: String = self.entries.iter().map(|entry| entry.to_line()).collect();
+            std::fs::write(&self.index_path, all)
+                .map_err(|e| format!("Unable to write {}. Error {e}", self.index_path.display()))
+        } else {
+            self.append(&self.index_path, lines.as_bytes()).map(|_| ())
+        }
+    }
+
+    fn append(&self, path: &Path, bytes: &[u8]) -> Result<u64, String> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("Unable to open {}. Error {e}", path.display()))?;
+        let offset = file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+        file.write_all(bytes).map_err(|e| format!("Unable to write {}. Error {e}", path.display()))?;
+        Ok(offset)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Appends a game and returns its id.
+    pub fn add_game(&mut self, game: &Game) -> Result<usize, String> {
+        let pgn = game.to_pgn();
+        // records are separated by a blank line
+        let offset = self.append(&self.data_path, format!("{pgn}\n").as_bytes())?;
+        let entry = IndexEntry::from_game(game, offset, pgn.trim_end().len() as u64)?;
+        self.append(&self.index_path, entry.to_line().as_bytes())?;
+        self.insert(entry);
+        Ok(self.entries.len() - 1)
+    }
+
+    /// Imports every readable game of a PGN text.
+    pub fn import_pgn(&mut self, text: &str) -> Result<ImportReport, String> {
+        let mut report = ImportReport::default();
+        for (i, game) in read_games(text)?.into_iter().enumerate() {
+            match game {
+                Ok(game) => {
+                    self.add_game(&game)?;
+                    report.imported += 1;
+                },
+                Err(e) => report.skipped.push(format!("Game {}: {}", i + 1, e)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Reads the game with the given id from disk.
+    pub fn game(&self, id: usize) -> Result<Game, String> {
+        let entry = self.entries.get(id).ok_or(format!("No game with id {id}"))?;
+        let mut file = File::open(&self.data_path)
+            .map_err(|e| format!("Unable to open {}. Error {e}", self.data_path.display()))?;
+        file.seek(SeekFrom::Start(entry.offset)).map_err(|e| e.to_string())?;
+
+        let mut bytes = vec![0; entry.length as usize];
+        file.read_exact(&mut bytes).map_err(|e| format!("Unable to read game {id}. Error {e}"))?;
+        let text = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+        parse_pgn(&text)?.into_iter().next().ok_or(format!("No game with id {id}"))
+    }
+
+    /// Ids of the games matching the query, in the order they were added.
+    pub fn search(&self, query: &GameQuery) -> Vec<usize> {
+        let candidates: Vec<usize> = if let Some(key) = query.position {
+            self.by_key.get(&key).cloned().unwrap_or_default()
+        } else if let Some(eco) = query.eco.as_ref().filter(|eco| eco.len() == 3) {
+            self.by_eco.get(&eco.to_uppercase()).cloned().unwrap_or_default()
+        } else if let Some(result) = &query.result {
+            self.by_result.get(result).cloned().unwrap_or_default()
+        } else {
+            (0..self.entries.len()).collect()
+        };
+
+        candidates.into_iter().filter(|&id| {
+            let entry = &self.entries[id];
+            query.player.as_ref().is_none_or(|player| contains_ignore_case(&entry.white, player) || contains_ignore_case(&entry.black, player))
+                && query.white.as_ref().is_none_or(|white| contains_ignore_case(&entry.white, white))
+                && query.black.as_ref().is_none_or(|black| contains_ignore_case(&entry.black, black))
+                && query.eco.as_ref().is_none_or(|eco| entry.eco.starts_with(&eco.to_uppercase()))
+                && query.result.as_ref().is_none_or(|result| &entry.result == result)
+                && query.position.is_none_or(|key| entry.keys.binary_search(&key).is_ok())
+        }).collect()
+    }
+
+    /// Games matching the query with their ids.
+    pub fn search_games(&self, query: &GameQuery) -> Result<Vec<(usize, Game)>, String> {
+        self.search(query).into_iter().map(|id| Ok((id, self.game(id)?))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const GAMES: &str = r#"[White "Paul Morphy"]
+[Black "Duke Karl"]
+[Result "1-0"]
+[ECO "C41"]
+
+1. e4 e5 2. Nf3 d6 3. d4 Bg4 4. dxe5 Bxf3 5. Qxf3 dxe5 1-0
+
+[White "Magnus Carlsen"]
+[Black "Paul Keres"]
+[Result "1/2-1/2"]
+[ECO "C42"]
+
+1. e4 e5 2. Nf3 Nf6 1/2-1/2
+
+[White "Anna"]
+[Black "Magnus Carlsen"]
+[Result "0-1"]
+[ECO "A45"]
+
+1. d4 Nf6 2. Bg5 0-1
+
+1. e4 e5 2. Ke3 *
+"#;
+
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("chess-db-{}-{}", std::process::id(), name));
+            let db = Self(path);
+            db.remove();
+            db
+        }
+
+        fn remove(&self) {
+            let _ = std::fs::remove_file(&self.0);
+            let mut index = self.0.as_os_str().to_owned();
+            index.push(".idx");
+            let _ = std::fs::remove_file(index);
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    #[test]
+    fn import_and_search_by_tags() {
+        let tmp = TempDb::new("tags");
+        let mut db = GameDatabase::open(&tmp.0).unwrap();
+        let report = db.import_pgn(GAMES).unwrap();
+        assert_eq!(report.imported, 3);
+        assert_eq!(report.skipped.len(), 1);
+
+        let query = |query: GameQuery| db.search(&query);
+        assert_eq!(query(GameQuery { player: Some(String::from("carlsen")), ..Default::default() }), vec![1, 2]);
+        assert_eq!(query(GameQuery { white: Some(String::from("Carlsen")), ..Default::default() }), vec![1]);
+        assert_eq!(query(GameQuery { eco: Some(String::from("C4")), ..Default::default() }), vec![0, 1]);
+        assert_eq!(query(GameQuery { eco: Some(String::from("a45")), ..Default::default() }), vec![2]);
+        assert_eq!(query(GameQuery { result: Some(String::from("1-0")), ..Default::default() }), vec![0]);
+        assert_eq!(query(GameQuery { player: Some(String::from("Paul")), result: Some(String::from("1/2-1/2")), ..Default::default() }), vec![1]);
+    }
+
+    #[test]
+    fn search_by_position() {
+        let tmp = TempDb::new("position");
+        let mut db = GameDatabase::open(&tmp.0).unwrap();
+        db.import_pgn(GAMES).unwrap();
+
+        let after_nf3 = GameQuery::default().with_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2").unwrap();
+        assert_eq!(db.search(&after_nf3), vec![0, 1]);
+
+        let games = db.search_games(&GameQuery::default().with_fen(START_FEN).unwrap()).unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[2].1.tag("White"), Some("Anna"));
+        assert_eq!(games[2].1.moves, vec![Move::from_uci("d2d4").unwrap(), Move::from_uci("g8f6").unwrap(), Move::from_uci("c1g5").unwrap()]);
+    }
+
+    #[test]
+    fn reopen_and_rebuild_index() {
+        let tmp = TempDb::new("reopen");
+        {
+            let mut db = GameDatabase::open(&tmp.0).unwrap();
+            db.import_pgn(GAMES).unwrap();
+        }
+
+        let db = GameDatabase::open(&tmp.0).unwrap();
+        assert_eq!(db.len(), 3);
+        assert_eq!(db.game(1).unwrap().tag("Black"), Some("Paul Keres"));
+
+        // losing the index makes the next open index the data file again
+        let mut index = tmp.0.as_os_str().to_owned();
+        index.push(".idx");
+        std::fs::write(&index, "garbage\n").unwrap();
+        let mut db = GameDatabase::open(&tmp.0).unwrap();
+        assert_eq!(db.len(), 3);
+        assert_eq!(db.game(2).unwrap().result(), "0-1");
+
+        db.import_pgn("[White \"Late\"]\n\n1. c4 *").unwrap();
+        let db = GameDatabase::open(&tmp.0).unwrap();
+        assert_eq!(db.search(&GameQuery { white: Some(String::from("late")), ..Default::default() }), vec![3]);
+    }
+}
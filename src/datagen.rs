@@ -0,0 +1,287 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::arena::*;
+use crate::board::*;
+use crate::board::castling::*;
+use crate::board::piece::*;
+use crate::rng::*;
+use crate::search::*;
+use crate::strategy::*;
+
+// Pieces in `PieceType::index` and `PieceColor::index` order.
+const PIECE_TYPES: [PieceType; 6] = [PieceType::King, PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight, PieceType::Pawn];
+const COLORS: [PieceColor; 2] = [PieceColor::White, PieceColor::Black];
+const NO_EN_PASSANT: u8 = 64;
+
+/// A position of a self-play game, labelled for training an evaluation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainingPosition {
+    pub board: Board,
+    /// Search score in centipawns from white's point of view.
+    pub score: i32,
+    /// How the game went on to end.
+    pub outcome: Outcome,
+}
+
+impl TrainingPosition {
+    /// Bytes of a record: the occupied cells as a little-endian `u64` with
+    /// a8 as bit 0, a nibble per piece in cell order (colour times 6 plus
+    /// `PieceType::index`), a byte of side to move (bit 4) and castling
+    /// rights (bits 0 to 3), the en passant cell or 64, the halfmove clock,
+    /// then the fullmove number and score as little-endian `u16` and `i16`
+    /// and the result: 0 for a black win, 1 for a draw and 2 for a white win.
+    pub const SIZE: usize = 32;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let mut occupied = 0u64;
+        for (i, (idx, piece)) in self.board.cells.iter().enumerate()
+            .filter_map(|(idx, cell)| cell.piece.map(|piece| (idx, piece)))
+            .take(32)
+            .enumerate() {
+            occupied |= 1 << idx;
+            let code = (piece.color.index() * 6 + piece.piece_type.index()) as u8;
+            bytes[8 + i / 2] |= code << (4 * (i % 2));
+        }
+        bytes[..8].copy_from_slice(&occupied.to_le_bytes());
+
+        let black = if self.board.side_to_move == PieceColor::Black { 16 } else { 0 };
+        bytes[24] = black | self.board.castling.0;
+        bytes[25] = self.board.en_passant.map_or(NO_EN_PASSANT, |idx| idx as u8);
+        bytes[26] = self.board.halfmove_clock.min(255) as u8;
+        bytes[27..29].copy_from_slice(&(self.board.fullmove_number.min(u16::MAX as u32) as u16).to_le_bytes());
+        bytes[29..31].copy_from_slice(&(self.score.clamp(i16::MIN as i32, i16::MAX as i32) as i16).to_le_bytes());
+        bytes[31] = match self.outcome {
+            Outcome::BlackWins => 0,
+            Outcome::Draw => 1,
+            Outcome::WhiteWins => 2,
+        };
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != Self::SIZE {
+            return Err(format!("Expected {} bytes for a position, found {}", Self::SIZE, bytes.len()));
+        }
+
+        let mut board = Board::empty();
+        let occupied = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        if occupied.count_ones() > 32 {
+            return Err(String::from("More than 32 pieces"));
+        }
+        for (i, idx) in (0..64).filter(|idx| occupied & (1 << idx) != 0).enumerate() {
+            let code = (bytes[8 + i / 2] >> (4 * (i % 2))) as usize & 15;
+            if code >= 12 { return Err(format!("Invalid piece code {code}")); }
+            board.cells[idx].piece = Some(Piece { piece_type: PIECE_TYPES[code % 6], color: COLORS[code / 6] });
+        }
+
+        board.side_to_move = if bytes[24] & 16 != 0 { PieceColor::Black } else { PieceColor::White };
+        board.castling = CastlingRights(bytes[24] & 15);
+        board.en_passant = match bytes[25] {
+            NO_EN_PASSANT => None,
+            idx if idx < 64 => Some(idx as usize),
+            idx => return Err(format!("Invalid en passant cell {idx}")),
+        };
+        board.halfmove_clock = bytes[26] as u32;
+        board.fullmove_number = u16::from_le_bytes([bytes[27], bytes[28]]) as u32;
+
+        let score = i16::from_le_bytes([bytes[29], bytes[30]]) as i32;
+        let outcome = match bytes[31] {
+            0 => Outcome::BlackWins,
+            1 => Outcome::Draw,
+            2 => Outcome::WhiteWins,
+            result => return Err(format!("Invalid result {result}")),
+        };
+        Ok(Self { board, score, outcome })
+    }
+
+    /// `fen | score | result`, the result being white's score of 1.0, 0.5
+    /// or 0.0, as read by `parse_labeled_positions`.
+    pub fn to_text(&self) -> String {
+        format!("{} | {} | {:.1}", self.board.to_fen(), self.score, self.outcome.white_score())
+    }
+}
+
+/// Reads records written with `TrainingPosition::to_bytes`, one after the
+/// other.
+pub fn read_training_data(bytes: &[u8]) -> Result<Vec<TrainingPosition>, String> {
+    if !bytes.len().is_multiple_of(TrainingPosition::SIZE) {
+        return Err(format!("Training data is not a whole number of {} byte records", TrainingPosition::SIZE));
+    }
+    bytes.chunks_exact(TrainingPosition::SIZE).map(TrainingPosition::from_bytes).collect()
+}
+
+/// Settings of self-play for training data. Every game starts from random
+/// moves and the computer plays both sides, recording the quiet positions
+/// on the way: those not in check, whose best move is no capture or
+/// promotion and whose score is no mate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataGen {
+    pub games: u32,
+    /// Games played at once, each by its own player.
+    pub workers: usize,
+    /// Random moves played before the computer takes over.
+    pub random_plies: usize,
+    pub max_plies: usize,
+    /// A game is won once the score is beyond this many centipawns for one
+    /// side on several moves in a row.
+    pub adjudication: Option<i32>,
+    pub seed: u64,
+}
+
+// Plies in a row beyond the adjudication score which end a game.
+const ADJUDICATION_PLIES: i32 = 4;
+
+impl Default for DataGen {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            random_plies: 8,
+            max_plies: 400,
+            adjudication: Some(1500),
+            seed: 1,
+        }
+    }
+}
+
+impl DataGen {
+    /// Plays the games on `workers` threads, each with a player made by
+    /// `new_player`, calling `on_game` with the number, result and positions
+    /// of every game as it ends. A game depends only on the seed and its
+    /// number, not on the thread playing it.
+    pub fn run(&self, new_player: impl Fn() -> AlphaBetaStrategy + Sync, mut on_game: impl FnMut(u32, Outcome, Vec<TrainingPosition>)) {
+        let next = AtomicU32::new(0);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..self.workers.max(1) {
+                let (sender, next, new_player) = (sender.clone(), &next, &new_player);
+                scope.spawn(move || {
+                    let player = new_player();
+                    loop {
+                        let game = next.fetch_add(1, Ordering::Relaxed);
+                        if game >= self.games { break; }
+                        let (outcome, positions) = self.play(&player, game);
+                        if sender.send((game, outcome, positions)).is_err() { break; }
+                    }
+                });
+            }
+            drop(sender);
+
+            for (game, outcome, positions) in receiver {
+                on_game(game, outcome, positions);
+            }
+        });
+    }
+
+    /// Plays game number `game` and returns its result and positions.
+    pub fn play(&self, player: &AlphaBetaStrategy, game: u32) -> (Outcome, Vec<TrainingPosition>) {
+        let mut rng = Rng::new(self.seed.wrapping_mul(0x9e37_79b9_7f4a_7c15).wrapping_add(game as u64));
+        let mut board = random_opening(&mut rng, self.random_plies);
+        player.clear_hash();
+
+        let mut history = GameHistory::new(&board);
+        let mut positions = Vec::new();
+        // plies in a row beyond the adjudication score, negative for black
+        let mut streak: i32 = 0;
+        let mut plies = 0;
+        let outcome = loop {
+            if board.legal_moves().is_empty() {
+                break match (board.in_check(), board.side_to_move) {
+                    (false, _) => Outcome::Draw,
+                    (true, PieceColor::White) => Outcome::BlackWins,
+                    (true, PieceColor::Black) => Outcome::WhiteWins,
+                };
+            }
+            if history.is_threefold(&board) || board.halfmove_clock >= 100 || plies >= self.max_plies {
+                break Outcome::Draw;
+            }
+
+            player.set_history(&history);
+            let result = player.search(&board);
+            let mv = result.best_move.expect("No move found");
+            let score = if board.side_to_move == PieceColor::White { result.score } else { -result.score };
+
+            if let Some(threshold) = self.adjudication {
+                streak = match score {
+                    _ if score >= threshold => streak.max(0) + 1,
+                    _ if score <= -threshold => streak.min(0) - 1,
+                    _ => 0,
+                };
+                if streak.abs() >= ADJUDICATION_PLIES {
+                    break if streak > 0 { Outcome::WhiteWins } else { Outcome::BlackWins };
+                }
+            }
+
+            if !board.in_check() && !is_tactical(&board, mv) && !is_mate_score(result.score) {
+                positions.push(TrainingPosition { board: board.clone(), score, outcome: Outcome::Draw });
+            }
+            board.make(mv);
+            history.push(&board);
+            plies += 1;
+        };
+
+        for position in &mut positions {
+            position.outcome = outcome;
+        }
+        (outcome, positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tune::*;
+    use pretty_assertions::assert_eq;
+
+    fn settings() -> DataGen {
+        DataGen { games: 3, workers: 2, max_plies: 24, ..DataGen::default() }
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let position = TrainingPosition {
+            board: Board::from_fen("r3k2r/pp3ppp/2n5/3pP3/8/8/PPP2PPP/R3K2R w Kq d6 3 17").unwrap(),
+            score: -135,
+            outcome: Outcome::BlackWins,
+        };
+        let bytes = position.to_bytes();
+        assert_eq!(TrainingPosition::from_bytes(&bytes).unwrap(), position);
+
+        let start = TrainingPosition { board: Board::new(), score: 20, outcome: Outcome::Draw };
+        let data: Vec<u8> = [bytes, start.to_bytes()].concat();
+        assert_eq!(read_training_data(&data).unwrap(), vec![position, start]);
+        assert!(read_training_data(&data[1..]).is_err());
+    }
+
+    #[test]
+    fn text_export_can_be_tuned_on() {
+        let position = TrainingPosition { board: Board::new(), score: 31, outcome: Outcome::WhiteWins };
+        assert_eq!(position.to_text(), format!("{START_FEN} | 31 | 1.0"));
+        let labeled = parse_labeled_positions(&position.to_text()).unwrap();
+        assert_eq!(labeled[0].result, 1.0);
+    }
+
+    #[test]
+    fn records_quiet_positions_with_the_result() {
+        let (outcome, positions) = settings().play(&AlphaBetaStrategy::new(1), 0);
+        assert!(!positions.is_empty());
+        for position in &positions {
+            assert_eq!(position.outcome, outcome);
+            assert!(!position.board.in_check());
+        }
+    }
+
+    #[test]
+    fn games_do_not_depend_on_the_thread() {
+        let mut games = Vec::new();
+        settings().run(|| AlphaBetaStrategy::new(1), |game, _, positions| games.push((game, positions)));
+        games.sort_by_key(|(game, _)| *game);
+        assert_eq!(games.len(), 3);
+        for (game, positions) in games {
+            assert_eq!(settings().play(&AlphaBetaStrategy::new(1), game).1, positions);
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use crate::strategy::HumanStrategy;
+use crate::notation::PieceStyle;
+use crate::strategy::*;
 use crate::board::*;
 use crate::player::*;
+use crate::syzygy::*;
 use crate::ui::*;
 
 pub trait Engine {
@@ -10,43 +12,223 @@ pub struct ConsoleEngine<'a> {
     p1: Player<'a>,
     p2: Player<'a>,
     board: Board,
+    history: GameHistory,
     turn: bool,
     ui: &'a dyn UI,
+    tablebase: Option<&'a Tablebase>,
+    ponder: bool,
+    // side of the player pondering and the reply it expects
+    expected_reply: Option<(bool, Move)>,
 }
 
 impl<'a> ConsoleEngine<'a> {
-    pub fn new() -> Self {
+    /// A game between the given strategies, each of which may be a person at
+    /// the console or a computer player such as `AlphaBetaStrategy`.
+    pub fn new(white: &'a dyn Strategy, black: &'a dyn Strategy) -> Self {
         Self {
-            p1: Player { color: true, strategy: &HumanStrategy {} },
-            p2: Player { color: false, strategy: &HumanStrategy {} },
+            p1: Player { color: true, strategy: white },
+            p2: Player { color: false, strategy: black },
             board: Board::new(),
+            history: GameHistory::new(&Board::new()),
             turn: true,
             ui: &ConsoleUI {},
+            tablebase: None,
+            ponder: false,
+            expected_reply: None,
         }
     }
 
+    /// Ends the game as soon as the tablebase knows its result.
+    pub fn set_tablebase(&mut self, tablebase: &'a Tablebase) {
+        self.tablebase = Some(tablebase);
+    }
+
+    /// Lets computer players think on their opponent's time: after each
+    /// move the player who made it starts pondering, and when the opponent
+    /// has answered it is told whether its guess was right.
+    pub fn set_pondering(&mut self, ponder: bool) {
+        self.ponder = ponder;
+    }
+
+    /// The current position, after the game when `game_loop` has returned.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The positions of the game so far.
+    pub fn history(&self) -> &GameHistory {
+        &self.history
+    }
+
     pub fn game_loop(&mut self) {
         while !self.is_game_over() {
+            if let Some(wdl) = self.tablebase_result() {
+                self.ui.display_board(&self.board);
+                let winner = match wdl {
+                    Wdl::Win => Some(self.turn),
+                    Wdl::Loss => Some(!self.turn),
+                    _ => None,
+                };
+                self.ui.show_adjudication(winner, "Tablebase adjudication");
+                self.stop_pondering();
+                return;
+            }
+
             self.ui.display_board(&self.board);
-            let curr_move = if self.turn {
-                self.p1.next_move(&self.board)
-            } else {
-                self.p2.next_move(&self.board)
+            self.player(self.turn).strategy.set_history(&self.history);
+            // a player without a move, such as a finished script or a person
+            // who closed the input, loses
+            let curr_move = match self.player(self.turn).try_next_move(&self.board) {
+                Ok(mv) => mv,
+                Err(e) => {
+                    self.ui.show_adjudication(Some(!self.turn), &e);
+                    self.stop_pondering();
+                    return;
+                },
             };
 
-            if let Err(e) = self.board.make_move(curr_move.from, curr_move.to) {
+            // people are asked again, so only a computer gets here and
+            // it would play the same move forever
+            if !self.is_valid_move(&curr_move) {
+                self.ui.show_adjudication(Some(!self.turn), &format!("Illegal move {curr_move}"));
+                self.stop_pondering();
+                return;
+            }
+
+            if let Err(e) = self.board.apply_move(curr_move) {
                 panic!("Unable to move. Error {:?}", e);
             }
+            self.history.push(&self.board);
+
+            if let Some((color, expected)) = self.expected_reply.take() {
+                let player = self.player(color);
+                if expected == curr_move { player.strategy.ponder_hit() } else { player.strategy.ponder_miss() }
+            }
+            if self.ponder && !self.is_game_over() {
+                self.expected_reply = self.player(self.turn).strategy.start_pondering(&self.board)
+                    .map(|reply| (self.turn, reply));
+            }
 
             self.turn = !self.turn;
         }
+
+        self.stop_pondering();
+
+        self.ui.display_board(&self.board);
+        if self.board.is_checkmate() {
+            self.ui.show_winner(!self.turn);
+        } else if self.history.is_threefold(&self.board) {
+            self.ui.show_adjudication(None, "Threefold repetition");
+        } else {
+            self.ui.show_draw();
+        }
+    }
+
+    fn stop_pondering(&mut self) {
+        if let Some((color, _)) = self.expected_reply.take() {
+            self.player(color).strategy.ponder_miss();
+        }
+    }
+
+    fn player(&self, color: bool) -> &Player<'a> {
+        if color { &self.p1 } else { &self.p2 }
     }
 
     fn is_game_over(&self) -> bool {
-        return false;
+        self.board.halfmove_clock >= 100 || self.history.is_threefold(&self.board) || self.board.legal_moves().is_empty()
+    }
+
+    /// Result of the position in the tablebase for the side to move,
+    /// a draw when the fifty move rule comes first.
+    fn tablebase_result(&self) -> Option<Wdl> {
+        let tablebase = self.tablebase.filter(|tablebase| tablebase.covers(&self.board))?;
+        let wdl = tablebase.probe_wdl(&self.board).ok()?;
+        if wdl == Wdl::Win || wdl == Wdl::Loss {
+            if let Ok(dtz) = tablebase.probe_dtz(&self.board) {
+                if dtz.unsigned_abs() + self.board.halfmove_clock > 100 { return Some(Wdl::Draw); }
+            }
+        }
+        Some(wdl)
+    }
+
+    fn is_valid_move(&self, mv: &Move) -> bool {
+        self.board.is_legal(*mv)
+    }
+}
+
+impl<'a> Default for ConsoleEngine<'a> {
+    fn default() -> Self {
+        Self::new(
+            &HumanStrategy { notation: PieceStyle::ENGLISH },
+            &HumanStrategy { notation: PieceStyle::ENGLISH },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_players_finish_a_game() {
+        let (white, black) = (RandomStrategy::new(1), RandomStrategy::new(2));
+        let mut engine = ConsoleEngine::new(&white, &black);
+        engine.game_loop();
+        let board = engine.board();
+        assert!(board.halfmove_clock >= 100 || engine.history().is_threefold(board) || board.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn computer_ponders_against_a_random_player() {
+        let (white, black) = (AlphaBetaStrategy::new(2), RandomStrategy::new(3));
+        let mut engine = ConsoleEngine::new(&white, &black);
+        engine.set_pondering(true);
+        engine.game_loop();
+        let board = engine.board();
+        assert!(board.halfmove_clock >= 100 || engine.history().is_threefold(board) || board.legal_moves().is_empty());
+        assert!(!white.is_pondering());
+    }
+
+    #[test]
+    fn scripted_game_ends_in_mate() {
+        let script = ScriptedStrategy::from_text("1. f3 e5 2. g4 Qh4#");
+        let mut engine = ConsoleEngine::new(&script, &script);
+        engine.game_loop();
+        assert!(engine.board().is_checkmate());
+        assert_eq!(script.remaining(), 0);
+    }
+
+    #[test]
+    fn shuffling_knights_draw_by_repetition() {
+        let script = ScriptedStrategy::from_text("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 5. e4");
+        let mut engine = ConsoleEngine::new(&script, &script);
+        engine.game_loop();
+        assert!(engine.history().is_threefold(engine.board()));
+        assert_eq!(script.remaining(), 1);
+    }
+
+    #[test]
+    fn exhausted_scripts_forfeit() {
+        let script = ScriptedStrategy::from_text("1. e4 e5");
+        let mut engine = ConsoleEngine::new(&script, &script);
+        engine.game_loop();
+        assert_eq!(engine.board().fullmove_number, 2);
+        assert_eq!(script.remaining(), 0);
+    }
+
+    // always answers with the same illegal move
+    struct Stubborn;
+
+    impl Strategy for Stubborn {
+        fn next_move(&self, _board: &Board) -> Move {
+            Move::from_uci("e2e5").unwrap()
+        }
     }
 
-    fn is_valid_move(&self) -> bool {
-        return true;
+    #[test]
+    fn illegal_computer_moves_forfeit() {
+        let mut engine = ConsoleEngine::new(&Stubborn, &Stubborn);
+        engine.game_loop();
+        assert_eq!(engine.board(), &Board::new());
     }
 }
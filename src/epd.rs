@@ -0,0 +1,350 @@
+use std::fmt::Display;
+
+use crate::board::*;
+use crate::notation::*;
+use crate::strategy::*;
+
+/// A single EPD operation such as `bm Nf3;` or `id "WAC.001";`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation {
+    pub opcode: String,
+    pub operands: Vec<String>,
+}
+
+/// Extended Position Description: the first four FEN fields followed by
+/// semicolon terminated operations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epd {
+    pub board: Board,
+    pub operations: Vec<Operation>,
+}
+
+fn is_string_opcode(opcode: &str) -> bool {
+    opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c') && opcode.as_bytes()[1].is_ascii_digit())
+}
+
+fn parse_operations(text: &str) -> Result<Vec<Operation>, String> {
+    let mut operations = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() { break; }
+
+        let mut opcode = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+            opcode.push(c);
+        }
+        if opcode.is_empty() { return Err(format!("Missing opcode in {text}")); }
+
+        let mut operands = Vec::new();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.peek() {
+                None => break,
+                Some(';') => {
+                    chars.next();
+                    break;
+                },
+                Some('"') => {
+                    chars.next();
+                    let mut operand = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => if let Some(c) = chars.next() { operand.push(c); },
+                            Some(c) => operand.push(c),
+                            None => return Err(format!("Unterminated string in {text}")),
+                        }
+                    }
+                    operands.push(operand);
+                },
+                Some(_) => {
+                    let mut operand = String::new();
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                        operand.push(c);
+                    }
+                    operands.push(operand);
+                },
+            }
+        }
+
+        operations.push(Operation { opcode, operands });
+    }
+
+    Ok(operations)
+}
+
+impl Epd {
+    pub fn new(board: Board) -> Self {
+        Self { board, operations: Vec::new() }
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let mut rest = line;
+        let mut fields = Vec::with_capacity(4);
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 { return Err(format!("Invalid epd string {line}")); }
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+
+        let mut epd = Self {
+            board: Board::from_fen(&fields.join(" "))?,
+            operations: parse_operations(rest)?,
+        };
+
+        if let Some(hmvc) = epd.operand("hmvc") {
+            epd.board.halfmove_clock = hmvc.parse().map_err(|_| format!("Invalid hmvc {hmvc}"))?;
+        }
+        if let Some(fmvn) = epd.operand("fmvn") {
+            epd.board.fullmove_number = fmvn.parse().map_err(|_| format!("Invalid fmvn {fmvn}"))?;
+        }
+
+        Ok(epd)
+    }
+
+    /// Parses every non empty line of an EPD file. Lines starting with `#`
+    /// are treated as comments.
+    pub fn parse_all(contents: &str) -> Result<Vec<Self>, String> {
+        contents.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| Self::parse(line).map_err(|e| format!("Line {}: {}", i + 1, e)))
+            .collect()
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&Operation> {
+        self.operations.iter().find(|op| op.opcode == opcode)
+    }
+
+    /// First operand of the given opcode.
+    pub fn operand(&self, opcode: &str) -> Option<&str> {
+        self.operation(opcode).and_then(|op| op.operands.first()).map(|s| s.as_str())
+    }
+
+    /// Replaces the operation with the same opcode or appends a new one.
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|op| op.opcode == opcode) {
+            Some(op) => op.operands = operands,
+            None => self.operations.push(Operation { opcode: String::from(opcode), operands }),
+        }
+    }
+
+    pub fn remove_operation(&mut self, opcode: &str) {
+        self.operations.retain(|op| op.opcode != opcode);
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operand("id")
+    }
+
+    /// Comment operation `c0` to `c9`.
+    pub fn comment(&self, n: u8) -> Option<&str> {
+        if n > 9 { return None; }
+        self.operand(&format!("c{n}"))
+    }
+
+    fn san_moves(&self, opcode: &str) -> Result<Vec<Move>, String> {
+        match self.operation(opcode) {
+            Some(op) => op.operands.iter().map(|san| parse_san(&self.board, san)).collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Moves of the `bm` operation.
+    pub fn best_moves(&self) -> Result<Vec<Move>, String> {
+        self.san_moves("bm")
+    }
+
+    /// Moves of the `am` operation.
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, String> {
+        self.san_moves("am")
+    }
+
+    /// Principal variation of the `pv` operation, played out from the board.
+    pub fn principal_variation(&self) -> Result<Vec<Move>, String> {
+        match self.operation("pv") {
+            Some(op) => {
+                let line: Vec<&str> = op.operands.iter().map(|s| s.as_str()).collect();
+                parse_san_line(&self.board, &line)
+            },
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Analysis count depth of the `acd` operation.
+    pub fn analysis_depth(&self) -> Option<u32> {
+        self.operand("acd").and_then(|s| s.parse().ok())
+    }
+
+    /// Centipawn evaluation of the `ce` operation, from the side to move.
+    pub fn centipawn_evaluation(&self) -> Option<i32> {
+        self.operand("ce").and_then(|s| s.parse().ok())
+    }
+
+    pub fn set_best_moves(&mut self, moves: &[Move]) {
+        let operands = moves.iter().map(|&mv| to_san(&self.board, mv)).collect();
+        self.set_operation("bm", operands);
+    }
+
+    pub fn set_principal_variation(&mut self, moves: &[Move]) {
+        let operands = to_san_line(&self.board, moves);
+        self.set_operation("pv", operands);
+    }
+}
+
+impl Display for Epd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fen = self.board.to_fen();
+        let fields: Vec<&str> = fen.split(' ').take(4).collect();
+        write!(f, "{}", fields.join(" "))?;
+
+        for op in &self.operations {
+            write!(f, " {}", op.opcode)?;
+            for operand in &op.operands {
+                let quote = is_string_opcode(&op.opcode) || operand.is_empty()
+                    || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"');
+                if quote {
+                    write!(f, " \"{}\"", operand.replace('\\', "\\\\").replace('"', "\\\""))?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+
+        std::fmt::Result::Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdTestResult {
+    pub id: String,
+    pub played: Move,
+    pub played_san: String,
+    pub solved: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EpdSuiteReport {
+    pub results: Vec<EpdTestResult>,
+    pub solved: usize,
+    pub failed: usize,
+}
+
+/// Whether the move satisfies the `bm` and `am` operations of the position.
+/// Returns `None` for positions which have neither.
+pub fn is_solution(epd: &Epd, mv: Move) -> Result<Option<bool>, String> {
+    let best = epd.best_moves()?;
+    let avoid = epd.avoid_moves()?;
+    if best.is_empty() && avoid.is_empty() { return Ok(None); }
+
+    Ok(Some((best.is_empty() || best.contains(&mv)) && !avoid.contains(&mv)))
+}
+
+/// Runs the strategy on every position carrying a `bm` or `am` operation.
+/// `on_result` is called after each position so callers can report progress.
+pub fn run_test_suite(
+    positions: &[Epd],
+    strategy: &dyn Strategy,
+    limits: &SearchLimits,
+    mut on_result: impl FnMut(&EpdTestResult),
+) -> Result<EpdSuiteReport, String> {
+    let mut report = EpdSuiteReport::default();
+
+    for (i, epd) in positions.iter().enumerate() {
+        if epd.best_moves()?.is_empty() && epd.avoid_moves()?.is_empty() { continue; }
+
+        let played = strategy.next_move_with_limits(&epd.board, limits);
+        let solved = is_solution(epd, played)?.unwrap_or(false);
+        let result = EpdTestResult {
+            id: epd.id().map_or(format!("#{}", i + 1), String::from),
+            played,
+            played_san: if epd.board.is_legal(played) { to_san(&epd.board, played) } else { played.to_string() },
+            solved,
+        };
+
+        if solved { report.solved += 1; } else { report.failed += 1; }
+        on_result(&result);
+        report.results.push(result);
+    }
+
+    Ok(report)
+}
+
+impl EpdSuiteReport {
+    pub fn summary(&self) -> String {
+        let total = self.solved + self.failed;
+        format!("Solved {}/{} ({} failed)", self.solved, total, self.failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const WAC_001: &str = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#;
+
+    struct FixedStrategy(Move);
+
+    impl Strategy for FixedStrategy {
+        fn next_move(&self, _board: &Board) -> Move {
+            self.0
+        }
+    }
+
+    #[test]
+    fn parse_epd_operations() {
+        let epd = Epd::parse(WAC_001).unwrap();
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.best_moves().unwrap(), vec![Move::from_uci("g3g6").unwrap()]);
+        assert_eq!(epd.board.placement_fen(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1");
+    }
+
+    #[test]
+    fn parse_epd_numeric_and_comment_operations() {
+        let epd = Epd::parse(r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - acd 12; ce -15; pv e4 e5 Nf3; c0 "start; position"; am a4 h4; hmvc 3; fmvn 7;"#).unwrap();
+        assert_eq!(epd.analysis_depth(), Some(12));
+        assert_eq!(epd.centipawn_evaluation(), Some(-15));
+        assert_eq!(epd.comment(0), Some("start; position"));
+        assert_eq!(epd.principal_variation().unwrap().len(), 3);
+        assert_eq!(epd.avoid_moves().unwrap().len(), 2);
+        assert_eq!(epd.board.halfmove_clock, 3);
+        assert_eq!(epd.board.fullmove_number, 7);
+    }
+
+    #[test]
+    fn write_epd_round_trip() {
+        let epd = Epd::parse(WAC_001).unwrap();
+        assert_eq!(epd.to_string(), WAC_001);
+
+        let mut epd = Epd::new(Board::new());
+        epd.set_best_moves(&[Move::from_uci("e2e4").unwrap(), Move::from_uci("d2d4").unwrap()]);
+        epd.set_operation("c1", vec![String::from("a comment")]);
+        epd.set_operation("acd", vec![String::from("5")]);
+        assert_eq!(epd.to_string(), r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 d4; c1 "a comment"; acd 5;"#);
+        assert_eq!(Epd::parse(&epd.to_string()).unwrap(), epd);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid epd string")]
+    fn parse_truncated_epd() {
+        Epd::parse("8/8/8/8/8/8/8/8 w -").unwrap();
+    }
+
+    #[test]
+    fn run_suite_counts_solved_and_failed() {
+        let positions = Epd::parse_all(&format!("# WAC\n{}\n\n{}\n", WAC_001,
+            r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 d4; id "start";"#)).unwrap();
+
+        let report = run_test_suite(&positions, &FixedStrategy(Move::from_uci("g3g6").unwrap()), &SearchLimits::default(), |_| {}).unwrap();
+        assert_eq!(report.solved, 1);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.results[0].played_san, "Qg6");
+        assert_eq!(report.results[1].id, "start");
+    }
+}
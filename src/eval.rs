@@ -0,0 +1,319 @@
+pub mod nnue;
+pub mod weights;
+
+use crate::board::*;
+use crate::board::movegen::*;
+use crate::board::piece::*;
+use crate::board::square::*;
+
+pub use nnue::*;
+pub use weights::*;
+
+/// Static evaluation of a position in centipawns from the side to move.
+/// Evaluators are shared between search threads.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, board: &Board) -> i32;
+
+    /// State for evaluating the positions of one search thread move by
+    /// move, for evaluators which are faster that way.
+    fn incremental(&self) -> Option<Box<dyn IncrementalEvaluator>> {
+        None
+    }
+}
+
+/// Evaluation following a search through the tree. It is told of every move
+/// before the move is made on the board, and of every move taken back.
+pub trait IncrementalEvaluator: Send {
+    /// Starts over from `board`.
+    fn reset(&mut self, board: &Board);
+    fn make(&mut self, board: &Board, mv: Move);
+    fn make_null(&mut self);
+    fn unmake(&mut self);
+    /// The evaluation of `board`, the position reached by the moves made.
+    fn evaluate(&mut self, board: &Board) -> i32;
+}
+
+/// Game phase from 24 with all pieces on the board down to 0 with only kings
+/// and pawns left.
+pub fn game_phase(board: &Board) -> i32 {
+    let phase: i32 = board.cells.iter().filter_map(|cell| cell.piece).map(|piece| match piece.piece_type {
+        PieceType::Knight | PieceType::Bishop => 1,
+        PieceType::Rook => 2,
+        PieceType::Queen => 4,
+        _ => 0,
+    }).sum();
+    phase.min(24)
+}
+
+/// Counts material only, with the classic 1/3/3/5/9 values.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        crate::search::material(board)
+    }
+}
+
+/// Hand-written evaluation of material, piece-square tables, mobility, king
+/// safety, pawn structure and the bishop pair, each tapered between
+/// middlegame and endgame values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClassicalEvaluator {
+    pub weights: EvalWeights,
+}
+
+// Cell as seen from white, so that black pieces can use the same tables.
+fn relative(idx: usize, color: PieceColor) -> usize {
+    if color == PieceColor::White { idx } else { idx ^ 56 }
+}
+
+/// Receives the terms of the classical evaluation, either to add up their
+/// weights or to record which weights were used.
+trait Terms {
+    fn add(&mut self, param: Param, count: i32);
+}
+
+struct Sum<'a> {
+    weights: &'a EvalWeights,
+    score: Score,
+}
+
+impl Terms for Sum<'_> {
+    fn add(&mut self, param: Param, count: i32) {
+        self.score += self.weights.get(param) * count;
+    }
+}
+
+/// How often each weight counts in a position, white's terms positive and
+/// black's negative, for tuning the weights.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub phase: i32,
+    /// Weights by `Param::index` with their count, summed over the board.
+    pub terms: Vec<(usize, i32)>,
+}
+
+impl Terms for Trace {
+    fn add(&mut self, param: Param, count: i32) {
+        let index = param.index();
+        match self.terms.iter_mut().find(|(i, _)| *i == index) {
+            Some((_, total)) => *total += count,
+            None => self.terms.push((index, count)),
+        }
+    }
+}
+
+// Flips the sign of the terms of black's pieces.
+struct Signed<'a, T: Terms> {
+    terms: &'a mut T,
+    sign: i32,
+}
+
+impl<T: Terms> Terms for Signed<'_, T> {
+    fn add(&mut self, param: Param, count: i32) {
+        self.terms.add(param, self.sign * count);
+    }
+}
+
+impl ClassicalEvaluator {
+    pub fn new(weights: EvalWeights) -> Self {
+        Self { weights }
+    }
+
+    /// The weights this evaluator uses in the position, see `Trace`.
+    pub fn trace(board: &Board) -> Trace {
+        let mut trace = Trace { phase: game_phase(board), terms: Vec::new() };
+        Self::terms(board, &mut trace);
+        trace.terms.retain(|&(_, count)| count != 0);
+        trace
+    }
+
+    fn terms(board: &Board, terms: &mut impl Terms) {
+        let mut pawn_files = [[0u8; 8]; 2];
+        for (idx, cell) in board.cells.iter().enumerate() {
+            if let Some(piece) = cell.piece {
+                if piece.piece_type == PieceType::Pawn {
+                    pawn_files[piece.color.index()][col(idx)] += 1;
+                }
+            }
+        }
+
+        Self::side(board, PieceColor::White, &pawn_files, &mut Signed { terms, sign: 1 });
+        Self::side(board, PieceColor::Black, &pawn_files, &mut Signed { terms, sign: -1 });
+    }
+
+    fn side(board: &Board, color: PieceColor, pawn_files: &[[u8; 8]; 2], terms: &mut impl Terms) {
+        let own_pawns = &pawn_files[color.index()];
+        let enemy_pawns = &pawn_files[color.opposite().index()];
+        let mut bishops = 0;
+
+        for (idx, cell) in board.cells.iter().enumerate() {
+            let piece = match cell.piece {
+                Some(piece) if piece.color == color => piece,
+                _ => continue,
+            };
+            let kind = piece.piece_type.index();
+            terms.add(Param::Material(kind), 1);
+            terms.add(Param::Pst(kind, relative(idx, color)), 1);
+
+            match piece.piece_type {
+                PieceType::Knight => terms.add(Param::Mobility(kind), mobility(board, idx, color, &KNIGHT_OFFSETS, false)),
+                PieceType::Bishop => {
+                    bishops += 1;
+                    terms.add(Param::Mobility(kind), mobility(board, idx, color, &BISHOP_OFFSETS, true));
+                },
+                PieceType::Rook => terms.add(Param::Mobility(kind), mobility(board, idx, color, &ROOK_OFFSETS, true)),
+                PieceType::Queen => terms.add(Param::Mobility(kind), mobility(board, idx, color, &KING_OFFSETS, true)),
+                PieceType::Pawn => Self::pawn(board, idx, color, own_pawns, enemy_pawns, terms),
+                PieceType::King => Self::king_safety(board, idx, color, terms),
+            }
+        }
+
+        if bishops >= 2 { terms.add(Param::BishopPair, 1); }
+        for &count in own_pawns {
+            if count > 1 { terms.add(Param::DoubledPawn, count as i32 - 1); }
+        }
+    }
+
+    fn pawn(board: &Board, idx: usize, color: PieceColor, own_pawns: &[u8; 8], enemy_pawns: &[u8; 8], terms: &mut impl Terms) {
+        let file = col(idx);
+        let neighbours = [file.wrapping_sub(1), file + 1].into_iter().filter(|&f| f < 8);
+        if neighbours.clone().all(|f| own_pawns[f] == 0) {
+            terms.add(Param::IsolatedPawn, 1);
+        }
+
+        // passed when no enemy pawn on this or a neighbouring file is ahead
+        let forward: i32 = if color == PieceColor::White { -1 } else { 1 };
+        let blocked = [file as i32 - 1, file as i32, file as i32 + 1].into_iter()
+            .filter(|f| (0..8).contains(f) && enemy_pawns[*f as usize] > 0)
+            .any(|f| {
+                let mut r = row(idx) as i32 + forward;
+                while (0..8).contains(&r) {
+                    if board.cells[from_row_col(r as usize, f as usize)].piece
+                        == Some(Piece { piece_type: PieceType::Pawn, color: color.opposite() }) {
+                        return true;
+                    }
+                    r += forward;
+                }
+                false
+            });
+        if !blocked {
+            terms.add(Param::PassedPawn(7 - row(relative(idx, color))), 1);
+        }
+    }
+
+    fn king_safety(board: &Board, idx: usize, color: PieceColor, terms: &mut impl Terms) {
+        let forward = if color == PieceColor::White { -1 } else { 1 };
+        let own_pawn = Some(Piece { piece_type: PieceType::Pawn, color });
+        let shield = (-1..=1)
+            .filter_map(|dc| offset(idx, forward, dc))
+            .filter(|&cell| board.cells[cell].piece == own_pawn)
+            .count();
+        terms.add(Param::PawnShield, shield as i32);
+
+        let attacked = KING_OFFSETS.iter()
+            .filter_map(|&o| step(idx, o))
+            .filter(|&cell| board.is_square_attacked(cell, color.opposite()))
+            .count();
+        terms.add(Param::KingZoneAttack, attacked as i32);
+    }
+}
+
+// Squares a piece can move to or capture on, ignoring pins.
+fn mobility(board: &Board, from: usize, color: PieceColor, offsets: &[i32], slides: bool) -> i32 {
+    let mut count = 0;
+    for &o in offsets {
+        let mut current = from;
+        while let Some(to) = step(current, o) {
+            match board.cells[to].piece {
+                Some(piece) => {
+                    if piece.color != color { count += 1; }
+                    break;
+                },
+                None => count += 1,
+            }
+            if !slides { break; }
+            current = to;
+        }
+    }
+    count
+}
+
+impl Evaluator for ClassicalEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let mut sum = Sum { weights: &self.weights, score: Score::ZERO };
+        Self::terms(board, &mut sum);
+        let white = sum.score.taper(game_phase(board));
+        if board.side_to_move == PieceColor::White { white } else { -white }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn eval(fen: &str) -> i32 {
+        ClassicalEvaluator::default().evaluate(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn phase_of_start_and_endgame() {
+        assert_eq!(game_phase(&Board::new()), 24);
+        assert_eq!(game_phase(&Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap()), 0);
+    }
+
+    #[test]
+    fn symmetric_positions_are_equal() {
+        assert_eq!(eval(START_FEN), 0);
+        assert_eq!(eval("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4"), 0);
+    }
+
+    #[test]
+    fn score_is_from_the_side_to_move() {
+        let white = eval("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+        let black = eval("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        assert!(white > 0);
+        assert_eq!(white, -black);
+    }
+
+    #[test]
+    fn mirrored_positions_agree() {
+        assert_eq!(eval("4k3/8/8/2P5/8/8/8/4K3 w - - 0 1"), eval("4k3/8/8/8/2p5/8/8/4K3 b - - 0 1"));
+    }
+
+    #[test]
+    fn pawn_structure() {
+        // a passed pawn on the sixth beats one blocked by an enemy pawn
+        assert!(eval("4k3/8/2P5/8/8/8/8/4K3 w - - 0 1") > eval("4k3/2p5/2P5/8/8/8/8/4K3 w - - 0 1") + 82);
+        let doubled = eval("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1");
+        let connected = eval("4k3/8/8/8/8/4P3/3P4/4K3 w - - 0 1");
+        assert!(connected > doubled);
+    }
+
+    #[test]
+    fn bishop_pair_and_king_shelter() {
+        let pair = eval("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let mixed = eval("4k3/8/8/8/8/8/8/2N1KB2 w - - 0 1");
+        assert!(pair > mixed);
+
+        let sheltered = eval("r5k1/5ppp/8/8/8/8/5PPP/R5K1 b - - 0 1");
+        let exposed = eval("r5k1/8/5ppp/8/8/8/5PPP/R5K1 b - - 0 1");
+        assert!(sheltered > exposed);
+    }
+
+    #[test]
+    fn trace_adds_up_to_the_evaluation() {
+        let board = Board::from_fen("r1bqk2r/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/P4PPP/R1BQKB1R w KQkq - 0 1").unwrap();
+        let weights = EvalWeights::default().to_vec();
+        let trace = ClassicalEvaluator::trace(&board);
+        let score = trace.terms.iter().fold(Score::ZERO, |sum, &(index, count)| sum + weights[index] * count);
+        assert_eq!(score.taper(trace.phase), ClassicalEvaluator::default().evaluate(&board));
+    }
+
+    #[test]
+    fn material_evaluator() {
+        assert_eq!(MaterialEvaluator.evaluate(&Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 b - - 0 1").unwrap()), 400);
+    }
+}
@@ -0,0 +1,516 @@
+mod simd;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::board::*;
+use crate::board::piece::*;
+use crate::board::square::*;
+
+use super::*;
+
+// Clipping of the accumulator and scale of the output weights.
+const QA: i32 = 255;
+const QB: i32 = 64;
+// Centipawns per unit of the network output.
+const SCALE: i32 = 400;
+
+/// Inputs of the network, seen from one side: every piece by its kind and
+/// cell, relative to that side's king.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeatureSet {
+    /// All pieces but the kings.
+    HalfKp,
+    /// All pieces including the kings.
+    HalfKa,
+}
+
+impl FeatureSet {
+    // Kinds of piece per side.
+    fn kinds(&self) -> usize {
+        match self {
+            FeatureSet::HalfKp => 5,
+            FeatureSet::HalfKa => 6,
+        }
+    }
+
+    pub fn inputs(&self) -> usize {
+        64 * 2 * self.kinds() * 64
+    }
+
+    /// The input for `piece` on `idx` from the side of `perspective`, whose
+    /// king is on `king`. Cells are flipped for black, so that both sides
+    /// share the weights.
+    pub fn index(&self, perspective: PieceColor, king: usize, piece: Piece, idx: usize) -> Option<usize> {
+        let kind = match piece.piece_type {
+            PieceType::Pawn => 0,
+            PieceType::Knight => 1,
+            PieceType::Bishop => 2,
+            PieceType::Rook => 3,
+            PieceType::Queen => 4,
+            PieceType::King if *self == FeatureSet::HalfKa => 5,
+            PieceType::King => return None,
+        };
+        let kind = if piece.color == perspective { kind } else { kind + self.kinds() };
+        Some((relative(king, perspective) * 2 * self.kinds() + kind) * 64 + relative(idx, perspective))
+    }
+}
+
+/// A quantized network of one hidden layer per side, the side to move's
+/// first, with a single output.
+///
+/// Weight files are little-endian: the magic `CNUE`, the version 1, the
+/// feature set (0 for HalfKP, 1 for HalfKA) and the hidden size as `u32`,
+/// then the `i16` hidden weights input by input, the `i16` hidden biases,
+/// the `i16` output weights and the `i32` output bias. Output weights are
+/// bounded by `Network::max_output_weight`, so that the output layer sums
+/// within `i32`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    pub features: FeatureSet,
+    /// Width of the hidden layer, a multiple of 16 up to `MAX_HIDDEN`.
+    pub hidden: usize,
+    pub feature_weights: Vec<i16>,
+    pub feature_bias: Vec<i16>,
+    /// Weights of the side to move's hidden layer, then the other side's.
+    pub output_weights: Vec<i16>,
+    pub output_bias: i32,
+}
+
+impl Network {
+    const MAGIC: &'static [u8; 4] = b"CNUE";
+    const VERSION: u32 = 1;
+    pub const MAX_HIDDEN: usize = 2048;
+
+    /// The largest output weight magnitude of a hidden layer of `hidden`:
+    /// both sides' clipped layers times their weights stay within `i32`.
+    pub fn max_output_weight(hidden: usize) -> i32 {
+        i32::MAX / (QA * 2 * hidden as i32)
+    }
+
+    /// A network of zero weights, scoring every position 0.
+    pub fn zeroed(features: FeatureSet, hidden: usize) -> Self {
+        Self {
+            features,
+            hidden,
+            feature_weights: vec![0; features.inputs() * hidden],
+            feature_bias: vec![0; hidden],
+            output_weights: vec![0; 2 * hidden],
+            output_bias: 0,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let header = |i: usize| bytes.get(4 * i..4 * i + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+        if bytes.get(..4) != Some(Self::MAGIC.as_slice()) {
+            return Err(String::from("Not a network file"));
+        }
+        if header(1) != Some(Self::VERSION) {
+            return Err(format!("Unsupported network version {}", header(1).unwrap_or(0)));
+        }
+        let features = match header(2) {
+            Some(0) => FeatureSet::HalfKp,
+            Some(1) => FeatureSet::HalfKa,
+            other => return Err(format!("Unknown feature set {}", other.unwrap_or(0))),
+        };
+        let hidden = header(3).unwrap_or(0) as usize;
+        if hidden == 0 || !hidden.is_multiple_of(simd::LANES) {
+            return Err(format!("Hidden size {hidden} is not a multiple of {}", simd::LANES));
+        }
+        if hidden > Self::MAX_HIDDEN {
+            return Err(format!("Hidden size {hidden} is above {}", Self::MAX_HIDDEN));
+        }
+
+        // checked before allocating the weights a corrupt header asks for
+        let expected = features.inputs().checked_mul(hidden)
+            .and_then(|weights| weights.checked_add(3 * hidden))
+            .and_then(|values| values.checked_mul(2))
+            .and_then(|size| size.checked_add(16 + 4))
+            .ok_or_else(|| format!("Hidden size {hidden} is too large"))?;
+        if bytes.len() != expected {
+            return Err(format!("Expected {expected} bytes for the network, found {}", bytes.len()));
+        }
+
+        let mut network = Self::zeroed(features, hidden);
+
+        let mut values = bytes[16..].chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]));
+        for weight in network.feature_weights.iter_mut()
+            .chain(network.feature_bias.iter_mut())
+            .chain(network.output_weights.iter_mut()) {
+            *weight = values.next().unwrap();
+        }
+        network.output_bias = i32::from_le_bytes(bytes[expected - 4..].try_into().unwrap());
+
+        let limit = Self::max_output_weight(hidden);
+        if let Some(weight) = network.output_weights.iter().find(|weight| (**weight as i32).abs() > limit) {
+            return Err(format!("Output weight {weight} is beyond ±{limit}"));
+        }
+        Ok(network)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let features = match self.features {
+            FeatureSet::HalfKp => 0u32,
+            FeatureSet::HalfKa => 1,
+        };
+        let mut bytes = Self::MAGIC.to_vec();
+        for value in [Self::VERSION, features, self.hidden as u32] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for weight in self.feature_weights.iter().chain(&self.feature_bias).chain(&self.output_weights) {
+            bytes.extend_from_slice(&weight.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Unable to read {}. Error {e}", path.display()))?;
+        Self::from_bytes(&bytes).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| format!("Unable to write {}. Error {e}", path.display()))
+    }
+
+    fn row(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    /// The hidden layer of `perspective` computed from scratch.
+    fn refresh(&self, board: &Board, perspective: PieceColor, acc: &mut [i16]) {
+        self.refresh_after(board, &Delta::default(), perspective, king_cell(board, perspective), acc);
+    }
+
+    /// The hidden layer of `perspective` computed from scratch for the
+    /// position after a move which changed `board` by `delta`, with its
+    /// king on `king`.
+    fn refresh_after(&self, board: &Board, delta: &Delta, perspective: PieceColor, king: usize, acc: &mut [i16]) {
+        acc.copy_from_slice(&self.feature_bias);
+        let removed = |idx: usize| delta.removed.iter().flatten().any(|&(_, cell)| cell == idx);
+        let pieces = board.cells.iter().enumerate()
+            .filter(|&(idx, _)| !removed(idx))
+            .filter_map(|(idx, cell)| cell.piece.map(|piece| (piece, idx)))
+            .chain(delta.added.iter().flatten().copied());
+        for (piece, idx) in pieces {
+            if let Some(feature) = self.features.index(perspective, king, piece, idx) {
+                simd::add_assign(acc, self.row(feature));
+            }
+        }
+    }
+
+    /// Centipawns for the side whose hidden layer is `us`.
+    fn output(&self, us: &[i16], them: &[i16]) -> i32 {
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let sum = simd::crelu_dot(us, our_weights, QA as i16) + simd::crelu_dot(them, their_weights, QA as i16);
+        // any output bias fits once scaled down
+        (((sum / QA) as i64 + self.output_bias as i64) * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
+
+fn king_cell(board: &Board, color: PieceColor) -> usize {
+    board.cells.iter()
+        .position(|cell| cell.piece == Some(Piece { piece_type: PieceType::King, color }))
+        .unwrap_or(0)
+}
+
+/// Evaluation by a `Network`. Alone it computes the hidden layers of every
+/// position from scratch, a search keeps them up to date move by move with
+/// `NnueState`.
+#[derive(Clone, Debug)]
+pub struct NnueEvaluator {
+    network: Arc<Network>,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Network) -> Self {
+        Self { network: Arc::new(network) }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Network::load(path).map(Self::new)
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let hidden = self.network.hidden;
+        let mut layers = vec![0; 2 * hidden];
+        let (us, them) = layers.split_at_mut(hidden);
+        self.network.refresh(board, board.side_to_move, us);
+        self.network.refresh(board, board.side_to_move.opposite(), them);
+        self.network.output(us, them)
+    }
+
+    fn incremental(&self) -> Option<Box<dyn IncrementalEvaluator>> {
+        Some(Box::new(NnueState::new(self.network.clone())))
+    }
+}
+
+// Pieces a move takes off or puts on cells: at most two of each, when
+// castling or capturing.
+#[derive(Clone, Copy, Debug, Default)]
+struct Delta {
+    removed: [Option<(Piece, usize)>; 2],
+    added: [Option<(Piece, usize)>; 2],
+}
+
+impl Delta {
+    /// The changes `mv` makes to `board`, found from the move alone.
+    fn of_move(board: &Board, mv: Move) -> Self {
+        let mut delta = Delta::default();
+        let Some(piece) = board.piece_at(mv.from) else { return delta; };
+
+        let landed = mv.promotion.map_or(piece, |piece_type| Piece { piece_type, color: piece.color });
+        delta.removed[0] = Some((piece, mv.from));
+        delta.added[0] = Some((landed, mv.to));
+
+        if let Some(captured) = board.captured_piece(mv) {
+            // en passant takes the pawn beside the one moving
+            let cell = if board.piece_at(mv.to).is_some() { mv.to } else { mv.from - col(mv.from) + col(mv.to) };
+            delta.removed[1] = Some((captured, cell));
+        } else if piece.piece_type == PieceType::King && mv.from.abs_diff(mv.to) == 2 {
+            let (rook_from, rook_to) = if mv.to > mv.from { (mv.from + 3, mv.from + 1) } else { (mv.from - 4, mv.from - 1) };
+            let rook = Piece { piece_type: PieceType::Rook, color: piece.color };
+            delta.removed[1] = Some((rook, rook_from));
+            delta.added[1] = Some((rook, rook_to));
+        }
+        delta
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Accumulator {
+    // hidden layers of white and black
+    layers: [Vec<i16>; 2],
+    // whether each layer is up to date, else it is the previous entry's
+    // plus `delta`
+    computed: [bool; 2],
+    kings: [usize; 2],
+    delta: Delta,
+}
+
+/// The hidden layers of the positions along the current line of a search,
+/// updated lazily from the previous position's with the pieces each move
+/// changed, and computed anew for a side whose king moved.
+pub struct NnueState {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    // entries of `stack` in use, which are kept allocated
+    len: usize,
+}
+
+impl NnueState {
+    pub fn new(network: Arc<Network>) -> Self {
+        Self { network, stack: Vec::new(), len: 0 }
+    }
+
+    fn push(&mut self) -> &mut Accumulator {
+        if self.len == self.stack.len() {
+            let layer = vec![0; self.network.hidden];
+            self.stack.push(Accumulator {
+                layers: [layer.clone(), layer],
+                computed: [false; 2],
+                kings: [0; 2],
+                delta: Delta::default(),
+            });
+        }
+        self.len += 1;
+        &mut self.stack[self.len - 1]
+    }
+
+    /// Brings the layer of `color` in the top entry up to date from the
+    /// last one computed.
+    fn update(&mut self, color: PieceColor) {
+        let side = color.index();
+        let top = self.len - 1;
+        let last = (0..=top).rev().find(|&i| self.stack[i].computed[side]).expect("No computed accumulator");
+        for i in last + 1..=top {
+            let (done, rest) = self.stack.split_at_mut(i);
+            let (previous, entry) = (&done[i - 1], &mut rest[0]);
+            entry.layers[side].copy_from_slice(&previous.layers[side]);
+            let king = entry.kings[side];
+            for (piece, idx) in entry.delta.removed.iter().flatten() {
+                if let Some(feature) = self.network.features.index(color, king, *piece, *idx) {
+                    simd::sub_assign(&mut entry.layers[side], self.network.row(feature));
+                }
+            }
+            for (piece, idx) in entry.delta.added.iter().flatten() {
+                if let Some(feature) = self.network.features.index(color, king, *piece, *idx) {
+                    simd::add_assign(&mut entry.layers[side], self.network.row(feature));
+                }
+            }
+            entry.computed[side] = true;
+        }
+    }
+}
+
+impl IncrementalEvaluator for NnueState {
+    fn reset(&mut self, board: &Board) {
+        self.len = 0;
+        let network = self.network.clone();
+        let entry = self.push();
+        for color in [PieceColor::White, PieceColor::Black] {
+            network.refresh(board, color, &mut entry.layers[color.index()]);
+            entry.kings[color.index()] = king_cell(board, color);
+        }
+        entry.computed = [true; 2];
+    }
+
+    fn make(&mut self, board: &Board, mv: Move) {
+        let delta = Delta::of_move(board, mv);
+        let moved = board.piece_at(mv.from);
+        let kings = self.stack[self.len - 1].kings;
+
+        let network = self.network.clone();
+        let entry = self.push();
+        entry.delta = delta;
+        entry.computed = [false; 2];
+        entry.kings = kings;
+        for color in [PieceColor::White, PieceColor::Black] {
+            let side = color.index();
+            if moved == Some(Piece { piece_type: PieceType::King, color }) {
+                entry.kings[side] = mv.to;
+                network.refresh_after(board, &delta, color, mv.to, &mut entry.layers[side]);
+                entry.computed[side] = true;
+            }
+        }
+    }
+
+    fn make_null(&mut self) {
+        let top = self.len - 1;
+        let kings = self.stack[top].kings;
+        let entry = self.push();
+        entry.kings = kings;
+        entry.delta = Delta::default();
+        entry.computed = [false; 2];
+    }
+
+    fn unmake(&mut self) {
+        self.len -= 1;
+    }
+
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        let (us, them) = (board.side_to_move, board.side_to_move.opposite());
+        self.update(us);
+        self.update(them);
+        let top = &self.stack[self.len - 1];
+        self.network.output(&top.layers[us.index()], &top.layers[them.index()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::*;
+    use crate::search::*;
+    use pretty_assertions::assert_eq;
+
+    fn random_network(features: FeatureSet, seed: u64) -> Network {
+        let mut rng = Rng::new(seed);
+        let mut small = |range: u64| rng.below(2 * range + 1) as i16 - range as i16;
+        let mut network = Network::zeroed(features, 32);
+        network.feature_weights.iter_mut().for_each(|weight| *weight = small(40));
+        network.feature_bias.iter_mut().for_each(|weight| *weight = small(100));
+        network.output_weights.iter_mut().for_each(|weight| *weight = small(64));
+        network.output_bias = 500;
+        network
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let network = random_network(FeatureSet::HalfKa, 1);
+        let bytes = network.to_bytes();
+        assert_eq!(Network::from_bytes(&bytes).unwrap(), network);
+        assert_eq!(Network::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            format!("Expected {} bytes for the network, found {}", bytes.len(), bytes.len() - 1));
+        assert_eq!(Network::from_bytes(b"NNUE").unwrap_err(), "Not a network file");
+    }
+
+    #[test]
+    fn corrupt_headers_are_rejected_before_allocating() {
+        let header = |hidden: u32| [*b"CNUE", 1u32.to_le_bytes(), 1u32.to_le_bytes(), hidden.to_le_bytes()].concat();
+        assert_eq!(Network::from_bytes(&header(1 << 30)).unwrap_err(), "Hidden size 1073741824 is above 2048");
+        assert_eq!(Network::from_bytes(&header(2048)).unwrap_err(),
+            format!("Expected {} bytes for the network, found 16", 16 + 2 * (49152 * 2048 + 3 * 2048) + 4));
+
+        // narrow layers take any weight
+        assert!(Network::max_output_weight(128) > i16::MAX as i32);
+        let mut network = Network::zeroed(FeatureSet::HalfKp, 256);
+        let limit = Network::max_output_weight(network.hidden);
+        network.output_weights[3] = -(limit as i16) - 1;
+        assert_eq!(Network::from_bytes(&network.to_bytes()).unwrap_err(),
+            format!("Output weight {} is beyond ±{limit}", -limit - 1));
+    }
+
+    #[test]
+    fn zero_network_scores_nothing() {
+        let evaluator = NnueEvaluator::new(Network::zeroed(FeatureSet::HalfKp, 16));
+        assert_eq!(evaluator.evaluate(&Board::new()), 0);
+    }
+
+    #[test]
+    fn colours_share_the_weights() {
+        // the same position with colours and board flipped
+        let evaluator = NnueEvaluator::new(random_network(FeatureSet::HalfKp, 2));
+        let white = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let black = Board::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3").unwrap();
+        assert_eq!(evaluator.evaluate(&white), evaluator.evaluate(&black));
+    }
+
+    // The same network evaluating every position from scratch.
+    struct FromScratch(NnueEvaluator);
+
+    impl Evaluator for FromScratch {
+        fn evaluate(&self, board: &Board) -> i32 {
+            self.0.evaluate(board)
+        }
+    }
+
+    #[test]
+    fn search_follows_the_moves() {
+        let evaluator = NnueEvaluator::new(random_network(FeatureSet::HalfKp, 4));
+        let board = Board::from_fen("r3k2r/ppp2ppp/2n5/3qp3/3P4/2N2N2/PPP2PPP/R2QK2R w KQkq - 0 1").unwrap();
+        let mut incremental = Search::new();
+        incremental.set_evaluator(Arc::new(evaluator.clone()));
+        let mut from_scratch = Search::new();
+        from_scratch.set_evaluator(Arc::new(FromScratch(evaluator)));
+        assert_eq!(incremental.search(&board, 4), from_scratch.search(&board, 4));
+    }
+
+    #[test]
+    fn incremental_updates_match_a_fresh_evaluation() {
+        for features in [FeatureSet::HalfKp, FeatureSet::HalfKa] {
+            let evaluator = NnueEvaluator::new(random_network(features, 3));
+            let mut state = evaluator.incremental().unwrap();
+            // castling both ways, en passant, a capturing promotion and king moves
+            let mut board = Board::from_fen("r3k2r/1P6/8/8/3pP3/8/8/R3K2R b KQkq e3 0 1").unwrap();
+            state.reset(&board);
+            let mut undos = Vec::new();
+            for uci in ["d4e3", "e1c1", "e8g8", "b7a8q", "f8a8", "c1b1", "e3e2", "h1h8", "g8h8"] {
+                let mv = Move::from_uci(uci).unwrap();
+                assert!(board.is_legal(mv), "{uci}");
+                state.make(&board, mv);
+                undos.push((mv, board.make(mv)));
+                assert_eq!(state.evaluate(&board), evaluator.evaluate(&board), "{uci}");
+            }
+
+            state.make_null();
+            let undo = board.make_null();
+            assert_eq!(state.evaluate(&board), evaluator.evaluate(&board));
+            state.unmake();
+            board.unmake_null(undo);
+
+            while let Some((mv, undo)) = undos.pop() {
+                state.unmake();
+                board.unmake(mv, undo);
+                assert_eq!(state.evaluate(&board), evaluator.evaluate(&board));
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Vector kernels of the network. The portable versions work in fixed blocks
+// of `LANES` values, which compilers turn into vector instructions on any
+// target. On x86-64 the output layer uses AVX2 when the processor has it.
+
+/// Width of the network layers must be a multiple of this.
+pub const LANES: usize = 16;
+
+pub fn add_assign(acc: &mut [i16], row: &[i16]) {
+    for (acc, row) in acc.chunks_exact_mut(LANES).zip(row.chunks_exact(LANES)) {
+        for i in 0..LANES {
+            acc[i] = acc[i].wrapping_add(row[i]);
+        }
+    }
+}
+
+pub fn sub_assign(acc: &mut [i16], row: &[i16]) {
+    for (acc, row) in acc.chunks_exact_mut(LANES).zip(row.chunks_exact(LANES)) {
+        for i in 0..LANES {
+            acc[i] = acc[i].wrapping_sub(row[i]);
+        }
+    }
+}
+
+/// Sum of the products of `weights` with `acc` clipped to `0..=max`, which
+/// the weights must keep within `i32`: AVX2 wraps where the portable
+/// version would overflow.
+pub fn crelu_dot(acc: &[i16], weights: &[i16], max: i16) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: the processor supports AVX2
+        return unsafe { avx2::crelu_dot(acc, weights, max) };
+    }
+    crelu_dot_portable(acc, weights, max)
+}
+
+pub fn crelu_dot_portable(acc: &[i16], weights: &[i16], max: i16) -> i32 {
+    let mut sums = [0i32; LANES];
+    for (acc, weights) in acc.chunks_exact(LANES).zip(weights.chunks_exact(LANES)) {
+        for i in 0..LANES {
+            sums[i] += acc[i].clamp(0, max) as i32 * weights[i] as i32;
+        }
+    }
+    sums.iter().sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::LANES;
+
+    /// # Safety
+    ///
+    /// The processor must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn crelu_dot(acc: &[i16], weights: &[i16], max: i16) -> i32 {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(max);
+        let mut sum = _mm256_setzero_si256();
+        for (acc, weights) in acc.chunks_exact(LANES).zip(weights.chunks_exact(LANES)) {
+            // SAFETY: both chunks hold 16 values, one unaligned vector
+            let (acc, weights) = unsafe {
+                (_mm256_loadu_si256(acc.as_ptr().cast()), _mm256_loadu_si256(weights.as_ptr().cast()))
+            };
+            let clipped = _mm256_min_epi16(_mm256_max_epi16(acc, zero), max);
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, weights));
+        }
+
+        let mut lanes = [0i32; 8];
+        // SAFETY: the array has room for one unaligned vector
+        unsafe { _mm256_storeu_si256(lanes.as_mut_ptr().cast(), sum) };
+        lanes.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernels_agree_with_plain_arithmetic() {
+        let acc: Vec<i16> = (0..64).map(|i| (i * 37 % 600) as i16 - 200).collect();
+        let weights: Vec<i16> = (0..64).map(|i| (i * 13 % 50) as i16 - 25).collect();
+        let expected: i32 = acc.iter().zip(&weights).map(|(&a, &w)| a.clamp(0, 255) as i32 * w as i32).sum();
+        assert_eq!(crelu_dot(&acc, &weights, 255), expected);
+        assert_eq!(crelu_dot_portable(&acc, &weights, 255), expected);
+
+        let mut sum = acc.clone();
+        add_assign(&mut sum, &weights);
+        sub_assign(&mut sum, &weights);
+        assert_eq!(sum, acc);
+    }
+}
@@ -0,0 +1,432 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+use std::path::Path;
+
+/// A weight with separate middlegame and endgame values, blended by the
+/// game phase.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const ZERO: Score = Score { mg: 0, eg: 0 };
+
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    /// Blends the two values, `phase` going from 24 in the opening to 0 in
+    /// a pawn endgame.
+    pub fn taper(&self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (24 - phase)) / 24
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, factor: i32) -> Score {
+        Score::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+/// A single weight of the classical evaluation, naming an entry of
+/// `EvalWeights`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Param {
+    Material(usize),
+    /// Piece index and cell from white's point of view.
+    Pst(usize, usize),
+    Mobility(usize),
+    PassedPawn(usize),
+    DoubledPawn,
+    IsolatedPawn,
+    BishopPair,
+    PawnShield,
+    KingZoneAttack,
+}
+
+impl Param {
+    /// Number of weights, all of them numbered by `index`.
+    pub const COUNT: usize = 6 + 6 * 64 + 6 + 8 + 5;
+
+    /// Position of the weight in `EvalWeights::to_vec`.
+    pub fn index(&self) -> usize {
+        match *self {
+            Param::Material(piece) => piece,
+            Param::Pst(piece, idx) => 6 + piece * 64 + idx,
+            Param::Mobility(piece) => 390 + piece,
+            Param::PassedPawn(rank) => 396 + rank,
+            Param::DoubledPawn => 404,
+            Param::IsolatedPawn => 405,
+            Param::BishopPair => 406,
+            Param::PawnShield => 407,
+            Param::KingZoneAttack => 408,
+        }
+    }
+}
+
+/// Names of the pieces in weight files, in `PieceType::index` order.
+pub const PIECE_NAMES: [&str; 6] = ["king", "queen", "rook", "bishop", "knight", "pawn"];
+
+/// All weights of the classical evaluation. Arrays by piece are indexed by
+/// `PieceType::index`, piece-square tables by cell from white's point of view
+/// (a8 first) and mirrored for black.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalWeights {
+    pub material: [Score; 6],
+    pub pst: [[Score; 64]; 6],
+    /// Per square a knight, bishop, rook or queen can move to.
+    pub mobility: [Score; 6],
+    /// By rank counted from the pawn's own side.
+    pub passed_pawn: [Score; 8],
+    pub doubled_pawn: Score,
+    pub isolated_pawn: Score,
+    pub bishop_pair: Score,
+    /// Per own pawn in front of the king.
+    pub pawn_shield: Score,
+    /// Per square next to the king attacked by the opponent.
+    pub king_zone_attack: Score,
+}
+
+// Piece-square tables of the "simplified evaluation function", which the
+// endgame shares except for the king.
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+fn table(mg: &[i32; 64], eg: &[i32; 64]) -> [Score; 64] {
+    std::array::from_fn(|idx| Score::new(mg[idx], eg[idx]))
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self {
+            material: [
+                Score::ZERO,
+                Score::new(1025, 936),
+                Score::new(477, 512),
+                Score::new(365, 297),
+                Score::new(337, 281),
+                Score::new(82, 94),
+            ],
+            pst: [
+                table(&KING_MIDDLEGAME_TABLE, &KING_ENDGAME_TABLE),
+                table(&QUEEN_TABLE, &QUEEN_TABLE),
+                table(&ROOK_TABLE, &ROOK_TABLE),
+                table(&BISHOP_TABLE, &BISHOP_TABLE),
+                table(&KNIGHT_TABLE, &KNIGHT_TABLE),
+                table(&PAWN_TABLE, &PAWN_TABLE),
+            ],
+            mobility: [
+                Score::ZERO,
+                Score::new(1, 2),
+                Score::new(2, 4),
+                Score::new(4, 4),
+                Score::new(4, 4),
+                Score::ZERO,
+            ],
+            passed_pawn: [
+                Score::ZERO,
+                Score::new(5, 10),
+                Score::new(5, 15),
+                Score::new(10, 25),
+                Score::new(20, 45),
+                Score::new(35, 70),
+                Score::new(60, 110),
+                Score::ZERO,
+            ],
+            doubled_pawn: Score::new(-10, -20),
+            isolated_pawn: Score::new(-10, -15),
+            bishop_pair: Score::new(30, 50),
+            pawn_shield: Score::new(10, 0),
+            king_zone_attack: Score::new(-8, -2),
+        }
+    }
+}
+
+impl EvalWeights {
+    pub fn get(&self, param: Param) -> Score {
+        match param {
+            Param::Material(piece) => self.material[piece],
+            Param::Pst(piece, idx) => self.pst[piece][idx],
+            Param::Mobility(piece) => self.mobility[piece],
+            Param::PassedPawn(rank) => self.passed_pawn[rank],
+            Param::DoubledPawn => self.doubled_pawn,
+            Param::IsolatedPawn => self.isolated_pawn,
+            Param::BishopPair => self.bishop_pair,
+            Param::PawnShield => self.pawn_shield,
+            Param::KingZoneAttack => self.king_zone_attack,
+        }
+    }
+
+    /// All weights in `Param::index` order.
+    pub fn to_vec(&self) -> Vec<Score> {
+        self.groups().into_iter().flat_map(|(_, scores)| scores.to_vec()).collect()
+    }
+
+    /// Sets all weights from a vector in `Param::index` order.
+    pub fn set_from_slice(&mut self, values: &[Score]) {
+        let mut values = values.iter();
+        for (_, scores) in self.groups_mut() {
+            for score in scores.iter_mut() {
+                *score = *values.next().unwrap();
+            }
+        }
+    }
+
+    /// Every group of weights by the name used in weight files.
+    pub fn groups(&self) -> Vec<(String, &[Score])> {
+        let mut groups: Vec<(String, &[Score])> = vec![(String::from("material"), &self.material)];
+        for (name, table) in PIECE_NAMES.iter().zip(&self.pst) {
+            groups.push((format!("pst.{name}"), table));
+        }
+        groups.extend([
+            (String::from("mobility"), &self.mobility[..]),
+            (String::from("passed_pawn"), &self.passed_pawn[..]),
+            (String::from("doubled_pawn"), std::slice::from_ref(&self.doubled_pawn)),
+            (String::from("isolated_pawn"), std::slice::from_ref(&self.isolated_pawn)),
+            (String::from("bishop_pair"), std::slice::from_ref(&self.bishop_pair)),
+            (String::from("pawn_shield"), std::slice::from_ref(&self.pawn_shield)),
+            (String::from("king_zone_attack"), std::slice::from_ref(&self.king_zone_attack)),
+        ]);
+        groups
+    }
+
+    pub fn groups_mut(&mut self) -> Vec<(String, &mut [Score])> {
+        let mut groups: Vec<(String, &mut [Score])> = vec![(String::from("material"), &mut self.material)];
+        for (name, table) in PIECE_NAMES.iter().zip(&mut self.pst) {
+            groups.push((format!("pst.{name}"), table));
+        }
+        groups.extend([
+            (String::from("mobility"), &mut self.mobility[..]),
+            (String::from("passed_pawn"), &mut self.passed_pawn[..]),
+            (String::from("doubled_pawn"), std::slice::from_mut(&mut self.doubled_pawn)),
+            (String::from("isolated_pawn"), std::slice::from_mut(&mut self.isolated_pawn)),
+            (String::from("bishop_pair"), std::slice::from_mut(&mut self.bishop_pair)),
+            (String::from("pawn_shield"), std::slice::from_mut(&mut self.pawn_shield)),
+            (String::from("king_zone_attack"), std::slice::from_mut(&mut self.king_zone_attack)),
+        ]);
+        groups
+    }
+
+    /// Weights in the text format read by `from_config`: one group per
+    /// line, its name followed by `mg,eg` pairs.
+    pub fn to_config(&self) -> String {
+        let mut text = String::new();
+        for (name, scores) in self.groups() {
+            text.push_str(&name);
+            for score in scores {
+                text.push_str(&format!(" {},{}", score.mg, score.eg));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Reads weights written by `to_config`. Groups missing from the text
+    /// keep their default values, so a file may override a few only. Blank
+    /// lines and lines starting with `#` are skipped.
+    pub fn from_config(text: &str) -> Result<Self, String> {
+        let mut weights = Self::default();
+        weights.apply_config(text)?;
+        Ok(weights)
+    }
+
+    /// Overrides the groups given in `text`, in the format of `to_config`.
+    pub fn apply_config(&mut self, text: &str) -> Result<(), String> {
+        let mut groups = self.groups_mut();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let error = |message: String| format!("Line {}: {message}", number + 1);
+            let mut tokens = line.split_whitespace();
+            let name = tokens.next().unwrap();
+            let scores = match groups.iter_mut().find(|(group, _)| group == name) {
+                Some((_, scores)) => scores,
+                None => return Err(error(format!("Unknown weight {name}"))),
+            };
+
+            let values: Vec<&str> = tokens.collect();
+            if values.len() != scores.len() {
+                return Err(error(format!("Expected {} values for {name}, found {}", scores.len(), values.len())));
+            }
+            for (score, value) in scores.iter_mut().zip(values) {
+                let parsed = value.split_once(',')
+                    .and_then(|(mg, eg)| Some(Score::new(mg.parse().ok()?, eg.parse().ok()?)));
+                *score = parsed.ok_or_else(|| error(format!("Invalid value {value} for {name}")))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}. Error {e}", path.display()))?;
+        Self::from_config(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_config())
+            .map_err(|e| format!("Unable to write {}. Error {e}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn taper_blends_by_phase() {
+        let score = Score::new(100, 40);
+        assert_eq!(score.taper(24), 100);
+        assert_eq!(score.taper(0), 40);
+        assert_eq!(score.taper(12), 70);
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let mut weights = EvalWeights::default();
+        weights.pst[4][27] = Score::new(-7, 13);
+        weights.bishop_pair = Score::new(1, 2);
+        assert_eq!(EvalWeights::from_config(&weights.to_config()).unwrap(), weights);
+    }
+
+    #[test]
+    fn params_index_the_flat_weights() {
+        let weights = EvalWeights::default();
+        let flat = weights.to_vec();
+        assert_eq!(flat.len(), Param::COUNT);
+        for param in [Param::Material(5), Param::Pst(0, 63), Param::Mobility(4), Param::PassedPawn(6), Param::KingZoneAttack] {
+            assert_eq!(flat[param.index()], weights.get(param));
+        }
+
+        let mut changed = EvalWeights::default();
+        let mut values = flat.clone();
+        values[Param::BishopPair.index()] = Score::new(-1, -1);
+        changed.set_from_slice(&values);
+        assert_eq!(changed.bishop_pair, Score::new(-1, -1));
+        assert_eq!(changed.to_vec(), values);
+    }
+
+    #[test]
+    fn partial_config_keeps_defaults() {
+        let weights = EvalWeights::from_config("# sharper\n\nbishop_pair 45,60\nmaterial 0,0 900,900 500,500 300,300 300,300 100,100\n").unwrap();
+        assert_eq!(weights.bishop_pair, Score::new(45, 60));
+        assert_eq!(weights.material[5], Score::new(100, 100));
+        assert_eq!(weights.pst, EvalWeights::default().pst);
+    }
+
+    #[test]
+    fn config_errors() {
+        assert_eq!(EvalWeights::from_config("queen_power 1,2").unwrap_err(), "Line 1: Unknown weight queen_power");
+        assert_eq!(EvalWeights::from_config("\nbishop_pair 1,2 3,4").unwrap_err(), "Line 2: Expected 1 values for bishop_pair, found 2");
+        assert_eq!(EvalWeights::from_config("bishop_pair 12").unwrap_err(), "Line 1: Invalid value 12 for bishop_pair");
+    }
+}
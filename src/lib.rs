@@ -0,0 +1,19 @@
+pub mod arena;
+pub mod board;
+pub mod book;
+pub mod cli;
+pub mod database;
+pub mod datagen;
+pub mod engine;
+pub mod epd;
+pub mod eval;
+pub mod notation;
+pub mod pgn;
+pub mod player;
+pub mod rng;
+pub mod search;
+pub mod solver;
+pub mod strategy;
+pub mod syzygy;
+pub mod tune;
+pub mod ui;
@@ -1,12 +1,15 @@
-mod board;
-mod engine;
-mod player;
-mod strategy;
-mod ui;
-
-use engine::*;
+use chess::engine::*;
 
 fn main() {
-    let mut engine = ConsoleEngine::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(e) = chess::cli::run(&args) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut engine = ConsoleEngine::default();
     engine.game_loop();
 }
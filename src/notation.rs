@@ -0,0 +1,315 @@
+pub mod descriptive;
+pub mod input;
+pub mod letters;
+
+use crate::board::*;
+use crate::board::piece::*;
+use crate::board::square::*;
+
+pub use descriptive::*;
+pub use input::*;
+pub use letters::*;
+
+pub fn is_castling(board: &Board, mv: Move) -> bool {
+    matches!(board.piece_at(mv.from), Some(piece) if piece.piece_type == PieceType::King)
+        && mv.from.abs_diff(mv.to) == 2
+}
+
+pub fn is_capture(board: &Board, mv: Move) -> bool {
+    board.captured_piece(mv).is_some()
+}
+
+/// Check or mate marker to append after a legal move.
+fn check_suffix(board: &Board, mv: Move) -> &'static str {
+    let mut after = board.clone();
+    after.make(mv);
+    if !after.in_check() {
+        ""
+    } else if after.legal_moves().is_empty() {
+        "#"
+    } else {
+        "+"
+    }
+}
+
+/// Renders a legal move in Standard Algebraic Notation, e.g. `Nbd7`, `exd5`,
+/// `e8=Q+` or `O-O`.
+pub fn to_san(board: &Board, mv: Move) -> String {
+    to_san_with(board, mv, &PieceStyle::ENGLISH)
+}
+
+/// Like `to_san` but naming the pieces in the given style, e.g. `Sbd7` with
+/// German letters or `♘bd7` with figurines.
+pub fn to_san_with(board: &Board, mv: Move, style: &PieceStyle) -> String {
+    let piece = match board.piece_at(mv.from) {
+        Some(piece) => piece,
+        None => return mv.to_string(),
+    };
+
+    let mut san = String::new();
+    if is_castling(board, mv) {
+        san.push_str(if mv.to > mv.from { "O-O" } else { "O-O-O" });
+    } else {
+        match style.piece_text(piece) {
+            Some(text) => {
+                san.push_str(&text);
+                san.push_str(&disambiguation(board, mv, piece));
+            },
+            None => if is_capture(board, mv) { san.push(file_char(mv.from)); },
+        }
+
+        if is_capture(board, mv) { san.push('x'); }
+        san.push_str(&square_name(mv.to));
+
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push_str(&style.piece_text(Piece { piece_type: promotion, color: piece.color }).unwrap_or_default());
+        }
+    }
+
+    san.push_str(check_suffix(board, mv));
+    san
+}
+
+fn disambiguation(board: &Board, mv: Move, piece: Piece) -> String {
+    let rivals: Vec<Move> = board.legal_moves().into_iter()
+        .filter(|other| other.to == mv.to && other.from != mv.from && board.piece_at(other.from) == Some(piece))
+        .collect();
+
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|other| col(other.from) != col(mv.from)) {
+        file_char(mv.from).to_string()
+    } else if rivals.iter().all(|other| row(other.from) != row(mv.from)) {
+        rank_char(mv.from).to_string()
+    } else {
+        square_name(mv.from)
+    }
+}
+
+/// Parses a move in Standard Algebraic Notation against the legal moves of
+/// the board. Check markers and annotations are ignored and redundant
+/// disambiguation is accepted.
+pub fn parse_san(board: &Board, san: &str) -> Result<Move, String> {
+    parse_san_with(board, san, &PieceStyle::ENGLISH)
+}
+
+/// Like `parse_san` but with pieces named in the given style.
+pub fn parse_san_with(board: &Board, san: &str, style: &PieceStyle) -> Result<Move, String> {
+    let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal = board.legal_moves();
+
+    if let Some(kingside) = match text {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    } {
+        return legal.into_iter()
+            .find(|&mv| is_castling(board, mv) && (mv.to > mv.from) == kingside)
+            .ok_or(format!("Illegal move {san}"));
+    }
+
+    let mut chars: Vec<char> = text.chars().filter(|&c| c != 'x' && c != ':' && c != '-').collect();
+
+    let piece_type = match chars.first().copied().and_then(|c| style.piece_type(c)) {
+        Some(piece_type) => {
+            chars.remove(0);
+            piece_type
+        },
+        None => PieceType::Pawn,
+    };
+
+    let mut promotion = None;
+    if let Some(&last) = chars.last() {
+        let promoted = style.piece_type(last)
+            .filter(|&promoted| piece_type == PieceType::Pawn && promoted != PieceType::King);
+        if let Some(promoted) = promoted {
+            promotion = Some(promoted);
+            chars.pop();
+            if chars.last() == Some(&'=') { chars.pop(); }
+        }
+    }
+
+    if chars.len() < 2 { return Err(format!("Invalid move {san}")); }
+    let destination: String = chars[chars.len()-2..].iter().collect();
+    // an uppercase file is descriptive notation, like the B3 of N-B3
+    if !chars[chars.len()-2].is_ascii_lowercase() { return Err(format!("Invalid move {san}")); }
+    let to = parse_square(&destination).ok_or(format!("Invalid move {san}"))?;
+
+    let mut from_file = None;
+    let mut from_rank = None;
+    for &c in &chars[..chars.len()-2] {
+        match c {
+            'a'..='h' => from_file = Some(c as usize - 'a' as usize),
+            '1'..='8' => from_rank = Some(c as usize - '0' as usize),
+            _ => return Err(format!("Invalid move {san}")),
+        }
+    }
+
+    let candidates: Vec<Move> = legal.into_iter()
+        .filter(|mv| mv.to == to && mv.promotion == promotion)
+        .filter(|mv| matches!(board.piece_at(mv.from), Some(piece) if piece.piece_type == piece_type))
+        .filter(|mv| from_file.is_none_or(|f| col(mv.from) == f))
+        .filter(|mv| from_rank.is_none_or(|r| rank(mv.from) == r))
+        .collect();
+
+    match candidates.len() {
+        0 => Err(format!("Illegal move {san}")),
+        1 => Ok(candidates[0]),
+        _ => Err(format!("Ambiguous move {san}")),
+    }
+}
+
+/// Plays a sequence of SAN moves from the given board and returns the moves.
+pub fn parse_san_line(board: &Board, line: &[&str]) -> Result<Vec<Move>, String> {
+    let mut board = board.clone();
+    let mut moves = Vec::with_capacity(line.len());
+    for san in line {
+        let mv = parse_san(&board, san)?;
+        board.make(mv);
+        moves.push(mv);
+    }
+
+    Ok(moves)
+}
+
+/// Renders a sequence of moves played from the given board in SAN.
+pub fn to_san_line(board: &Board, moves: &[Move]) -> Vec<String> {
+    to_san_line_with(board, moves, &PieceStyle::ENGLISH)
+}
+
+pub fn to_san_line_with(board: &Board, moves: &[Move], style: &PieceStyle) -> Vec<String> {
+    let mut board = board.clone();
+    moves.iter().map(|&mv| {
+        let san = to_san_with(&board, mv, style);
+        board.make(mv);
+        san
+    }).collect()
+}
+
+/// Numbered move text for a score sheet, e.g. `1. e4 e5 2. Nf3`. A game
+/// starting with black to move begins with `1...`.
+pub fn score_sheet(board: &Board, moves: &[Move], style: &PieceStyle) -> String {
+    number_moves(board, &to_san_line_with(board, moves, style))
+}
+
+/// Joins already rendered moves played from the board into numbered move text.
+pub fn number_moves(board: &Board, rendered: &[String]) -> String {
+    let mut number = board.fullmove_number;
+    let mut white = board.side_to_move == PieceColor::White;
+    let mut text = String::new();
+
+    for (i, san) in rendered.iter().enumerate() {
+        if !text.is_empty() { text.push(' '); }
+        if white {
+            text.push_str(&format!("{number}. "));
+        } else if i == 0 {
+            text.push_str(&format!("{number}... "));
+        }
+        text.push_str(san);
+
+        if !white { number += 1; }
+        white = !white;
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn san_of_simple_moves() {
+        let board = Board::new();
+        assert_eq!(to_san(&board, Move::from_uci("e2e4").unwrap()), "e4");
+        assert_eq!(to_san(&board, Move::from_uci("g1f3").unwrap()), "Nf3");
+    }
+
+    #[test]
+    fn san_disambiguation_and_captures() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::from_uci("e5f7").unwrap()), "Nxf7");
+        assert_eq!(to_san(&board, Move::from_uci("d5e6").unwrap()), "dxe6");
+        assert_eq!(to_san(&board, Move::from_uci("e1g1").unwrap()), "O-O");
+        assert_eq!(to_san(&board, Move::from_uci("e1c1").unwrap()), "O-O-O");
+        assert_eq!(to_san(&board, Move::from_uci("c3b1").unwrap()), "Nb1");
+        assert_eq!(to_san(&board, Move::from_uci("d2c1").unwrap()), "Bc1");
+
+        let rooks = Board::from_fen("4k3/8/8/R6R/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(to_san(&rooks, Move::from_uci("a5d5").unwrap()), "Rad5");
+        assert_eq!(to_san(&rooks, Move::from_uci("a5a3").unwrap()), "R5a3");
+        assert_eq!(to_san(&rooks, Move::from_uci("h5e5").unwrap()), "Rhe5+");
+    }
+
+    #[test]
+    fn san_promotion_and_mate() {
+        let board = Board::from_fen("7k/P7/6K1/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(to_san(&board, Move::with_promotion(8, 0, PieceType::Queen)), "a8=Q#");
+        assert_eq!(to_san(&board, Move::with_promotion(8, 0, PieceType::Knight)), "a8=N");
+    }
+
+    #[test]
+    fn parse_san_round_trips_all_legal_moves() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            for mv in board.legal_moves() {
+                assert_eq!(parse_san(&board, &to_san(&board, mv)).unwrap(), mv);
+            }
+        }
+    }
+
+    #[test]
+    fn localized_san() {
+        let board = Board::new();
+        let german = PieceStyle::Letters(PieceLetters::GERMAN);
+        let french = PieceStyle::Letters(PieceLetters::FRENCH);
+        let knight = Move::from_uci("g1f3").unwrap();
+
+        assert_eq!(to_san_with(&board, knight, &german), "Sf3");
+        assert_eq!(to_san_with(&board, knight, &french), "Cf3");
+        assert_eq!(parse_san_with(&board, "Sf3", &german).unwrap(), knight);
+        assert_eq!(parse_san_with(&board, "Cf3", &french).unwrap(), knight);
+        assert!(parse_san_with(&board, "Nf3", &german).is_err());
+
+        let promotion = Board::from_fen("7k/P7/6K1/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(to_san_with(&promotion, Move::with_promotion(8, 0, PieceType::Queen), &german), "a8=D#");
+        assert_eq!(parse_san_with(&promotion, "a8=T", &german).unwrap(), Move::with_promotion(8, 0, PieceType::Rook));
+    }
+
+    #[test]
+    fn figurine_san() {
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let knight = Move::from_uci("g8f6").unwrap();
+        assert_eq!(to_san_with(&board, knight, &PieceStyle::Figurine), "\u{265E}f6");
+        assert_eq!(parse_san_with(&board, "\u{2658}f6", &PieceStyle::Figurine).unwrap(), knight);
+    }
+
+    #[test]
+    fn score_sheets() {
+        let moves: Vec<Move> = ["e2e4", "e7e5", "g1f3"].iter().map(|m| Move::from_uci(m).unwrap()).collect();
+        assert_eq!(score_sheet(&Board::new(), &moves, &PieceStyle::ENGLISH), "1. e4 e5 2. Nf3");
+        assert_eq!(score_sheet(&Board::new(), &moves, &PieceStyle::Figurine), "1. e4 e5 2. \u{2658}f3");
+
+        let mut board = Board::new();
+        board.make(moves[0]);
+        assert_eq!(score_sheet(&board, &moves[1..], &PieceStyle::Letters(PieceLetters::GERMAN)), "1... e5 2. Sf3");
+    }
+
+    #[test]
+    fn parse_lenient_san() {
+        let board = Board::new();
+        assert_eq!(parse_san(&board, "Ng1f3").unwrap(), Move::from_uci("g1f3").unwrap());
+        assert_eq!(parse_san(&board, "e4!?").unwrap(), Move::from_uci("e2e4").unwrap());
+        assert!(parse_san(&board, "e5").unwrap_err().contains("Illegal move"));
+
+        let knights = Board::from_fen("4k3/8/8/8/8/8/8/1N3NK1 w - - 0 1").unwrap();
+        assert!(parse_san(&knights, "Nd2").unwrap_err().contains("Ambiguous move"));
+        assert_eq!(parse_san(&knights, "Nbd2").unwrap(), Move::from_uci("b1d2").unwrap());
+    }
+}
@@ -0,0 +1,411 @@
+use crate::board::*;
+use crate::board::piece::*;
+use crate::board::square::*;
+
+use super::*;
+
+// English descriptive notation names files after the pieces which start on
+// them and counts ranks from the side of the player making the move, so
+// white's e4 and black's e5 are both K4.
+const FILE_NAMES: [&str; 8] = ["QR", "QN", "QB", "Q", "K", "KB", "KN", "KR"];
+
+fn short_file_name(col: usize) -> &'static str {
+    match col {
+        0 | 7 => "R",
+        1 | 6 => "N",
+        2 | 5 => "B",
+        3 => "Q",
+        _ => "K",
+    }
+}
+
+fn relative_rank(idx: usize, color: PieceColor) -> usize {
+    if color == PieceColor::White { rank(idx) } else { 9 - rank(idx) }
+}
+
+fn piece_char(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::King => 'K',
+        PieceType::Queen => 'Q',
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Knight => 'N',
+        PieceType::Pawn => 'P',
+    }
+}
+
+fn piece_from_char(c: char) -> Option<PieceType> {
+    match c {
+        'K' => Some(PieceType::King),
+        'Q' => Some(PieceType::Queen),
+        'R' => Some(PieceType::Rook),
+        'B' => Some(PieceType::Bishop),
+        'N' => Some(PieceType::Knight),
+        'P' => Some(PieceType::Pawn),
+        _ => None,
+    }
+}
+
+/// Files matching a file name such as `KB` (the f file) or `B` (c or f).
+fn files_named(name: &str) -> Option<Vec<usize>> {
+    if let Some(col) = FILE_NAMES.iter().position(|&n| n == name) {
+        return Some(vec![col]);
+    }
+
+    let files: Vec<usize> = (0..8).filter(|&col| short_file_name(col) == name).collect();
+    if files.is_empty() { None } else { Some(files) }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct SquareSpec {
+    files: Option<Vec<usize>>,
+    rank: usize,
+}
+
+impl SquareSpec {
+    fn parse(text: &str) -> Option<Self> {
+        let rank = text.chars().last()?.to_digit(10)? as usize;
+        if !(1..=8).contains(&rank) { return None; }
+
+        let name = &text[..text.len()-1];
+        let files = if name.is_empty() { None } else { Some(files_named(name)?) };
+        Some(Self { files, rank })
+    }
+
+    fn matches(&self, idx: usize, color: PieceColor) -> bool {
+        relative_rank(idx, color) == self.rank
+            && self.files.as_ref().is_none_or(|files| files.contains(&col(idx)))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct PieceSpec {
+    piece_type: PieceType,
+    files: Option<Vec<usize>>,
+    square: Option<SquareSpec>,
+}
+
+impl PieceSpec {
+    /// Parses `P`, `KBP`, `N`, `QN`, `R(1)`, `N/Q2` and similar.
+    fn parse(text: &str) -> Option<Self> {
+        let (name, square) = match text.find(['(', '/']) {
+            Some(i) => {
+                let inner = text[i+1..].trim_end_matches(')');
+                (&text[..i], Some(SquareSpec::parse(inner)?))
+            },
+            None => (text, None),
+        };
+
+        let piece_type = piece_from_char(name.chars().last()?)?;
+        let prefix = &name[..name.len()-1];
+        let files = match (piece_type, prefix) {
+            (_, "") => None,
+            (PieceType::Pawn, _) => Some(files_named(prefix)?),
+            (PieceType::Rook | PieceType::Knight | PieceType::Bishop, "K") => Some(vec![4, 5, 6, 7]),
+            (PieceType::Rook | PieceType::Knight | PieceType::Bishop, "Q") => Some(vec![0, 1, 2, 3]),
+            _ => return None,
+        };
+
+        Some(Self { piece_type, files, square })
+    }
+
+    fn matches(&self, board: &Board, idx: usize, piece_type: PieceType) -> bool {
+        piece_type == self.piece_type
+            && self.files.as_ref().is_none_or(|files| files.contains(&col(idx)))
+            && self.square.as_ref().is_none_or(|square| square.matches(idx, board.side_to_move))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    Square(SquareSpec),
+    Capture(PieceSpec),
+}
+
+fn normalize(text: &str) -> String {
+    let mut text = text.trim().to_uppercase().replace("KT", "N").replace(' ', "");
+    for suffix in ["DISCH", "DBLCH", "CH", "MATE", "E.P.", "EP", "+", "#", "!", "?", "."] {
+        while text.len() > suffix.len() && text.ends_with(suffix) {
+            text.truncate(text.len() - suffix.len());
+        }
+    }
+    // a second pass catches checks written after e.p., like PxP e.p. ch
+    for suffix in ["E.P.", "EP", "."] {
+        if text.ends_with(suffix) { text.truncate(text.len() - suffix.len()); }
+    }
+    text
+}
+
+fn castling_side(text: &str) -> Option<bool> {
+    match text {
+        "O-O" | "0-0" | "CASTLES" | "CASTLESK" | "CASTLES(K)" | "CASTLES(KR)" => Some(true),
+        "O-O-O" | "0-0-0" | "CASTLESQ" | "CASTLES(Q)" | "CASTLES(QR)" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a move in English descriptive notation, such as `P-K4`, `N-KB3`,
+/// `PxP`, `R(1)-K1`, `P-K8(Q)` or `O-O`, against the legal moves of the board.
+/// Ranks are counted from the side to move.
+pub fn parse_descriptive(board: &Board, input: &str) -> Result<Move, String> {
+    let text = normalize(input);
+    let legal = board.legal_moves();
+
+    if let Some(kingside) = castling_side(&text) {
+        return legal.into_iter()
+            .find(|&mv| is_castling(board, mv) && (mv.to > mv.from) == kingside)
+            .ok_or(format!("Illegal move {input}"));
+    }
+
+    let mut body = text.as_str();
+    let mut promotion = None;
+    for (open, close) in [("(", ")"), ("=", ""), ("/", "")] {
+        if let Some(i) = body.rfind(open) {
+            let inner = body[i+1..].trim_end_matches(close);
+            if inner.len() == 1 {
+                if let Some(piece_type) = piece_from_char(inner.chars().next().unwrap()) {
+                    promotion = Some(piece_type);
+                    body = &body[..i];
+                    break;
+                }
+            }
+        }
+    }
+
+    let (mover, target) = match (body.find('-'), body.find('X')) {
+        (Some(i), _) => (&body[..i], Target::Square(SquareSpec::parse(&body[i+1..]).ok_or(format!("Invalid move {input}"))?)),
+        (None, Some(i)) => (&body[..i], Target::Capture(PieceSpec::parse(&body[i+1..]).ok_or(format!("Invalid move {input}"))?)),
+        (None, None) => return Err(format!("Invalid move {input}")),
+    };
+    let mover = PieceSpec::parse(mover).ok_or(format!("Invalid move {input}"))?;
+
+    let candidates: Vec<Move> = legal.into_iter()
+        .filter(|&mv| !is_castling(board, mv) && mv.promotion == promotion)
+        .filter(|&mv| match board.piece_at(mv.from) {
+            Some(piece) => mover.matches(board, mv.from, piece.piece_type),
+            None => false,
+        })
+        .filter(|&mv| match &target {
+            Target::Square(square) => square.matches(mv.to, board.side_to_move),
+            Target::Capture(captured) => is_capture(board, mv) && captured.matches(
+                board, captured_square(board, mv), board.piece_at(mv.to).map_or(PieceType::Pawn, |piece| piece.piece_type)),
+        })
+        .collect();
+
+    match candidates.len() {
+        0 => Err(format!("Illegal move {input}")),
+        1 => Ok(candidates[0]),
+        _ => Err(format!("Ambiguous move {input}")),
+    }
+}
+
+/// Square of the piece taken by a capture, which differs from the target
+/// square for en passant.
+fn captured_square(board: &Board, mv: Move) -> usize {
+    match board.piece_at(mv.to) {
+        Some(_) => mv.to,
+        None if board.side_to_move == PieceColor::White => mv.to + 8,
+        None => mv.to - 8,
+    }
+}
+
+fn square_names(idx: usize, color: PieceColor) -> Vec<String> {
+    let rank = relative_rank(idx, color);
+    let mut names = vec![format!("{}{}", short_file_name(col(idx)), rank)];
+    let full = format!("{}{}", FILE_NAMES[col(idx)], rank);
+    if !names.contains(&full) { names.push(full); }
+    names
+}
+
+/// Ways to name the piece on the square, from the shortest to the most
+/// precise. Squares are named from the side to move.
+fn piece_names(board: &Board, idx: usize, piece_type: PieceType, captured: bool) -> Vec<String> {
+    let color = board.side_to_move;
+    let letter = piece_char(piece_type);
+    let mut names = vec![letter.to_string()];
+    match piece_type {
+        PieceType::Pawn => {
+            names.push(format!("{}P", short_file_name(col(idx))));
+            names.push(format!("{}P", FILE_NAMES[col(idx)]));
+        },
+        PieceType::Rook | PieceType::Knight | PieceType::Bishop => {
+            names.push(format!("{}{}", if col(idx) >= 4 { 'K' } else { 'Q' }, letter));
+        },
+        _ => {},
+    }
+
+    let mut with_square: Vec<String> = square_names(idx, color).iter()
+        .map(|square| format!("{letter}({square})"))
+        .collect();
+    names.append(&mut with_square);
+    names.dedup();
+
+    if captured {
+        // captured pieces are also named with their square after a slash
+        let extra: Vec<String> = names.iter()
+            .filter(|name| !name.contains('('))
+            .flat_map(|name| square_names(idx, color).into_iter().map(move |square| format!("{name}/{square}")))
+            .collect();
+        names.extend(extra);
+    }
+    names
+}
+
+/// Renders a legal move in English descriptive notation using the shortest
+/// form which is not ambiguous, e.g. `P-K4`, `N-B3`, `PxP` or `QRxN`.
+pub fn to_descriptive(board: &Board, mv: Move) -> String {
+    let piece = match board.piece_at(mv.from) {
+        Some(piece) => piece,
+        None => return mv.to_string(),
+    };
+
+    let check = if board.is_legal(mv) {
+        let mut after = board.clone();
+        after.make(mv);
+        if after.is_checkmate() { " mate" } else if after.in_check() { " ch" } else { "" }
+    } else {
+        ""
+    };
+
+    if is_castling(board, mv) {
+        return format!("{}{}", if mv.to > mv.from { "O-O" } else { "O-O-O" }, check);
+    }
+
+    let movers = piece_names(board, mv.from, piece.piece_type, false);
+    let targets = if is_capture(board, mv) {
+        let captured_idx = captured_square(board, mv);
+        let captured = board.piece_at(captured_idx).map_or(PieceType::Pawn, |piece| piece.piece_type);
+        piece_names(board, captured_idx, captured, true).into_iter()
+            .map(|name| format!("x{name}"))
+            .collect()
+    } else {
+        square_names(mv.to, piece.color).into_iter().map(|name| format!("-{name}")).collect::<Vec<String>>()
+    };
+
+    let promotion = mv.promotion.map_or(String::new(), |piece_type| format!("({})", piece_char(piece_type)));
+    let mut candidates: Vec<String> = movers.iter()
+        .flat_map(|mover| targets.iter().map(move |target| format!("{mover}{target}")))
+        .map(|text| format!("{text}{promotion}"))
+        .collect();
+    candidates.sort_by_key(|text| text.len());
+
+    let en_passant = if is_capture(board, mv) && board.piece_at(mv.to).is_none() { " e.p." } else { "" };
+    let text = candidates.iter()
+        .find(|text| parse_descriptive(board, text) == Ok(mv))
+        .cloned()
+        .unwrap_or_else(|| candidates.last().cloned().unwrap_or_default());
+
+    format!("{text}{en_passant}{check}")
+}
+
+/// Renders a sequence of moves played from the given board in descriptive
+/// notation.
+pub fn to_descriptive_line(board: &Board, moves: &[Move]) -> Vec<String> {
+    let mut board = board.clone();
+    moves.iter().map(|&mv| {
+        let text = to_descriptive(&board, mv);
+        board.make(mv);
+        text
+    }).collect()
+}
+
+/// Parses a sequence of descriptive moves played from the given board.
+pub fn parse_descriptive_line(board: &Board, line: &[&str]) -> Result<Vec<Move>, String> {
+    let mut board = board.clone();
+    let mut moves = Vec::with_capacity(line.len());
+    for text in line {
+        let mv = parse_descriptive(&board, text)?;
+        board.make(mv);
+        moves.push(mv);
+    }
+
+    Ok(moves)
+}
+
+/// Numbered descriptive move text, e.g. `1. P-K4 P-K4 2. N-KB3`.
+pub fn descriptive_score_sheet(board: &Board, moves: &[Move]) -> String {
+    number_moves(board, &to_descriptive_line(board, moves))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn uci(s: &str) -> Move {
+        Move::from_uci(s).unwrap()
+    }
+
+    #[test]
+    fn parse_side_relative_squares() {
+        let board = Board::new();
+        assert_eq!(parse_descriptive(&board, "P-K4").unwrap(), uci("e2e4"));
+        assert_eq!(parse_descriptive(&board, "N-KB3").unwrap(), uci("g1f3"));
+        assert_eq!(parse_descriptive(&board, "Kt-QB3").unwrap(), uci("b1c3"));
+        assert!(parse_descriptive(&board, "N-B3").unwrap_err().contains("Ambiguous"));
+
+        let mut board = Board::new();
+        board.make(uci("e2e4"));
+        assert_eq!(parse_descriptive(&board, "P-K4").unwrap(), uci("e7e5"));
+        assert_eq!(parse_descriptive(&board, "P-QB4").unwrap(), uci("c7c5"));
+        assert_eq!(parse_descriptive(&board, "QKtP-Kt3").unwrap(), uci("b7b6"));
+    }
+
+    #[test]
+    fn parse_ambiguous_captures() {
+        // 1. e4 d5 2. c4 e6: white pawns on c4 and e4 can both take on d5
+        let board = Board::from_fen("rnbqkbnr/ppp2ppp/4p3/3p4/2P1P3/8/PP1P1PPP/RNBQKBNR w KQkq - 0 3").unwrap();
+        assert!(parse_descriptive(&board, "PxP").unwrap_err().contains("Ambiguous"));
+        assert_eq!(parse_descriptive(&board, "KPxP").unwrap(), uci("e4d5"));
+        assert_eq!(parse_descriptive(&board, "BPxP").unwrap(), uci("c4d5"));
+        assert_eq!(to_descriptive(&board, uci("e4d5")), "KPxP");
+        assert_eq!(to_descriptive(&board, uci("c4d5")), "BPxP");
+    }
+
+    #[test]
+    fn render_shortest_unambiguous_form() {
+        let board = Board::new();
+        assert_eq!(to_descriptive(&board, uci("e2e4")), "P-K4");
+        assert_eq!(to_descriptive(&board, uci("g1f3")), "N-KB3");
+
+        let mut board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        assert_eq!(to_descriptive(&board, uci("f1b5")), "B-N5");
+        assert_eq!(to_descriptive(&board, uci("b1c3")), "N-B3");
+        board.make(uci("f1b5"));
+        board.make(uci("a7a6"));
+        assert_eq!(to_descriptive(&board, uci("b5c6")), "BxN");
+        assert_eq!(to_descriptive(&board, uci("e1g1")), "O-O");
+    }
+
+    #[test]
+    fn promotion_check_and_en_passant() {
+        let board = Board::from_fen("7k/P7/6K1/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(to_descriptive(&board, Move::with_promotion(8, 0, PieceType::Queen)), "P-R8(Q) mate");
+        assert_eq!(parse_descriptive(&board, "P-R8=N").unwrap(), Move::with_promotion(8, 0, PieceType::Knight));
+
+        let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        assert_eq!(to_descriptive(&board, uci("e5d6")), "PxP e.p.");
+        assert_eq!(parse_descriptive(&board, "PxP e.p.").unwrap(), uci("e5d6"));
+    }
+
+    #[test]
+    fn round_trip_all_legal_moves() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            for mv in board.legal_moves() {
+                assert_eq!(parse_descriptive(&board, &to_descriptive(&board, mv)).unwrap(), mv, "{}", to_descriptive(&board, mv));
+            }
+        }
+    }
+
+    #[test]
+    fn descriptive_game_text() {
+        let moves = parse_descriptive_line(&Board::new(), &["P-K4", "P-K4", "N-KB3", "N-QB3", "B-N5"]).unwrap();
+        assert_eq!(to_san_line(&Board::new(), &moves), vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(descriptive_score_sheet(&Board::new(), &moves), "1. P-K4 P-K4 2. N-KB3 N-QB3 3. B-N5");
+    }
+}
@@ -0,0 +1,308 @@
+use crate::board::*;
+use crate::board::piece::*;
+use crate::board::square::*;
+
+use super::*;
+
+fn compact(input: &str) -> String {
+    input.trim()
+        .trim_end_matches(['+', '#', '!', '?'])
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != ':')
+        .collect()
+}
+
+fn castling_move(board: &Board, text: &str) -> Option<Move> {
+    let text = text.to_uppercase().replace('0', "O");
+    let kingside = match text.as_str() {
+        "OO" => true,
+        "OOO" => false,
+        _ => return None,
+    };
+
+    board.legal_moves().into_iter()
+        .find(|&mv| is_castling(board, mv) && (mv.to > mv.from) == kingside)
+}
+
+/// Coordinates like `e2e4`, `E2xE4` or `e7e8`, where a missing promotion piece
+/// means a queen. A leading piece letter, as in `Ng1f3`, must name the piece
+/// on the from square.
+fn coordinate_move(board: &Board, text: &str, style: &PieceStyle) -> Option<Move> {
+    let lower = text.to_lowercase().replace('x', "");
+    let (piece_type, coordinates) = match Move::from_uci(&lower) {
+        Ok(_) => (None, lower),
+        Err(_) => {
+            let first = text.chars().next()?;
+            let piece_type = style.piece_type(first).or(style.piece_type(first.to_ascii_uppercase()))?;
+            // lowercasing may change the length of the letter, so the rest
+            // is taken from the text as typed
+            (Some(piece_type), text[first.len_utf8()..].to_lowercase().replace('x', ""))
+        },
+    };
+
+    let mut mv = Move::from_uci(&coordinates).ok()?;
+    let piece = board.piece_at(mv.from)?;
+    if piece_type.is_some_and(|piece_type| piece_type != piece.piece_type) { return None; }
+
+    if mv.promotion.is_none() && piece.piece_type == PieceType::Pawn && (rank(mv.to) == 1 || rank(mv.to) == 8) {
+        mv.promotion = Some(PieceType::Queen);
+    }
+
+    Some(mv).filter(|&mv| board.is_legal(mv))
+}
+
+/// SAN where the piece or promotion letter may have been typed in lowercase,
+/// like `nf3` or `e8=q`. A lowercase `b` can be a file or a bishop, so both
+/// readings are tried.
+fn relaxed_san_moves(board: &Board, text: &str, style: &PieceStyle) -> Vec<Move> {
+    let mut variants = vec![String::from(text)];
+
+    let mut chars = text.chars();
+    if let Some(first) = chars.next() {
+        if first.is_lowercase() {
+            variants.push(format!("{}{}", first.to_uppercase(), chars.as_str()));
+        }
+    }
+
+    for variant in variants.clone() {
+        if let Some(last) = variant.chars().last().filter(|c| c.is_lowercase() && !c.is_ascii_digit()) {
+            variants.push(format!("{}{}", &variant[..variant.len()-last.len_utf8()], last.to_uppercase()));
+        }
+    }
+
+    let mut moves = Vec::new();
+    for variant in variants {
+        if let Ok(mv) = parse_san_with(board, &variant, style) {
+            if !moves.contains(&mv) { moves.push(mv); }
+        }
+    }
+    moves
+}
+
+/// Parses a move typed by a person. Besides SAN and descriptive notation this
+/// accepts coordinates with or without separators (`e2-e4`, `e2 e4`), piece
+/// prefixed coordinates (`Ng1-f3`), lowercase piece letters (`nf3`) and
+/// castling written with zeros (`0-0`). The error lists the moves the player
+/// most likely meant.
+pub fn parse_user_move(board: &Board, input: &str, style: &PieceStyle) -> Result<Move, String> {
+    let text = compact(input);
+    if text.is_empty() { return Err(String::from("Please enter a move.")); }
+
+    if let Some(mv) = castling_move(board, &text) { return Ok(mv); }
+    if let Some(mv) = coordinate_move(board, &text, style) { return Ok(mv); }
+    if let Ok(mv) = parse_san_with(board, input.trim(), style) { return Ok(mv); }
+
+    let relaxed = relaxed_san_moves(board, &text, style);
+    match relaxed.len() {
+        1 => return Ok(relaxed[0]),
+        0 => {},
+        _ => return Err(format!("Ambiguous move {}. Did you mean {}?", input.trim(), join_san(board, &relaxed, style, " or "))),
+    }
+
+    if let Ok(mv) = parse_descriptive(board, input.trim()) { return Ok(mv); }
+
+    Err(describe_failure(board, input.trim(), style))
+}
+
+fn join_san(board: &Board, moves: &[Move], style: &PieceStyle, separator: &str) -> String {
+    moves.iter().map(|&mv| to_san_with(board, mv, style)).collect::<Vec<String>>().join(separator)
+}
+
+fn piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::King => "king",
+        PieceType::Queen => "queen",
+        PieceType::Rook => "rook",
+        PieceType::Bishop => "bishop",
+        PieceType::Knight => "knight",
+        PieceType::Pawn => "pawn",
+    }
+}
+
+/// What the player seems to have been trying to move.
+enum Intent {
+    Square(usize),
+    PieceType(PieceType),
+    PawnOnFile(usize),
+    Unknown,
+}
+
+fn guess_intent(board: &Board, text: &str, style: &PieceStyle) -> Intent {
+    let lower = text.to_lowercase();
+    if let Some(from) = lower.get(0..2).and_then(parse_square) {
+        if matches!(board.piece_at(from), Some(piece) if piece.color == board.side_to_move) {
+            return Intent::Square(from);
+        }
+    }
+
+    let first = match text.chars().next() {
+        Some(c) => c,
+        None => return Intent::Unknown,
+    };
+    if let Some(piece_type) = style.piece_type(first) {
+        return Intent::PieceType(piece_type);
+    }
+    if ('a'..='h').contains(&first) {
+        return Intent::PawnOnFile(first as usize - 'a' as usize);
+    }
+    match style.piece_type(first.to_ascii_uppercase()) {
+        Some(piece_type) => Intent::PieceType(piece_type),
+        None => Intent::Unknown,
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = previous[j-1] + usize::from(a[i-1] != b[j-1]);
+            current[j] = substitution.min(previous[j] + 1).min(current[j-1] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Legal moves closest to the input, comparing it with their SAN and
+/// coordinate spellings.
+pub fn closest_moves(board: &Board, input: &str, style: &PieceStyle, limit: usize) -> Vec<Move> {
+    let text = compact(input).to_lowercase().replace('x', "");
+    let mut scored: Vec<(usize, String, Move)> = board.legal_moves().into_iter().map(|mv| {
+        let san = to_san_with(board, mv, style);
+        let plain = compact(&san).to_lowercase().replace(['x', '='], "");
+        let distance = edit_distance(&text, &plain).min(edit_distance(&text, &mv.to_string()));
+        (distance, san, mv)
+    }).collect();
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+
+    let best = match scored.first() {
+        Some((distance, _, _)) => *distance,
+        None => return Vec::new(),
+    };
+    scored.into_iter()
+        .take_while(|(distance, _, _)| *distance == best)
+        .take(limit)
+        .map(|(_, _, mv)| mv)
+        .collect()
+}
+
+/// Moves the player most likely meant with the input: the moves of the piece
+/// they named, or else the closest legal moves.
+pub fn suggest_moves(board: &Board, input: &str, style: &PieceStyle) -> Vec<Move> {
+    let legal = board.legal_moves();
+    let moves: Vec<Move> = match guess_intent(board, &compact(input), style) {
+        Intent::Square(from) => return legal.into_iter().filter(|mv| mv.from == from).collect(),
+        Intent::PieceType(piece_type) => legal.into_iter()
+            .filter(|mv| matches!(board.piece_at(mv.from), Some(piece) if piece.piece_type == piece_type))
+            .collect(),
+        Intent::PawnOnFile(file) => legal.into_iter()
+            .filter(|mv| col(mv.from) == file)
+            .filter(|mv| matches!(board.piece_at(mv.from), Some(piece) if piece.piece_type == PieceType::Pawn))
+            .collect(),
+        Intent::Unknown => Vec::new(),
+    };
+
+    if moves.is_empty() { closest_moves(board, input, style, 5) } else { moves }
+}
+
+fn describe_failure(board: &Board, input: &str, style: &PieceStyle) -> String {
+    let text = compact(input);
+    let suggestions = suggest_moves(board, input, style);
+    let list = join_san(board, &suggestions, style, ", ");
+
+    let hint = match guess_intent(board, &text, style) {
+        Intent::Square(from) => {
+            let piece = board.piece_at(from).map_or("piece", |piece| piece_name(piece.piece_type));
+            if suggestions.is_empty() {
+                format!("The {} on {} has no legal moves.", piece, square_name(from))
+            } else {
+                format!("Legal moves for the {} on {}: {}", piece, square_name(from), list)
+            }
+        },
+        _ if suggestions.is_empty() => String::from("There are no legal moves."),
+        Intent::PieceType(piece_type) if suggestions.iter().all(|mv| matches!(board.piece_at(mv.from), Some(piece) if piece.piece_type == piece_type)) =>
+            format!("Legal {} moves: {}", piece_name(piece_type), list),
+        Intent::PawnOnFile(file) if suggestions.iter().all(|mv| col(mv.from) == file) =>
+            format!("Legal moves for the {}-pawn: {}", file_char(file), list),
+        _ => format!("Closest legal moves: {}", list),
+    };
+
+    format!("Could not play {input}. {hint}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(board: &Board, input: &str) -> Result<Move, String> {
+        parse_user_move(board, input, &PieceStyle::ENGLISH)
+    }
+
+    #[test]
+    fn accepts_forgiving_spellings() {
+        let board = Board::new();
+        let e4 = Move::from_uci("e2e4").unwrap();
+        let nf3 = Move::from_uci("g1f3").unwrap();
+        for input in ["e2e4", "e2-e4", "e2 e4", "E2E4", "e4", " e4\n"] {
+            assert_eq!(parse(&board, input).unwrap(), e4, "{input}");
+        }
+        for input in ["Nf3", "nf3", "Ng1-f3", "ng1f3", "N-KB3"] {
+            assert_eq!(parse(&board, input).unwrap(), nf3, "{input}");
+        }
+    }
+
+    #[test]
+    fn castling_and_promotion() {
+        let board = Board::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(parse(&board, "0-0").unwrap(), Move::from_uci("e1g1").unwrap());
+        assert_eq!(parse(&board, "o-o-o").unwrap(), Move::from_uci("e1c1").unwrap());
+        assert_eq!(parse(&board, "b7a8").unwrap(), Move::with_promotion(9, 0, PieceType::Queen));
+        assert_eq!(parse(&board, "bxa8=n").unwrap(), Move::with_promotion(9, 0, PieceType::Knight));
+        assert_eq!(parse(&board, "b8=r").unwrap(), Move::with_promotion(9, 1, PieceType::Rook));
+    }
+
+    #[test]
+    fn piece_letters_changing_length_in_lowercase() {
+        // the dotted capital I lowercases to two characters
+        let style = PieceStyle::from_name("KİTLS").unwrap();
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(parse_user_move(&board, "İd1d3", &style).unwrap(), Move::from_uci("d1d3").unwrap());
+        assert_eq!(parse_user_move(&board, "İd1xd3", &style).unwrap(), Move::from_uci("d1d3").unwrap());
+    }
+
+    #[test]
+    fn lowercase_b_can_be_a_bishop_or_a_file() {
+        let board = Board::from_fen("4k3/8/8/8/2p5/1P6/8/4KB2 w - - 0 1").unwrap();
+        assert_eq!(parse(&board, "bc4").unwrap(), Move::from_uci("b3c4").unwrap());
+        assert_eq!(parse(&board, "bd3").unwrap(), Move::from_uci("f1d3").unwrap());
+    }
+
+    #[test]
+    fn suggests_moves_of_the_intended_piece() {
+        let board = Board::new();
+        let error = parse(&board, "e2e5").unwrap_err();
+        assert_eq!(error, "Could not play e2e5. Legal moves for the pawn on e2: e3, e4");
+
+        let error = parse(&board, "Nf4").unwrap_err();
+        assert_eq!(error, "Could not play Nf4. Legal knight moves: Na3, Nc3, Nf3, Nh3");
+
+        let error = parse(&board, "e5").unwrap_err();
+        assert_eq!(error, "Could not play e5. Legal moves for the e-pawn: e3, e4");
+
+        let error = parse(&board, "Bc4").unwrap_err();
+        assert_eq!(error, "Could not play Bc4. Closest legal moves: b4, c4");
+    }
+
+    #[test]
+    fn closest_moves_by_spelling() {
+        let board = Board::new();
+        let closest = closest_moves(&board, "zz3", &PieceStyle::ENGLISH, 3);
+        assert_eq!(closest.len(), 3);
+        assert_eq!(edit_distance("nf3", "nf4"), 1);
+        assert_eq!(edit_distance("", "e4"), 2);
+    }
+}
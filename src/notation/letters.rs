@@ -0,0 +1,122 @@
+use crate::board::piece::*;
+
+/// Letters used for the pieces in algebraic notation. Pawns have no letter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PieceLetters {
+    pub king: char,
+    pub queen: char,
+    pub rook: char,
+    pub bishop: char,
+    pub knight: char,
+}
+
+impl PieceLetters {
+    pub const ENGLISH: Self = Self { king: 'K', queen: 'Q', rook: 'R', bishop: 'B', knight: 'N' };
+    pub const GERMAN: Self = Self { king: 'K', queen: 'D', rook: 'T', bishop: 'L', knight: 'S' };
+    pub const FRENCH: Self = Self { king: 'R', queen: 'D', rook: 'T', bishop: 'F', knight: 'C' };
+
+    /// Builds a letter set from five letters in the order king, queen, rook,
+    /// bishop, knight, e.g. `KDTLS`.
+    pub fn from_letters(letters: &str) -> Result<Self, String> {
+        let chars: Vec<char> = letters.chars().collect();
+        let valid = chars.len() == 5
+            && chars.iter().all(|c| c.is_uppercase())
+            && (1..5).all(|i| !chars[..i].contains(&chars[i]));
+        if !valid { return Err(format!("Invalid piece letters {letters}")); }
+
+        Ok(Self { king: chars[0], queen: chars[1], rook: chars[2], bishop: chars[3], knight: chars[4] })
+    }
+
+    pub fn letter(&self, piece_type: PieceType) -> Option<char> {
+        match piece_type {
+            PieceType::King => Some(self.king),
+            PieceType::Queen => Some(self.queen),
+            PieceType::Rook => Some(self.rook),
+            PieceType::Bishop => Some(self.bishop),
+            PieceType::Knight => Some(self.knight),
+            PieceType::Pawn => None,
+        }
+    }
+
+    pub fn piece_type(&self, c: char) -> Option<PieceType> {
+        PieceType::ALL.into_iter().find(|&piece_type| self.letter(piece_type) == Some(c))
+    }
+}
+
+/// How pieces are written in algebraic notation: with a set of letters or
+/// with the Unicode chess figurines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PieceStyle {
+    Letters(PieceLetters),
+    Figurine,
+}
+
+impl PieceStyle {
+    pub const ENGLISH: Self = PieceStyle::Letters(PieceLetters::ENGLISH);
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "english" | "en" => Ok(PieceStyle::Letters(PieceLetters::ENGLISH)),
+            "german" | "de" => Ok(PieceStyle::Letters(PieceLetters::GERMAN)),
+            "french" | "fr" => Ok(PieceStyle::Letters(PieceLetters::FRENCH)),
+            "figurine" => Ok(PieceStyle::Figurine),
+            _ => PieceLetters::from_letters(name).map(PieceStyle::Letters)
+                .map_err(|_| format!("Unknown notation {name}")),
+        }
+    }
+
+    /// Text for the piece, or `None` for pawns which are never named.
+    pub fn piece_text(&self, piece: Piece) -> Option<String> {
+        if piece.piece_type == PieceType::Pawn { return None; }
+
+        match self {
+            PieceStyle::Letters(letters) => letters.letter(piece.piece_type).map(String::from),
+            PieceStyle::Figurine => Some(piece.to_string()),
+        }
+    }
+
+    /// Piece type written as `c`. Figurines of either color are accepted.
+    pub fn piece_type(&self, c: char) -> Option<PieceType> {
+        match self {
+            PieceStyle::Letters(letters) => letters.piece_type(c),
+            PieceStyle::Figurine => PieceType::ALL.into_iter().find(|&piece_type| {
+                [PieceColor::White, PieceColor::Black].iter()
+                    .any(|&color| Piece { piece_type, color }.to_string().starts_with(c))
+            }),
+        }
+    }
+}
+
+impl Default for PieceStyle {
+    fn default() -> Self {
+        Self::ENGLISH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letters_by_language() {
+        assert_eq!(PieceLetters::GERMAN.piece_type('S'), Some(PieceType::Knight));
+        assert_eq!(PieceLetters::FRENCH.piece_type('R'), Some(PieceType::King));
+        assert_eq!(PieceLetters::FRENCH.letter(PieceType::Bishop), Some('F'));
+        assert_eq!(PieceLetters::ENGLISH.piece_type('S'), None);
+    }
+
+    #[test]
+    fn custom_letters() {
+        assert_eq!(PieceLetters::from_letters("KDTLS").unwrap(), PieceLetters::GERMAN);
+        assert!(PieceLetters::from_letters("KDTL").is_err());
+        assert!(PieceLetters::from_letters("KDTLK").is_err());
+    }
+
+    #[test]
+    fn figurines_reuse_piece_glyphs() {
+        let knight = Piece { piece_type: PieceType::Knight, color: PieceColor::White };
+        assert_eq!(PieceStyle::Figurine.piece_text(knight).unwrap(), "\u{2658}");
+        assert_eq!(PieceStyle::Figurine.piece_type('\u{265E}'), Some(PieceType::Knight));
+        assert_eq!(PieceStyle::Figurine.piece_type('N'), None);
+    }
+}
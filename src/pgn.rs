@@ -0,0 +1,396 @@
+use std::ops::Range;
+
+use crate::board::*;
+use crate::notation::*;
+
+/// A game read from or written to Portable Game Notation. Moves are kept as
+/// board moves played from the start position (or the `FEN` tag).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<Move>,
+}
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// Game termination markers of the movetext.
+pub const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+impl Game {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = String::from(value),
+            None => self.tags.push((String::from(name), String::from(value))),
+        }
+    }
+
+    /// Game result as `1-0`, `0-1`, `1/2-1/2` or `*` when unknown.
+    pub fn result(&self) -> &str {
+        self.tag("Result").unwrap_or("*")
+    }
+
+    pub fn start_board(&self) -> Result<Board, String> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen),
+            None => Ok(Board::new()),
+        }
+    }
+
+    /// Every position of the game, starting with the start position.
+    pub fn boards(&self) -> Result<Vec<Board>, String> {
+        let mut board = self.start_board()?;
+        let mut boards = Vec::with_capacity(self.moves.len() + 1);
+        boards.push(board.clone());
+        for &mv in &self.moves {
+            board.apply_move(mv)?;
+            boards.push(board.clone());
+        }
+
+        Ok(boards)
+    }
+
+    pub fn final_board(&self) -> Result<Board, String> {
+        let mut board = self.start_board()?;
+        for &mv in &self.moves {
+            board.apply_move(mv)?;
+        }
+
+        Ok(board)
+    }
+
+    /// Writes the game as PGN with the seven tag roster first and the move
+    /// text wrapped before 80 columns.
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, default) in SEVEN_TAG_ROSTER {
+            pgn.push_str(&format_tag(name, self.tag(name).unwrap_or(default)));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.iter().any(|(n, _)| n == name) {
+                pgn.push_str(&format_tag(name, value));
+            }
+        }
+        pgn.push('\n');
+
+        let board = self.start_board().unwrap_or_default();
+        let movetext = format!("{} {}", number_moves(&board, &to_san_line(&board, &self.moves)), self.result());
+        let mut line_len = 0;
+        for word in movetext.trim().split(' ') {
+            if line_len > 0 && line_len + word.len() + 1 > 79 {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            pgn.push_str(word);
+            line_len += word.len();
+        }
+        pgn.push('\n');
+
+        pgn
+    }
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Symbol(String),
+    VariationStart,
+    VariationEnd,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, Range<usize>)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut line_start = true;
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\n' => {
+                line_start = true;
+                continue;
+            },
+            _ if c.is_whitespace() => continue,
+            // escape mechanism: a line starting with % is ignored
+            '%' if line_start => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            },
+            ';' => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            },
+            '{' => {
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some(_) => {},
+                        None => return Err(String::from("Unterminated comment")),
+                    }
+                }
+            },
+            '(' => tokens.push((Token::VariationStart, start..start+1)),
+            ')' => tokens.push((Token::VariationEnd, start..start+1)),
+            '[' => {
+                let mut name = String::new();
+                while let Some((_, c)) = chars.next_if(|&(_, c)| !c.is_whitespace() && c != '"' && c != ']') {
+                    name.push(c);
+                }
+                while chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+
+                let mut value = String::new();
+                if chars.next_if(|&(_, c)| c == '"').is_some() {
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, '\\')) => if let Some((_, c)) = chars.next() { value.push(c); },
+                            Some((_, c)) => value.push(c),
+                            None => return Err(format!("Unterminated tag {name}")),
+                        }
+                    }
+                }
+
+                let end = loop {
+                    match chars.next() {
+                        Some((i, ']')) => break i + 1,
+                        Some(_) => {},
+                        None => return Err(format!("Unterminated tag {name}")),
+                    }
+                };
+                tokens.push((Token::Tag(name, value), start..end));
+            },
+            _ => {
+                let mut end = start + c.len_utf8();
+                let mut symbol = String::from(c);
+                while let Some((i, c)) = chars.next_if(|&(_, c)| !c.is_whitespace() && !"[]{}();".contains(c)) {
+                    symbol.push(c);
+                    end = i + c.len_utf8();
+                }
+                tokens.push((Token::Symbol(symbol), start..end));
+            },
+        }
+        line_start = false;
+    }
+
+    Ok(tokens)
+}
+
+/// Strips a move number glued to a move, as in `12.Nf3` or `12...Nf6`.
+fn strip_move_number(symbol: &str) -> &str {
+    if !symbol.starts_with(|c: char| c.is_ascii_digit()) || !symbol.contains('.') {
+        return symbol;
+    }
+
+    let digits = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    if digits.starts_with('.') { digits.trim_start_matches('.') } else { symbol }
+}
+
+struct GameBuilder {
+    game: Game,
+    board: Option<Board>,
+    error: Option<String>,
+    start: usize,
+    end: usize,
+    has_moves: bool,
+}
+
+impl GameBuilder {
+    fn new(start: usize) -> Self {
+        Self { game: Game::new(), board: None, error: None, start, end: start, has_moves: false }
+    }
+
+    fn play(&mut self, san: &str) {
+        if self.error.is_some() { return; }
+
+        if self.board.is_none() {
+            match self.game.start_board() {
+                Ok(board) => self.board = Some(board),
+                Err(e) => {
+                    self.error = Some(e);
+                    return;
+                },
+            }
+        }
+
+        let board = self.board.as_mut().unwrap();
+        match parse_san(board, san) {
+            Ok(mv) => {
+                board.make(mv);
+                self.game.moves.push(mv);
+            },
+            Err(e) => self.error = Some(format!("{} after {} moves", e, self.game.moves.len())),
+        }
+    }
+
+    fn finish(self) -> (Result<Game, String>, Range<usize>) {
+        let result = match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.game),
+        };
+        (result, self.start..self.end)
+    }
+}
+
+/// A game, or the reason it could not be read, with its byte range in the text.
+pub type GameSpan = (Result<Game, String>, Range<usize>);
+
+/// Reads every game of a PGN text together with the byte range it occupies.
+/// A game with an illegal move is returned as an error without affecting the
+/// games around it. Variations, comments and annotations are skipped.
+pub fn read_games_with_spans(text: &str) -> Result<Vec<GameSpan>, String> {
+    let mut games = Vec::new();
+    let mut current: Option<GameBuilder> = None;
+    let mut depth = 0;
+
+    for (token, span) in tokenize(text)? {
+        match token {
+            Token::Tag(name, value) => {
+                if current.as_ref().is_some_and(|builder| builder.has_moves) {
+                    games.push(current.take().unwrap().finish());
+                }
+                let builder = current.get_or_insert_with(|| GameBuilder::new(span.start));
+                builder.game.set_tag(&name, &value);
+                builder.end = span.end;
+            },
+            Token::VariationStart => depth += 1,
+            Token::VariationEnd => depth = if depth > 0 { depth - 1 } else { 0 },
+            Token::Symbol(symbol) => {
+                if depth > 0 || symbol.starts_with('$') { continue; }
+
+                let builder = current.get_or_insert_with(|| GameBuilder::new(span.start));
+                builder.end = span.end;
+                if RESULTS.contains(&symbol.as_str()) {
+                    if builder.game.tag("Result").is_none_or(|result| result == "*") {
+                        builder.game.set_tag("Result", &symbol);
+                    }
+                    games.push(current.take().unwrap().finish());
+                    depth = 0;
+                    continue;
+                }
+
+                let san = strip_move_number(&symbol);
+                if san.is_empty() || san.chars().all(|c| c.is_ascii_digit() || c == '.') { continue; }
+                builder.has_moves = true;
+                builder.play(san);
+            },
+        }
+    }
+
+    if let Some(builder) = current {
+        games.push(builder.finish());
+    }
+
+    Ok(games)
+}
+
+/// Reads every game of a PGN text, see `read_games_with_spans`.
+pub fn read_games(text: &str) -> Result<Vec<Result<Game, String>>, String> {
+    Ok(read_games_with_spans(text)?.into_iter().map(|(game, _)| game).collect())
+}
+
+/// Reads the games of a PGN text, failing on the first invalid game.
+pub fn parse_pgn(text: &str) -> Result<Vec<Game>, String> {
+    read_games(text)?.into_iter().enumerate()
+        .map(|(i, game)| game.map_err(|e| format!("Game {}: {}", i + 1, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const OPERA_GAME: &str = r#"[Event "Paris"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[Round "?"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+[ECO "C41"]
+
+1.e4 e5 2.Nf3 d6 3.d4 Bg4 {This is a weak move already.} 4.dxe5 Bxf3 5.Qxf3 dxe5
+6.Bc4 Nf6 7.Qb3 Qe7 8.Nc3 c6 9.Bg5 b5 10.Nxb5 cxb5 11.Bxb5+ Nbd7 12.O-O-O Rd8
+13.Rxd7 Rxd7 14.Rd1 Qe6 15.Bxd7+ Nxd7 16.Qb8+ Nxb8 17.Rd8# 1-0
+"#;
+
+    #[test]
+    fn read_a_game_with_comments() {
+        let games = parse_pgn(OPERA_GAME).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].tag("White"), Some("Paul Morphy"));
+        assert_eq!(games[0].tag("ECO"), Some("C41"));
+        assert_eq!(games[0].result(), "1-0");
+        assert_eq!(games[0].moves.len(), 33);
+        assert!(games[0].final_board().unwrap().is_checkmate());
+    }
+
+    #[test]
+    fn variations_nags_and_several_games() {
+        let text = "[White \"A\"]\n\n1. e4 $1 (1. d4 d5 (1... Nf6)) 1... c5 ; sicilian\n2. Nf3 *\n\n\
+                    [White \"B\"]\n[Black \"C\"]\n\n1. d4 d5 2. Bf4 1/2-1/2\n\n1. e4 e5 2. Ke2?? 0-1";
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(to_san_line(&Board::new(), &games[0].moves), vec!["e4", "c5", "Nf3"]);
+        assert_eq!(games[0].result(), "*");
+        assert_eq!(games[1].tag("Black"), Some("C"));
+        assert_eq!(games[1].result(), "1/2-1/2");
+        assert_eq!(games[2].moves.len(), 3);
+        assert_eq!(games[2].result(), "0-1");
+    }
+
+    #[test]
+    fn illegal_moves_only_affect_their_game() {
+        let text = "1. e4 e5 2. Ke3 *\n\n1. d4 *\n";
+        let games = read_games(text).unwrap();
+        assert!(games[0].as_ref().unwrap_err().contains("Illegal move Ke3"));
+        assert_eq!(games[1].as_ref().unwrap().moves.len(), 1);
+        assert!(parse_pgn(text).unwrap_err().starts_with("Game 1:"));
+    }
+
+    #[test]
+    fn games_from_a_fen_tag() {
+        let text = "[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n[SetUp \"1\"]\n\n1. e4 Kd7 *";
+        let game = &parse_pgn(text).unwrap()[0];
+        assert_eq!(game.final_board().unwrap().to_fen(), "8/3k4/8/8/4P3/8/8/4K3 w - - 1 2");
+        assert_eq!(game.boards().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let game = parse_pgn(OPERA_GAME).unwrap().remove(0);
+        let pgn = game.to_pgn();
+        assert!(pgn.starts_with("[Event \"Paris\"]\n"));
+        assert!(pgn.contains("\n\n1. e4 e5 2. Nf3 d6 3. d4 Bg4"));
+        assert!(pgn.trim_end().ends_with("17. Rd8# 1-0"));
+        assert!(pgn.lines().all(|line| line.len() < 80));
+        assert_eq!(parse_pgn(&pgn).unwrap().remove(0), game);
+    }
+
+    #[test]
+    fn spans_cover_each_game() {
+        let text = "[White \"A\"]\n\n1. e4 *\n\n[White \"B\"]\n\n1. d4 1-0\n";
+        let games = read_games_with_spans(text).unwrap();
+        assert_eq!(&text[games[0].1.clone()], "[White \"A\"]\n\n1. e4 *");
+        assert_eq!(&text[games[1].1.clone()], "[White \"B\"]\n\n1. d4 1-0");
+    }
+}
@@ -11,5 +11,11 @@ impl<'a> Player<'a> {
         println!("Curent turn: {}", if self.color {"WHITE"} else {"BLACK"});
         self.strategy.next_move(board)
     }
+
+    /// Like `next_move`, see `Strategy::try_next_move`.
+    pub fn try_next_move(&self, board: &Board) -> Result<Move, String> {
+        println!("Curent turn: {}", if self.color {"WHITE"} else {"BLACK"});
+        self.strategy.try_next_move(board)
+    }
 }
 
@@ -0,0 +1,70 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small pseudo random number generator (xorshift64*) for choosing moves.
+/// The same seed always gives the same numbers, for reproducible games.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // scramble the seed so that nearby seeds give unrelated sequences
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Self((z ^ (z >> 31)).max(1))
+    }
+
+    /// Seeded from the clock, different on every run.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Self::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number from 0 up to but excluding `bound`, which must not be 0.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+
+    /// A number from 0 up to but excluding 1.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        assert!((0..10).all(|_| a.next_u64() == b.next_u64()));
+        assert_ne!(Rng::new(7).next_u64(), Rng::new(8).next_u64());
+    }
+
+    #[test]
+    fn numbers_stay_in_range() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 6];
+        for _ in 0..1000 {
+            seen[rng.below(6) as usize] = true;
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+}
@@ -0,0 +1,906 @@
+pub mod analysis;
+pub mod ordering;
+pub mod see;
+pub mod selective;
+pub mod smp;
+pub mod time;
+pub mod tt;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::board::*;
+use crate::board::piece::*;
+use crate::eval::*;
+use crate::syzygy::*;
+
+pub use analysis::*;
+pub use ordering::*;
+pub use see::*;
+pub use selective::*;
+pub use smp::*;
+pub use time::*;
+pub use tt::*;
+
+/// Score of being mated right now. Mates further away score closer to zero
+/// so that the search prefers the shortest mate and the longest defence.
+pub const MATE: i32 = 30000;
+pub const INFINITY: i32 = 32000;
+pub const MAX_PLY: usize = 128;
+/// Score of a position won according to the tablebase, less the plies to
+/// reach it. It is beyond any evaluation but below the mate scores.
+pub const TB_WIN: i32 = MATE - 2 * MAX_PLY as i32;
+
+pub fn mated_in(ply: usize) -> i32 {
+    -MATE + ply as i32
+}
+
+pub fn mate_in(ply: usize) -> i32 {
+    MATE - ply as i32
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+/// Moves until mate for mate scores, positive when the side to move mates.
+pub fn mate_distance(score: i32) -> Option<i32> {
+    if !is_mate_score(score) { return None; }
+
+    let plies = MATE - score.abs();
+    let moves = (plies + 1) / 2;
+    Some(if score > 0 { moves } else { -moves })
+}
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::King => 0,
+        PieceType::Queen => 900,
+        PieceType::Rook => 500,
+        PieceType::Bishop => 330,
+        PieceType::Knight => 320,
+        PieceType::Pawn => 100,
+    }
+}
+
+/// Material balance from the side to move.
+pub fn material(board: &Board) -> i32 {
+    let mut score = 0;
+    for cell in board.cells.iter() {
+        if let Some(piece) = cell.piece {
+            let value = piece_value(piece.piece_type);
+            score += if piece.color == board.side_to_move { value } else { -value };
+        }
+    }
+    score
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Centipawns from the side to move, or a mate score.
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+    /// Permille of the transposition table used by this search.
+    pub hashfull: u32,
+}
+
+// How many nodes to search between looking at the clock and the stop flag.
+const CHECK_INTERVAL: u64 = 1024;
+
+// Captures which cannot raise the score to alpha even with this much to
+// spare are skipped in quiescence search.
+const DELTA_MARGIN: i32 = 200;
+
+// Half the width of the first aspiration window, doubled on every failure.
+const ASPIRATION_WINDOW: i32 = 25;
+// Shallower iterations are searched with an infinite window.
+const ASPIRATION_DEPTH: u32 = 4;
+const FUTILITY_DEPTH: u32 = 3;
+const REVERSE_FUTILITY_DEPTH: u32 = 6;
+const NULL_MOVE_DEPTH: u32 = 3;
+const LMR_DEPTH: u32 = 3;
+// Moves searched at full depth before the reductions start.
+const LMR_MOVES: usize = 3;
+
+/// Negamax search with alpha-beta pruning, deepened one ply at a time until
+/// the limits are reached or the search is stopped from another thread.
+/// Checks extend the search by a ply and the horizon is resolved by a
+/// quiescence search of captures, promotions and check evasions. Away from
+/// the principal variation the tree is pruned and reduced as `SearchOptions`
+/// allow. With a tablebase, positions in it are scored without searching
+/// them. Each search keeps its own move ordering tables, so one is needed
+/// per thread.
+pub struct Search {
+    nodes: u64,
+    options: SearchOptions,
+    // the side the search is for, whose draws cost the contempt
+    root_color: PieceColor,
+    table: Arc<TranspositionTable>,
+    evaluator: Arc<dyn Evaluator>,
+    // the evaluator's state along the searched line, if it keeps one
+    incremental: Option<Box<dyn IncrementalEvaluator>>,
+    tablebase: Option<Arc<Tablebase>>,
+    ordering: Box<OrderingTables>,
+    // the move played to reach each ply
+    path: [Option<Move>; MAX_PLY + 1],
+    // keys of the game's positions before the root
+    history: Vec<u64>,
+    // keys of the history and of the positions of the searched line
+    keys: Vec<u64>,
+    stop: Arc<AtomicBool>,
+    stopped: bool,
+    node_limit: Option<u64>,
+    time: Option<TimeManager>,
+    // helper threads of a parallel search are numbered from 1
+    helper: usize,
+    // nodes of all threads of a parallel search
+    shared_nodes: Option<Arc<AtomicU64>>,
+}
+
+impl Search {
+    // Size of the table of searches which do not share one.
+    const OWN_TABLE_MB: usize = 1;
+
+    pub fn new() -> Self {
+        Self::with_stop(Arc::new(AtomicBool::new(false)))
+    }
+
+    /// A search which gives up as soon as `stop` is set, returning the best
+    /// move found so far.
+    pub fn with_stop(stop: Arc<AtomicBool>) -> Self {
+        Self::with_table(Arc::new(TranspositionTable::new(Self::OWN_TABLE_MB)), stop)
+    }
+
+    /// A search remembering positions in `table`, which may be kept between
+    /// moves and shared with other searches.
+    pub fn with_table(table: Arc<TranspositionTable>, stop: Arc<AtomicBool>) -> Self {
+        Self {
+            nodes: 0,
+            options: SearchOptions::default(),
+            root_color: PieceColor::White,
+            table,
+            evaluator: Arc::new(ClassicalEvaluator::default()),
+            incremental: None,
+            tablebase: None,
+            ordering: Box::default(),
+            path: [None; MAX_PLY + 1],
+            history: Vec::new(),
+            keys: Vec::new(),
+            stop,
+            stopped: false,
+            node_limit: None,
+            time: None,
+            helper: 0,
+            shared_nodes: None,
+        }
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.incremental = evaluator.incremental();
+        self.evaluator = evaluator;
+    }
+
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    pub fn set_options(&mut self, options: SearchOptions) {
+        self.options = options;
+    }
+
+    /// The keys of the game's positions up to the one searched, see
+    /// `GameHistory::keys`. Lines coming back to any of them, or to an
+    /// earlier position of the line itself, are scored as draws.
+    pub fn set_history(&mut self, keys: &[u64]) {
+        self.history = keys.to_vec();
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Nodes searched so far by this search and, in a parallel search, by
+    /// the other threads.
+    fn searched_nodes(&self) -> u64 {
+        match &self.shared_nodes {
+            Some(shared) => shared.load(Ordering::Relaxed) + self.nodes % CHECK_INTERVAL,
+            None => self.nodes,
+        }
+    }
+
+    /// Searches exactly `depth` plies deep, or deeper when mates require it.
+    pub fn search(&mut self, board: &Board, depth: u32) -> SearchResult {
+        self.iterate(board, &SearchLimits::depth(depth.max(1)), |_| {})
+    }
+
+    /// Iterative deepening within `limits`, calling `on_iteration` with the
+    /// result of every completed depth.
+    pub fn iterate(&mut self, board: &Board, limits: &SearchLimits, mut on_iteration: impl FnMut(&SearchResult)) -> SearchResult {
+        self.start(board, limits);
+
+        let mut board = board.clone();
+        let root_moves = self.root_moves(&board);
+        let max_depth = limits.depth.unwrap_or(MAX_PLY as u32).clamp(1, MAX_PLY as u32);
+
+        let mut result = SearchResult {
+            best_move: root_moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: root_moves.first().copied().into_iter().collect(),
+            hashfull: 0,
+        };
+        if root_moves.is_empty() {
+            result.score = if board.in_check() { mated_in(0) } else { 0 };
+            return result;
+        }
+        // no point thinking on the clock about a forced move
+        if root_moves.len() == 1 && limits.clock.is_some() {
+            return result;
+        }
+
+        let mut stability = 0;
+        for iteration in 1..=max_depth {
+            // half of the helper threads search one ply deeper than the
+            // main thread, filling the table for its next iteration
+            let depth = (iteration + (self.helper % 2) as u32).min(max_depth);
+            let (score, pv) = self.aspiration(&mut board, &root_moves, &result, depth);
+
+            if self.stopped {
+                // the previous best move is searched first, so any move
+                // which completed and took its place is an improvement
+                if let Some(&best) = pv.first() {
+                    if result.depth == 0 || Some(best) != result.best_move {
+                        result.best_move = Some(best);
+                        result.score = score;
+                        result.pv = pv;
+                    }
+                }
+                break;
+            }
+
+            stability = if Some(pv[0]) == result.best_move { stability + 1 } else { 0 };
+            self.table.store(board.zobrist_key(), 0, depth, score, Bound::Exact, Some(pv[0]));
+            result = SearchResult { best_move: Some(pv[0]), score, depth, nodes: self.searched_nodes(), pv, hashfull: self.table.hashfull() };
+            on_iteration(&result);
+
+            if depth == max_depth { break; }
+            if !limits.is_infinite() && is_mate_score(score) && MATE - score.abs() <= depth as i32 {
+                break;
+            }
+            if self.time.as_ref().is_some_and(|time| !time.should_start_iteration(stability)) {
+                break;
+            }
+        }
+
+        result.nodes = self.nodes;
+        result.hashfull = self.table.hashfull();
+        result
+    }
+
+    /// Scores every root move exactly, best first, deepening until `limits`
+    /// are reached. Slower than `iterate`, which only proves the best move
+    /// better than the others, but a weakened player needs to know how much
+    /// worse each alternative is. Moves not scored at the first depth before
+    /// the limits are left out.
+    pub fn score_root_moves(&mut self, board: &Board, limits: &SearchLimits) -> Vec<(Move, i32)> {
+        self.start(board, limits);
+
+        let mut board = board.clone();
+        let mut scored: Vec<(Move, i32)> = self.root_moves(&board).into_iter().map(|mv| (mv, 0)).collect();
+        let max_depth = limits.depth.unwrap_or(MAX_PLY as u32).clamp(1, MAX_PLY as u32);
+        let mut pv = Vec::new();
+        for depth in 1..=max_depth {
+            let mut next = Vec::with_capacity(scored.len());
+            for &(mv, _) in &scored {
+                self.path[1] = Some(mv);
+                let undo = self.make(&mut board, mv);
+                let score = -self.negamax(&mut board, depth - 1, 1, -INFINITY, INFINITY, &mut pv);
+                self.unmake(&mut board, mv, undo);
+
+                if self.stopped { break; }
+                next.push((mv, score));
+            }
+
+            if !self.stopped || (depth == 1 && !next.is_empty()) {
+                next.sort_by_key(|&(_, score)| -score);
+                scored = next;
+            }
+            if self.stopped || self.time.as_ref().is_some_and(|time| !time.should_start_iteration(0)) {
+                break;
+            }
+        }
+        scored
+    }
+
+    fn start(&mut self, board: &Board, limits: &SearchLimits) {
+        self.nodes = 0;
+        self.stopped = false;
+        self.node_limit = limits.nodes;
+        self.root_color = board.side_to_move;
+        self.keys.clone_from(&self.history);
+        if self.keys.last() != Some(&board.zobrist_key()) { self.keys.push(board.zobrist_key()); }
+        self.time = Some(TimeManager::new(limits, board));
+        // a parallel search ages the table once for all its threads
+        if self.shared_nodes.is_none() { self.table.new_search(); }
+        if let Some(incremental) = &mut self.incremental { incremental.reset(board); }
+    }
+
+    fn make(&mut self, board: &mut Board, mv: Move) -> Undo {
+        if let Some(incremental) = &mut self.incremental { incremental.make(board, mv); }
+        let undo = board.make(mv);
+        self.keys.push(board.zobrist_key());
+        undo
+    }
+
+    fn unmake(&mut self, board: &mut Board, mv: Move, undo: Undo) {
+        if let Some(incremental) = &mut self.incremental { incremental.unmake(); }
+        self.keys.pop();
+        board.unmake(mv, undo);
+    }
+
+    fn make_null(&mut self, board: &mut Board) -> Undo {
+        if let Some(incremental) = &mut self.incremental { incremental.make_null(); }
+        let undo = board.make_null();
+        self.keys.push(board.zobrist_key());
+        undo
+    }
+
+    fn unmake_null(&mut self, board: &mut Board, undo: Undo) {
+        if let Some(incremental) = &mut self.incremental { incremental.unmake(); }
+        self.keys.pop();
+        board.unmake_null(undo);
+    }
+
+    /// Whether `board`, reached at `ply`, occurred before in the game or
+    /// on the searched line, looking back no further than the last capture,
+    /// pawn move or null move.
+    fn is_repetition(&self, board: &Board, ply: usize) -> bool {
+        let mut reversible = board.halfmove_clock as usize;
+        if let Some(null) = (1..=ply).rev().find(|&earlier| self.path[earlier].is_none()) {
+            reversible = reversible.min(ply - null);
+        }
+        let key = board.zobrist_key();
+        self.keys.iter().rev().take(reversible + 1).skip(2).step_by(2).any(|&earlier| earlier == key)
+    }
+
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        let score = match &mut self.incremental {
+            Some(incremental) => incremental.evaluate(board),
+            None => self.evaluator.evaluate(board),
+        };
+        if self.options.risk == 0 { return score; }
+
+        let risk = self.options.risk * game_phase(board) / 24;
+        if board.side_to_move == self.root_color { score + risk } else { score - risk }
+    }
+
+    /// The score of a draw for the side to move, below zero for the side
+    /// the search is for when it has contempt.
+    fn draw_score(&self, board: &Board) -> i32 {
+        if board.side_to_move == self.root_color { -self.options.contempt } else { self.options.contempt }
+    }
+
+    /// The legal moves, only those keeping the best result as quickly as
+    /// possible when the position is in the tablebase.
+    fn root_moves(&self, board: &Board) -> Vec<Move> {
+        let ranked = self.tablebase.as_ref()
+            .filter(|tablebase| tablebase.covers(board))
+            .and_then(|tablebase| tablebase.probe_root(board).ok());
+        match ranked {
+            Some(ranked) if !ranked.is_empty() => ranked.iter()
+                .filter(|root_move| root_move.rank() == ranked[0].rank())
+                .map(|root_move| root_move.mv)
+                .collect(),
+            _ => board.legal_moves(),
+        }
+    }
+
+    /// The tablebase score of a position just reached by a capture or pawn
+    /// move, for which the fifty move rule does not change the result.
+    fn probe_tablebase(&self, board: &Board, ply: usize) -> Option<i32> {
+        let tablebase = self.tablebase.as_ref()?;
+        if board.halfmove_clock != 0 || !tablebase.covers(board) { return None; }
+
+        Some(match tablebase.probe_wdl(board).ok()? {
+            Wdl::Win => TB_WIN - ply as i32,
+            Wdl::Loss => -TB_WIN + ply as i32,
+            _ => self.draw_score(board),
+        })
+    }
+
+    /// Searches the root at `depth` in a window around the score of the
+    /// previous iteration, widening it on the side where the score fell
+    /// outside until the score is exact.
+    fn aspiration(&mut self, board: &mut Board, moves: &[Move], previous: &SearchResult, depth: u32) -> (i32, Vec<Move>) {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = if self.options.aspiration_windows && depth >= ASPIRATION_DEPTH
+            && previous.depth > 0 && !is_mate_score(previous.score) {
+            (previous.score - delta, previous.score + delta)
+        } else {
+            (-INFINITY, INFINITY)
+        };
+
+        let mut first = previous.best_move;
+        loop {
+            let mut pv = Vec::new();
+            let score = self.root(board, moves, first, depth, (alpha, beta), &mut pv);
+            if self.stopped || (alpha < score && score < beta) { return (score, pv); }
+
+            if score <= alpha {
+                alpha = (alpha - delta).max(-INFINITY);
+            } else {
+                beta = (beta + delta).min(INFINITY);
+                first = pv.first().copied().or(first);
+            }
+            delta *= 2;
+        }
+    }
+
+    /// Searches every move of the root within the `window` of alpha and
+    /// beta, the first being `first`. Unless the score is within the window,
+    /// it is only a bound and the moves of `pv` need not be best.
+    fn root(&mut self, board: &mut Board, moves: &[Move], first: Option<Move>, depth: u32, window: (i32, i32), pv: &mut Vec<Move>) -> i32 {
+        let (mut alpha, beta) = window;
+        let mut ordered = moves.to_vec();
+        if let Some(position) = ordered.iter().position(|&mv| Some(mv) == first) {
+            ordered[..=position].rotate_right(1);
+        }
+
+        self.nodes += 1;
+        let mut child_pv = Vec::new();
+        for (i, mv) in ordered.into_iter().enumerate() {
+            self.path[1] = Some(mv);
+            let undo = self.make(board, mv);
+            let mut score;
+            if i == 0 || !self.options.pvs {
+                score = -self.negamax(board, depth - 1, 1, -beta, -alpha, &mut child_pv);
+            } else {
+                score = -self.negamax(board, depth - 1, 1, -alpha - 1, -alpha, &mut child_pv);
+                if score > alpha && score < beta && !self.stopped {
+                    score = -self.negamax(board, depth - 1, 1, -beta, -alpha, &mut child_pv);
+                }
+            }
+            self.unmake(board, mv, undo);
+
+            if self.stopped { break; }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta { break; }
+            }
+        }
+
+        alpha
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.stopped { return true; }
+
+        if self.node_limit.is_some_and(|limit| self.searched_nodes() >= limit) {
+            self.stopped = true;
+        } else if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            if let Some(shared) = &self.shared_nodes {
+                shared.fetch_add(CHECK_INTERVAL, Ordering::Relaxed);
+            }
+            self.stopped = self.stop.load(Ordering::Relaxed)
+                || self.time.as_ref().is_some_and(|time| time.hard_limit_reached());
+        }
+        self.stopped
+    }
+
+    fn negamax(&mut self, board: &mut Board, depth: u32, ply: usize, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        self.nodes += 1;
+        pv.clear();
+        if self.should_stop() { return 0; }
+
+        let moves = board.legal_moves();
+        let in_check = board.in_check();
+        if moves.is_empty() {
+            return if in_check { mated_in(ply) } else { self.draw_score(board) };
+        }
+        if board.halfmove_clock >= 100 || self.is_repetition(board, ply) { return self.draw_score(board); }
+        if ply >= MAX_PLY { return self.evaluate(board); }
+        if let Some(score) = self.probe_tablebase(board, ply) { return score; }
+
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 { return self.quiescence(board, ply, alpha, beta, pv); }
+
+        let key = board.zobrist_key();
+        let original_alpha = alpha;
+        let mut hash_move = None;
+        if let Some(entry) = self.table.probe(key, ply) {
+            hash_move = entry.best_move.filter(|mv| moves.contains(mv));
+            if let Some(score) = entry.cutoff(depth, alpha, beta) {
+                pv.extend(hash_move);
+                return score;
+            }
+        }
+
+        // only nodes searched with a null window are pruned
+        let pv_node = beta - alpha > 1;
+        let static_eval = if in_check || pv_node { None } else { Some(self.evaluate(board)) };
+        let mut child_pv = Vec::new();
+        if let Some(eval) = static_eval.filter(|_| !is_mate_score(beta)) {
+            if self.options.reverse_futility && depth <= REVERSE_FUTILITY_DEPTH && eval - reverse_futility_margin(depth) >= beta {
+                return eval;
+            }
+
+            // never two null moves in a row, nor with only pawns left
+            if self.options.null_move && depth >= NULL_MOVE_DEPTH && eval >= beta
+                && self.path[ply].is_some() && has_non_pawn_material(board) {
+                self.path[ply + 1] = None;
+                let undo = self.make_null(board);
+                let score = -self.negamax(board, depth - 1 - null_move_reduction(depth), ply + 1, -beta, -beta + 1, &mut child_pv);
+                self.unmake_null(board, undo);
+
+                if self.stopped { return 0; }
+                // a mate found after passing is not a proven one
+                if score >= beta { return if is_mate_score(score) { beta } else { score }; }
+            }
+        }
+        let futile = self.options.futility && depth <= FUTILITY_DEPTH && !is_mate_score(alpha)
+            && static_eval.is_some_and(|eval| eval + futility_margin(depth) <= alpha);
+
+        let previous = self.path[ply];
+        let mut picker = MovePicker::new(moves, hash_move, ply, previous, &self.ordering);
+        let mut quiets_tried = Vec::new();
+        let mut best_move = None;
+        let mut searched = 0;
+        while let Some(mv) = picker.next(board, &self.ordering) {
+            let quiet = !is_tactical(board, mv);
+            self.path[ply + 1] = Some(mv);
+            let undo = self.make(board, mv);
+            let gives_check = board.in_check();
+            if futile && quiet && !gives_check && searched > 0 {
+                self.unmake(board, mv, undo);
+                continue;
+            }
+
+            let mut score;
+            if searched == 0 {
+                score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            } else {
+                let reduction = if self.options.late_move_reductions && depth >= LMR_DEPTH && searched >= LMR_MOVES
+                    && quiet && !in_check && !gives_check {
+                    late_move_reduction(depth, searched)
+                } else {
+                    0
+                };
+                let window_beta = if self.options.pvs { alpha + 1 } else { beta };
+                score = -self.negamax(board, depth - 1 - reduction, ply + 1, -window_beta, -alpha, &mut child_pv);
+                if reduction > 0 && score > alpha && !self.stopped {
+                    score = -self.negamax(board, depth - 1, ply + 1, -window_beta, -alpha, &mut child_pv);
+                }
+                if window_beta < beta && score > alpha && score < beta && !self.stopped {
+                    score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+                }
+            }
+            self.unmake(board, mv, undo);
+            searched += 1;
+
+            if self.stopped { return 0; }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(mv);
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta {
+                    if quiet {
+                        self.ordering.record_cutoff(board, mv, ply, depth, previous, &quiets_tried);
+                    }
+                    break;
+                }
+            }
+            if quiet { quiets_tried.push(mv); }
+        }
+
+        let bound = if alpha >= beta {
+            Bound::Lower
+        } else if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.table.store(key, ply, depth, alpha, bound, best_move);
+
+        alpha
+    }
+
+    /// Searches captures and promotions until the position is quiet, letting
+    /// the side to move stand pat on the static score. In check every
+    /// evasion is searched instead.
+    fn quiescence(&mut self, board: &mut Board, ply: usize, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        self.nodes += 1;
+        pv.clear();
+        if self.should_stop() { return 0; }
+
+        let mut moves = board.legal_moves();
+        let in_check = board.in_check();
+        if moves.is_empty() {
+            return if in_check { mated_in(ply) } else { self.draw_score(board) };
+        }
+        if ply >= MAX_PLY { return self.evaluate(board); }
+
+        let stand_pat = self.evaluate(board);
+        if !in_check {
+            if stand_pat >= beta { return stand_pat; }
+            alpha = alpha.max(stand_pat);
+
+            moves.retain(|&mv| is_tactical(board, mv));
+            moves.sort_by_cached_key(|&mv| -mvv_lva(board, mv));
+        }
+
+        let mut child_pv = Vec::new();
+        for mv in moves {
+            if !in_check {
+                let gain = board.captured_piece(mv).map_or(0, |piece| piece_value(piece.piece_type))
+                    + mv.promotion.map_or(0, |promotion| piece_value(promotion) - piece_value(PieceType::Pawn));
+                if stand_pat + gain + DELTA_MARGIN <= alpha { continue; }
+                if see(board, mv) < 0 { continue; }
+            }
+
+            let undo = self.make(board, mv);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha, &mut child_pv);
+            self.unmake(board, mv, undo);
+
+            if self.stopped { return 0; }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta { break; }
+            }
+        }
+
+        alpha
+    }
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn material_search() -> Search {
+        let mut search = Search::new();
+        search.set_evaluator(Arc::new(MaterialEvaluator));
+        search
+    }
+
+    #[test]
+    fn mate_scores() {
+        assert_eq!(mate_distance(mate_in(1)), Some(1));
+        assert_eq!(mate_distance(mate_in(3)), Some(2));
+        assert_eq!(mate_distance(mated_in(2)), Some(-1));
+        assert_eq!(mate_distance(150), None);
+    }
+
+    #[test]
+    fn scores_every_root_move() {
+        let board = Board::from_fen("4k3/8/8/2q1r3/3P4/8/8/7K w - - 0 1").unwrap();
+        let scored = material_search().score_root_moves(&board, &SearchLimits::depth(1));
+        assert_eq!(scored.len(), board.legal_moves().len());
+        assert_eq!(scored[0], (Move::from_uci("d4c5").unwrap(), -500));
+        assert_eq!(scored[1], (Move::from_uci("d4e5").unwrap(), -900));
+        assert!(scored.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    #[test]
+    fn moves_repeating_the_game_score_as_draws() {
+        let mut board = Board::new();
+        let mut history = GameHistory::new(&board);
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"] {
+            board.make(Move::from_uci(uci).unwrap());
+            history.push(&board);
+        }
+        // the knight coming out again repeats the position after 1. Nf3 Nf6
+        let repeat = Move::from_uci("g8f6").unwrap();
+        let mut search = Search::new();
+        search.set_history(history.keys());
+        let scored = search.score_root_moves(&board, &SearchLimits::depth(2));
+        assert!(scored.contains(&(repeat, 0)));
+
+        let unaware = Search::new().score_root_moves(&board, &SearchLimits::depth(2));
+        assert!(!unaware.contains(&(repeat, 0)));
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let result = Search::new().search(&board, 2);
+        assert_eq!(result.best_move, Some(Move::from_uci("a1a8").unwrap()));
+        assert_eq!(result.score, mate_in(1));
+    }
+
+    #[test]
+    fn prefers_the_shorter_mate() {
+        // Qb7 mates at once, other queen moves mate later
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/7Q w - - 0 1").unwrap();
+        let result = Search::new().search(&board, 3);
+        assert_eq!(mate_distance(result.score), Some(1));
+        let mut after = board.clone();
+        after.make(result.best_move.unwrap());
+        assert!(after.is_checkmate());
+    }
+
+    #[test]
+    fn wins_a_hanging_queen() {
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = Search::new().search(&board, 2);
+        assert_eq!(result.best_move, Some(Move::from_uci("d2d5").unwrap()));
+        assert_eq!(result.pv.len(), 2);
+    }
+
+    #[test]
+    fn stops_at_the_node_limit() {
+        let mut search = Search::new();
+        let result = search.iterate(&Board::new(), &SearchLimits::nodes(5000), |_| {});
+        assert!(result.nodes <= 5001);
+        assert!(Board::new().is_legal(result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn reports_every_iteration() {
+        let mut depths = Vec::new();
+        let result = Search::new().iterate(&Board::new(), &SearchLimits::depth(3), |info| depths.push(info.depth));
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result.depth, 3);
+    }
+
+    #[test]
+    fn stops_iterating_once_mate_is_proven() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let result = Search::new().iterate(&board, &SearchLimits::movetime(std::time::Duration::from_secs(10)), |_| {});
+        assert_eq!(result.depth, 1);
+        assert_eq!(result.score, mate_in(1));
+    }
+
+    #[test]
+    fn stop_flag_returns_the_best_move_so_far() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let board = Board::new();
+        let result = std::thread::scope(|scope| {
+            let handle = scope.spawn(|| Search::with_stop(stop.clone()).iterate(&board, &SearchLimits::default(), |_| {}));
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            stop.store(true, Ordering::Relaxed);
+            handle.join().unwrap()
+        });
+        assert!(board.is_legal(result.best_move.unwrap()));
+        assert!(result.depth >= 1);
+    }
+
+    #[test]
+    fn table_saves_work_on_the_next_search() {
+        let table = Arc::new(TranspositionTable::new(4));
+        let stop = Arc::new(AtomicBool::new(false));
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+
+        let first = Search::with_table(table.clone(), stop.clone()).search(&board, 4);
+        let second = Search::with_table(table.clone(), stop).search(&board, 4);
+        assert!(second.nodes < first.nodes / 2, "{} then {}", first.nodes, second.nodes);
+        assert_eq!(first.score, second.score);
+    }
+
+    #[test]
+    fn does_not_take_a_defended_pawn_at_the_horizon() {
+        // Qxd5 wins a pawn at depth 1 but loses the queen to exd5
+        let board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let result = material_search().search(&board, 1);
+        assert_ne!(result.best_move, Some(Move::from_uci("d1d5").unwrap()));
+        assert_eq!(result.score, 900 - 200);
+    }
+
+    #[test]
+    fn resolves_captures_beyond_the_horizon() {
+        // Rxd5 Rxd5 Rxd5 wins the pawn, with the exchange played out in
+        // quiescence search
+        let board = Board::from_fen("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1").unwrap();
+        let result = material_search().search(&board, 1);
+        assert_eq!(result.best_move, Some(Move::from_uci("d2d5").unwrap()));
+        assert_eq!(result.score, 500);
+    }
+
+    #[test]
+    fn extends_checks_to_find_a_mate() {
+        // 1. Rc7+ Kg8 2. Re8# takes three plies, seen at depth 2 only
+        // because the check extends the search
+        let board = Board::from_fen("8/7k/4R3/8/8/K7/2R5/8 w - - 0 1").unwrap();
+        let result = Search::new().search(&board, 2);
+        assert_eq!(result.best_move, Some(Move::from_uci("c2c7").unwrap()));
+        assert_eq!(result.score, mate_in(3));
+    }
+
+    #[test]
+    fn selective_search_needs_fewer_nodes() {
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let mut plain = Search::new();
+        plain.set_options(SearchOptions::none());
+        let plain = plain.search(&board, 5);
+        let selective = Search::new().search(&board, 5);
+        assert!(selective.nodes < plain.nodes / 2, "{} against {}", selective.nodes, plain.nodes);
+        assert!(board.is_legal(selective.best_move.unwrap()));
+    }
+
+    #[test]
+    fn aspiration_windows_keep_the_exact_score() {
+        let board = Board::from_fen("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1").unwrap();
+        let mut narrow = material_search();
+        narrow.set_options(SearchOptions { aspiration_windows: true, ..SearchOptions::none() });
+        let mut wide = material_search();
+        wide.set_options(SearchOptions::none());
+        assert_eq!(narrow.search(&board, 5).score, wide.search(&board, 5).score);
+    }
+
+    #[test]
+    fn no_null_moves_in_pawn_endings() {
+        // zugzwang decides this ending, so passing must not be tried
+        let board = Board::from_fen("8/8/2k5/4p3/4P3/3K4/8/8 w - - 0 1").unwrap();
+        let mut with_null_move = material_search();
+        with_null_move.set_options(SearchOptions { null_move: true, ..SearchOptions::none() });
+        let mut plain = material_search();
+        plain.set_options(SearchOptions::none());
+        assert_eq!(with_null_move.search(&board, 7), plain.search(&board, 7));
+    }
+
+    #[test]
+    fn stalemate_is_a_draw() {
+        let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(Search::new().search(&board, 2).score, 0);
+    }
+
+    #[test]
+    fn contempt_scores_draws_below_zero_for_either_side() {
+        let mut search = Search::new();
+        search.set_options(SearchOptions { contempt: 30, ..SearchOptions::default() });
+        // every king move completes fifty moves without a capture
+        for fen in ["8/8/8/4k3/8/8/8/4K3 w - - 99 80", "8/8/8/4k3/8/8/8/4K3 b - - 99 80"] {
+            assert_eq!(search.search(&Board::from_fen(fen).unwrap(), 2).score, -30, "{fen}");
+        }
+    }
+
+    #[test]
+    fn repetitions_are_draws_worth_the_contempt() {
+        let mut board = Board::new();
+        let mut history = GameHistory::new(&board);
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"] {
+            board.make(Move::from_uci(uci).unwrap());
+            history.push(&board);
+        }
+        // the knight coming out again repeats the position after 1. Nf3 Nf6
+        let repeat = Move::from_uci("g8f6").unwrap();
+        for contempt in [100, -100] {
+            let mut search = Search::new();
+            search.set_options(SearchOptions { contempt, ..SearchOptions::default() });
+            search.set_history(history.keys());
+            let result = search.search(&board, 3);
+            if contempt > 0 {
+                assert_ne!(result.best_move, Some(repeat));
+            } else {
+                assert_eq!((result.best_move, result.score), (Some(repeat), 100));
+            }
+        }
+    }
+
+    #[test]
+    fn risk_values_the_pieces_left() {
+        let board = Board::new();
+        let plain = Search::new().search(&board, 1).score;
+        let mut risky = Search::new();
+        risky.set_options(SearchOptions { risk: 50, ..SearchOptions::default() });
+        assert_eq!(risky.search(&board, 1).score, plain + 50);
+    }
+}
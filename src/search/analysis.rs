@@ -0,0 +1,171 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use crate::board::*;
+use crate::notation::*;
+
+use super::*;
+
+/// A score as shown to a person: centipawns, or moves until mate, negative
+/// when the side to move is mated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnalysisScore {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl AnalysisScore {
+    pub fn new(score: i32) -> Self {
+        match mate_distance(score) {
+            Some(moves) => AnalysisScore::Mate(moves),
+            None => AnalysisScore::Centipawns(score),
+        }
+    }
+}
+
+impl Display for AnalysisScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnalysisScore::Centipawns(cp) => write!(f, "{:+.2}", *cp as f64 / 100.0),
+            AnalysisScore::Mate(moves) => write!(f, "#{moves}"),
+        }
+    }
+}
+
+/// One principal variation of an analysis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PvLine {
+    /// Search score from the side to move.
+    pub score: i32,
+    /// Depth at which the line was found.
+    pub depth: u32,
+    pub pv: Vec<Move>,
+}
+
+impl PvLine {
+    pub fn analysis_score(&self) -> AnalysisScore {
+        AnalysisScore::new(self.score)
+    }
+
+    /// The line in standard algebraic notation.
+    pub fn to_san(&self, board: &Board) -> String {
+        to_san_line(board, &self.pv).join(" ")
+    }
+}
+
+/// The best lines found so far, best first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Analysis {
+    pub lines: Vec<PvLine>,
+    /// The deepest completed iteration.
+    pub depth: u32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl Search {
+    /// Iterative deepening for the best `multi_pv` moves instead of only
+    /// the best: at every depth the root is searched once per line, without
+    /// the moves of the lines already found. `on_update` is called whenever
+    /// a line is found, with the lines of the current depth followed by the
+    /// remaining ones of the previous depth.
+    pub fn analyze(&mut self, board: &Board, limits: &SearchLimits, multi_pv: usize, mut on_update: impl FnMut(&Analysis)) -> Analysis {
+        self.start(board, limits);
+
+        let mut board = board.clone();
+        let root_moves = self.root_moves(&board);
+        let max_depth = limits.depth.unwrap_or(MAX_PLY as u32).clamp(1, MAX_PLY as u32);
+        let multi_pv = multi_pv.clamp(1, root_moves.len().max(1));
+
+        let mut analysis = Analysis::default();
+        if root_moves.is_empty() { return analysis; }
+
+        let mut stability = 0;
+        for depth in 1..=max_depth {
+            let previous_best = analysis.lines.first().map(|line| line.pv[0]);
+            let mut lines: Vec<PvLine> = Vec::new();
+            let mut remaining = root_moves.clone();
+            while lines.len() < multi_pv && !remaining.is_empty() {
+                let first = analysis.lines.get(lines.len()).map(|line| line.pv[0]);
+                let mut pv = Vec::new();
+                let score = self.root(&mut board, &remaining, first, depth, (-INFINITY, INFINITY), &mut pv);
+                if self.stopped || pv.is_empty() { break; }
+
+                remaining.retain(|&mv| mv != pv[0]);
+                lines.push(PvLine { score, depth, pv });
+
+                let fresh: Vec<Move> = lines.iter().map(|line| line.pv[0]).collect();
+                let older = analysis.lines.iter().filter(|line| !fresh.contains(&line.pv[0])).cloned();
+                let mut update = Analysis {
+                    lines: lines.iter().cloned().chain(older).take(multi_pv).collect(),
+                    depth: analysis.depth,
+                    nodes: self.searched_nodes(),
+                    elapsed: self.time.as_ref().map_or(Duration::ZERO, |time| time.elapsed()),
+                };
+                if lines.len() == multi_pv || remaining.is_empty() { update.depth = depth; }
+
+                on_update(&update);
+                analysis = update;
+            }
+            if self.stopped { break; }
+
+            let best = &analysis.lines[0];
+            stability = if previous_best == Some(best.pv[0]) { stability + 1 } else { 0 };
+            self.table.store(board.zobrist_key(), 0, depth, best.score, Bound::Exact, Some(best.pv[0]));
+
+            if depth == max_depth { break; }
+            if self.time.as_ref().is_some_and(|time| !time.should_start_iteration(stability)) {
+                break;
+            }
+        }
+
+        analysis.nodes = self.nodes;
+        analysis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_best_lines_in_order() {
+        let board = Board::from_fen("4k3/8/8/2q1r3/3P4/8/8/7K w - - 0 1").unwrap();
+        let mut search = Search::new();
+        search.set_evaluator(Arc::new(MaterialEvaluator));
+        let mut updates = 0;
+        let analysis = search.analyze(&board, &SearchLimits::depth(2), 3, |_| updates += 1);
+
+        assert_eq!(updates, 6);
+        assert_eq!(analysis.depth, 2);
+        assert_eq!(analysis.lines.len(), 3);
+        assert_eq!(analysis.lines[0].pv[0], Move::from_uci("d4c5").unwrap());
+        assert_eq!(analysis.lines[1].pv[0], Move::from_uci("d4e5").unwrap());
+        assert!(analysis.lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(analysis.nodes > 0);
+    }
+
+    #[test]
+    fn reports_mates() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let analysis = Search::new().analyze(&board, &SearchLimits::depth(3), 2, |_| {});
+        assert_eq!(analysis.lines[0].analysis_score(), AnalysisScore::Mate(1));
+        assert_eq!(analysis.lines[0].to_san(&board), "Ra8#");
+        assert!(matches!(analysis.lines[1].analysis_score(), AnalysisScore::Centipawns(_)));
+    }
+
+    #[test]
+    fn never_asks_for_more_lines_than_moves() {
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let analysis = Search::new().analyze(&board, &SearchLimits::depth(1), 10, |_| {});
+        assert_eq!(analysis.lines.len(), 3);
+    }
+
+    #[test]
+    fn scores_are_shown_in_pawns_or_moves_to_mate() {
+        assert_eq!(AnalysisScore::new(35).to_string(), "+0.35");
+        assert_eq!(AnalysisScore::new(-120).to_string(), "-1.20");
+        assert_eq!(AnalysisScore::new(mate_in(3)).to_string(), "#2");
+        assert_eq!(AnalysisScore::new(mated_in(2)).to_string(), "#-1");
+    }
+}
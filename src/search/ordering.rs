@@ -0,0 +1,256 @@
+use crate::board::*;
+
+use super::*;
+
+// History scores are kept within this bound so that old cutoffs fade out as
+// new ones are recorded.
+const MAX_HISTORY: i32 = 16384;
+
+/// Victim value minus a fraction of the attacker's, so that capturing the
+/// most valuable piece with the least valuable attacker is tried first.
+pub fn mvv_lva(board: &Board, mv: Move) -> i32 {
+    let victim = board.captured_piece(mv).map_or(0, |piece| piece_value(piece.piece_type));
+    let promotion = mv.promotion.map_or(0, piece_value);
+    let attacker = board.piece_at(mv.from).map_or(0, |piece| piece_value(piece.piece_type));
+    (victim + promotion) * 10 - attacker / 10
+}
+
+/// Captures and promotions, searched before quiet moves.
+pub fn is_tactical(board: &Board, mv: Move) -> bool {
+    mv.promotion.is_some() || board.captured_piece(mv).is_some()
+}
+
+/// What a search thread has learned about which quiet moves cause cutoffs:
+/// two killer moves per ply, a counter-move for every previous move and a
+/// history score for every side, origin and target.
+pub struct OrderingTables {
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    counter_moves: Vec<Option<Move>>,
+    history: Vec<i32>,
+}
+
+impl OrderingTables {
+    pub fn new() -> Self {
+        Self {
+            killers: [[None; 2]; MAX_PLY],
+            counter_moves: vec![None; 64 * 64],
+            history: vec![0; 2 * 64 * 64],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers[ply.min(MAX_PLY - 1)]
+    }
+
+    pub fn counter_move(&self, previous: Move) -> Option<Move> {
+        self.counter_moves[previous.from * 64 + previous.to]
+    }
+
+    pub fn history(&self, board: &Board, mv: Move) -> i32 {
+        self.history[Self::history_index(board, mv)]
+    }
+
+    fn history_index(board: &Board, mv: Move) -> usize {
+        board.side_to_move.index() * 64 * 64 + mv.from * 64 + mv.to
+    }
+
+    /// Records a quiet move which caused a beta cutoff, and penalises the
+    /// quiet moves searched before it in vain.
+    pub fn record_cutoff(&mut self, board: &Board, mv: Move, ply: usize, depth: u32, previous: Option<Move>, tried: &[Move]) {
+        let killers = &mut self.killers[ply.min(MAX_PLY - 1)];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+
+        if let Some(previous) = previous {
+            self.counter_moves[previous.from * 64 + previous.to] = Some(mv);
+        }
+
+        let bonus = (depth * depth).min(400) as i32;
+        self.update_history(board, mv, bonus);
+        for &quiet in tried.iter().filter(|&&quiet| quiet != mv) {
+            self.update_history(board, quiet, -bonus);
+        }
+    }
+
+    fn update_history(&mut self, board: &Board, mv: Move, bonus: i32) {
+        let entry = &mut self.history[Self::history_index(board, mv)];
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+impl Default for OrderingTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    ScoreCaptures,
+    GoodCaptures,
+    Killers,
+    CounterMove,
+    ScoreQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the legal moves of a position one at a time, in the order most
+/// likely to cause an early cutoff: the hash move, captures which do not lose
+/// material by MVV-LVA, the killer moves, the counter-move, the other quiet
+/// moves by history score and finally the losing captures. Each stage is
+/// only sorted when it is reached, which most cutoffs never do.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    counter_move: Option<Move>,
+    remaining: Vec<Move>,
+    // moves of the current stage, best last
+    current: Vec<(Move, i32)>,
+    bad_captures: Vec<(Move, i32)>,
+}
+
+impl MovePicker {
+    pub fn new(moves: Vec<Move>, hash_move: Option<Move>, ply: usize, previous: Option<Move>, tables: &OrderingTables) -> Self {
+        Self {
+            stage: Stage::HashMove,
+            hash_move,
+            killers: tables.killers(ply),
+            counter_move: previous.and_then(|previous| tables.counter_move(previous)),
+            remaining: moves,
+            current: Vec::new(),
+            bad_captures: Vec::new(),
+        }
+    }
+
+    fn take(&mut self, mv: Option<Move>) -> Option<Move> {
+        let position = self.remaining.iter().position(|&m| Some(m) == mv)?;
+        Some(self.remaining.swap_remove(position))
+    }
+
+    pub fn next(&mut self, board: &Board, tables: &OrderingTables) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::ScoreCaptures;
+                    if let Some(mv) = self.take(self.hash_move) { return Some(mv); }
+                },
+                Stage::ScoreCaptures => {
+                    self.stage = Stage::GoodCaptures;
+                    let (tactical, quiet) = self.remaining.iter().partition(|&&mv| is_tactical(board, mv));
+                    self.remaining = quiet;
+                    for mv in tactical {
+                        let scored = (mv, mvv_lva(board, mv));
+                        if see(board, mv) >= 0 { self.current.push(scored); } else { self.bad_captures.push(scored); }
+                    }
+                    self.current.sort_by_key(|&(_, score)| score);
+                },
+                Stage::GoodCaptures => {
+                    if let Some((mv, _)) = self.current.pop() { return Some(mv); }
+                    self.stage = Stage::Killers;
+                },
+                Stage::Killers => {
+                    for killer in self.killers {
+                        if let Some(mv) = self.take(killer) { return Some(mv); }
+                    }
+                    self.stage = Stage::CounterMove;
+                },
+                Stage::CounterMove => {
+                    self.stage = Stage::ScoreQuiets;
+                    if let Some(mv) = self.take(self.counter_move) { return Some(mv); }
+                },
+                Stage::ScoreQuiets => {
+                    self.stage = Stage::Quiets;
+                    self.current = self.remaining.drain(..).map(|mv| (mv, tables.history(board, mv))).collect();
+                    self.current.sort_by_key(|&(_, score)| score);
+                },
+                Stage::Quiets => {
+                    if let Some((mv, _)) = self.current.pop() { return Some(mv); }
+                    self.stage = Stage::BadCaptures;
+                    self.current = std::mem::take(&mut self.bad_captures);
+                    self.current.sort_by_key(|&(_, score)| score);
+                },
+                Stage::BadCaptures => {
+                    if let Some((mv, _)) = self.current.pop() { return Some(mv); }
+                    self.stage = Stage::Done;
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn uci(text: &str) -> Move {
+        Move::from_uci(text).unwrap()
+    }
+
+    fn picked(board: &Board, picker: &mut MovePicker, tables: &OrderingTables) -> Vec<String> {
+        std::iter::from_fn(|| picker.next(board, tables)).map(|mv| mv.to_string()).collect()
+    }
+
+    #[test]
+    fn stages_come_in_order() {
+        // Nxc6 wins a knight, while Nxd5 and Qxd5 lose material to exd5
+        let board = Board::from_fen("4k3/8/2n1p3/3p4/1N6/8/8/3QK3 w - - 0 1").unwrap();
+        let mut tables = OrderingTables::new();
+        tables.record_cutoff(&board, uci("e1f2"), 3, 4, Some(uci("e8d8")), &[]);
+        tables.record_cutoff(&board, uci("d1g4"), 2, 4, None, &[]);
+
+        let mut picker = MovePicker::new(board.legal_moves(), Some(uci("e1e2")), 2, Some(uci("e8d8")), &tables);
+        let moves = picked(&board, &mut picker, &tables);
+
+        assert_eq!(moves[0], "e1e2");
+        assert_eq!(moves[1], "b4c6");
+        assert_eq!(moves[2], "d1g4");
+        assert_eq!(moves[3], "e1f2");
+        assert_eq!(moves[moves.len() - 2..], ["b4d5", "d1d5"]);
+        assert_eq!(moves.len(), board.legal_moves().len());
+    }
+
+    #[test]
+    fn hash_move_comes_before_any_scoring() {
+        let board = Board::from_fen("4k3/8/2n1p3/3p4/1N6/8/8/3QK3 w - - 0 1").unwrap();
+        let tables = OrderingTables::new();
+        let mut picker = MovePicker::new(board.legal_moves(), Some(uci("e1e2")), 0, None, &tables);
+        assert_eq!(picker.next(&board, &tables), Some(uci("e1e2")));
+        assert!(picker.current.is_empty() && picker.bad_captures.is_empty());
+    }
+
+    #[test]
+    fn history_orders_quiet_moves() {
+        let board = Board::new();
+        let mut tables = OrderingTables::new();
+        tables.record_cutoff(&board, uci("g1f3"), 10, 3, None, &[uci("b1c3")]);
+        assert!(tables.history(&board, uci("g1f3")) > 0);
+        assert!(tables.history(&board, uci("b1c3")) < 0);
+
+        let mut picker = MovePicker::new(board.legal_moves(), None, 0, None, &tables);
+        let moves = picked(&board, &mut picker, &tables);
+        assert_eq!(moves[0], "g1f3");
+        assert_eq!(moves.last().unwrap(), "b1c3");
+    }
+
+    #[test]
+    fn history_stays_bounded() {
+        let board = Board::new();
+        let mut tables = OrderingTables::new();
+        for _ in 0..10000 {
+            tables.record_cutoff(&board, uci("e2e4"), 1, 20, None, &[]);
+        }
+        assert!(tables.history(&board, uci("e2e4")) <= MAX_HISTORY);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::board::*;
+use crate::board::piece::*;
+
+use super::*;
+
+// Kings only recapture when nothing else can, and then only safely.
+fn exchange_value(piece_type: PieceType) -> i32 {
+    if piece_type == PieceType::King { MATE } else { piece_value(piece_type) }
+}
+
+/// Static exchange evaluation: the material `mv` wins once both sides have
+/// recaptured on its target square with their least valuable piece for as
+/// long as that pays off. Pins are ignored.
+pub fn see(board: &Board, mv: Move) -> i32 {
+    let mover = match board.piece_at(mv.from) {
+        Some(piece) => piece,
+        None => return 0,
+    };
+
+    let mut gains = vec![board.captured_piece(mv).map_or(0, |piece| piece_value(piece.piece_type))];
+    let mut on_square = exchange_value(mover.piece_type);
+    if let Some(promotion) = mv.promotion {
+        gains[0] += piece_value(promotion) - piece_value(PieceType::Pawn);
+        on_square = piece_value(promotion);
+    }
+
+    // play out the exchange on the cells alone, uncovering x-ray attackers
+    let mut cells = board.clone();
+    if cells.piece_at(mv.to).is_none() && gains[0] > 0 && mv.promotion.is_none() {
+        let captured = if mover.color == PieceColor::White { mv.to + 8 } else { mv.to - 8 };
+        cells.cells[captured].piece = None;
+    }
+    cells.cells[mv.to].piece = Some(Piece { piece_type: mv.promotion.unwrap_or(mover.piece_type), color: mover.color });
+    cells.cells[mv.from].piece = None;
+
+    let mut side = mover.color.opposite();
+    loop {
+        let from = cells.attackers(mv.to, side).into_iter()
+            .min_by_key(|&idx| exchange_value(cells.cells[idx].piece.unwrap().piece_type));
+        let from = match from {
+            Some(from) => from,
+            None => break,
+        };
+
+        let attacker = cells.cells[from].piece.unwrap();
+        cells.cells[from].piece = None;
+        if attacker.piece_type == PieceType::King && !cells.attackers(mv.to, side.opposite()).is_empty() {
+            break;
+        }
+
+        gains.push(on_square - gains.last().unwrap());
+        on_square = exchange_value(attacker.piece_type);
+        cells.cells[mv.to].piece = Some(attacker);
+        side = side.opposite();
+    }
+
+    // each side may stop recapturing when going on would lose
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
+    }
+    gains[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn see_uci(fen: &str, uci: &str) -> i32 {
+        see(&Board::from_fen(fen).unwrap(), Move::from_uci(uci).unwrap())
+    }
+
+    #[test]
+    fn undefended_and_defended_captures() {
+        assert_eq!(see_uci("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        assert_eq!(see_uci("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"), 100 - 900);
+    }
+
+    #[test]
+    fn x_ray_recaptures() {
+        // NxP, PxN, BxP, then the queen behind the bishop recaptures too
+        assert_eq!(see_uci("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -220);
+        // two rooks against one defender
+        assert_eq!(see_uci("4k3/4r3/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5"), 100);
+    }
+
+    #[test]
+    fn quiet_moves_to_attacked_squares() {
+        assert_eq!(see_uci("4k3/8/8/3p4/8/8/8/2Q1K3 w - - 0 1", "c1c4"), -900);
+        assert_eq!(see_uci(START_FEN, "e2e4"), 0);
+    }
+
+    #[test]
+    fn en_passant_and_promotion() {
+        assert_eq!(see_uci("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", "e5d6"), 100);
+        assert_eq!(see_uci("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800);
+        assert_eq!(see_uci("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800 - 900);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::board::*;
+use crate::board::piece::*;
+
+/// The selective techniques of the search, all on by default. Each can be
+/// turned off to measure what it is worth in a match. Contempt and risk
+/// give the search a character, see `Personality`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Let the opponent move twice: if a shallow search still fails high,
+    /// so would a real move.
+    pub null_move: bool,
+    /// Search quiet moves late in the order less deeply, searching again at
+    /// full depth those which turn out better than expected.
+    pub late_move_reductions: bool,
+    /// Skip quiet moves near the horizon which cannot bring the static
+    /// score up to alpha.
+    pub futility: bool,
+    /// Return the static score near the horizon when it beats beta by a
+    /// margin.
+    pub reverse_futility: bool,
+    /// Search the root in a narrow window around the previous score.
+    pub aspiration_windows: bool,
+    /// Principal variation search: prove moves after the first worse with a
+    /// null window and search them again only when they are not.
+    pub pvs: bool,
+    /// Centipawns the side searched for gives up to avoid a draw, or takes
+    /// to reach one when negative.
+    pub contempt: i32,
+    /// Centipawns the side searched for sets on keeping the pieces on the
+    /// board, shrinking as they are traded. A negative risk seeks trades.
+    pub risk: i32,
+}
+
+impl SearchOptions {
+    /// The names accepted by `disable`.
+    pub const NAMES: [&'static str; 6] = ["null-move", "lmr", "futility", "reverse-futility", "aspiration", "pvs"];
+
+    /// Plain alpha-beta without any of the techniques.
+    pub fn none() -> Self {
+        Self {
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            reverse_futility: false,
+            aspiration_windows: false,
+            pvs: false,
+            contempt: 0,
+            risk: 0,
+        }
+    }
+
+    /// Turns off the technique called `name`, one of `NAMES`.
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        let option = match name {
+            "null-move" => &mut self.null_move,
+            "lmr" => &mut self.late_move_reductions,
+            "futility" => &mut self.futility,
+            "reverse-futility" => &mut self.reverse_futility,
+            "aspiration" => &mut self.aspiration_windows,
+            "pvs" => &mut self.pvs,
+            _ => return Err(format!("Unknown search option {name}, expected one of {}", Self::NAMES.join(", "))),
+        };
+        *option = false;
+        Ok(())
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            reverse_futility: true,
+            aspiration_windows: true,
+            pvs: true,
+            contempt: 0,
+            risk: 0,
+        }
+    }
+}
+
+/// Plies of depth saved by a null move.
+pub fn null_move_reduction(depth: u32) -> u32 {
+    if depth >= 7 { 3 } else { 2 }
+}
+
+/// Plies by which the `index`th move searched is reduced, growing slowly
+/// with both the depth and the number of moves already tried.
+pub fn late_move_reduction(depth: u32, index: usize) -> u32 {
+    let reduction = 0.75 + (depth as f64).ln() * (index as f64).ln() / 2.25;
+    (reduction as u32).min(depth - 1)
+}
+
+/// How far the static score may be below alpha for quiet moves at `depth`
+/// to be worth searching.
+pub fn futility_margin(depth: u32) -> i32 {
+    100 + 100 * depth as i32
+}
+
+/// How far the static score must be above beta to stop searching at `depth`.
+pub fn reverse_futility_margin(depth: u32) -> i32 {
+    80 * depth as i32
+}
+
+/// Whether the side to move has a piece besides its king and pawns. Without
+/// one, zugzwang is common and passing is no proof of anything.
+pub fn has_non_pawn_material(board: &Board) -> bool {
+    board.cells.iter().filter_map(|cell| cell.piece).any(|piece| {
+        piece.color == board.side_to_move && !matches!(piece.piece_type, PieceType::King | PieceType::Pawn)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_disabled_by_name() {
+        let mut options = SearchOptions::default();
+        for name in SearchOptions::NAMES {
+            options.disable(name).unwrap();
+        }
+        assert_eq!(options, SearchOptions::none());
+        assert!(options.disable("razoring").is_err());
+    }
+
+    #[test]
+    fn reductions_grow_with_depth_and_move_number() {
+        assert_eq!(late_move_reduction(3, 3), 1);
+        assert!(late_move_reduction(12, 30) > late_move_reduction(4, 4));
+        assert!(late_move_reduction(2, 60) < 2);
+    }
+
+    #[test]
+    fn pawn_endings_are_zugzwang_prone() {
+        assert!(!has_non_pawn_material(&Board::from_fen("4k3/4p3/8/8/8/8/3NP3/4K3 b - - 0 1").unwrap()));
+        assert!(has_non_pawn_material(&Board::from_fen("4k3/4p3/8/8/8/8/3NP3/4K3 w - - 0 1").unwrap()));
+    }
+}
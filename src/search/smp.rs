@@ -0,0 +1,125 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::board::*;
+
+use super::*;
+
+/// Lazy SMP: `threads` searches of the same position run at once and share
+/// only the transposition table, each helper profiting from the entries of
+/// the others. Helpers search at varied depths and stop when the main
+/// search, which keeps to the limits, is done. The deepest completed result
+/// wins, the main thread's on ties. The node limit counts the nodes of all
+/// threads and `on_iteration` only hears from the main thread.
+///
+/// With a single thread this is exactly `Search::iterate` on the calling
+/// thread, so results are reproducible.
+pub fn parallel_search(
+    board: &Board,
+    limits: &SearchLimits,
+    threads: usize,
+    stop: &Arc<AtomicBool>,
+    new_search: impl Fn(&Arc<AtomicBool>) -> Search,
+    on_iteration: impl FnMut(&SearchResult),
+) -> SearchResult {
+    let mut main = new_search(stop);
+    if threads <= 1 {
+        return main.iterate(board, limits, on_iteration);
+    }
+
+    let shared_nodes = Arc::new(AtomicU64::new(0));
+    let helpers_stop = Arc::new(AtomicBool::new(false));
+    let helper_limits = SearchLimits { depth: limits.depth, ..Default::default() };
+    main.table.new_search();
+
+    thread::scope(|scope| {
+        let helpers: Vec<_> = (1..threads).map(|helper| {
+            let mut search = new_search(&helpers_stop);
+            search.helper = helper;
+            search.shared_nodes = Some(shared_nodes.clone());
+            scope.spawn(move || {
+                let result = search.iterate(board, &helper_limits, |_| {});
+                (result, search.nodes())
+            })
+        }).collect();
+
+        main.shared_nodes = Some(shared_nodes.clone());
+        let mut best = main.iterate(board, limits, on_iteration);
+        let mut nodes = main.nodes();
+
+        helpers_stop.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            let (result, helper_nodes) = helper.join().expect("Search thread panicked");
+            nodes += helper_nodes;
+            if result.depth > best.depth && result.best_move.is_some() {
+                best = SearchResult { nodes: best.nodes, hashfull: best.hashfull, ..result };
+            }
+        }
+
+        best.nodes = nodes;
+        best
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn new_search(table: &Arc<TranspositionTable>) -> impl Fn(&Arc<AtomicBool>) -> Search + '_ {
+        |stop| {
+            let mut search = Search::with_table(table.clone(), stop.clone());
+            search.set_evaluator(Arc::new(ClassicalEvaluator::default()));
+            search
+        }
+    }
+
+    fn run(board: &Board, limits: &SearchLimits, threads: usize) -> SearchResult {
+        let table = Arc::new(TranspositionTable::new(4));
+        parallel_search(board, limits, threads, &Arc::new(AtomicBool::new(false)), new_search(&table), |_| {})
+    }
+
+    #[test]
+    fn single_thread_is_deterministic() {
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let first = run(&board, &SearchLimits::depth(4), 1);
+        let second = run(&board, &SearchLimits::depth(4), 1);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn helpers_add_their_nodes() {
+        let board = Board::new();
+        let single = run(&board, &SearchLimits::depth(4), 1);
+        let result = run(&board, &SearchLimits::depth(4), 4);
+
+        assert!(result.depth >= 4);
+        assert!(board.is_legal(result.best_move.unwrap()));
+        // the helpers search alongside the main thread until it is done
+        assert!(result.nodes > single.nodes, "{} nodes on 4 threads, {} on one", result.nodes, single.nodes);
+    }
+
+    #[test]
+    fn finds_the_mate_with_many_threads() {
+        let board = Board::from_fen("8/7k/4R3/8/8/K7/2R5/8 w - - 0 1").unwrap();
+        let result = run(&board, &SearchLimits::depth(3), 3);
+        assert_eq!(result.score, mate_in(3));
+    }
+
+    #[test]
+    fn stops_all_threads() {
+        let board = Board::new();
+        let stop = Arc::new(AtomicBool::new(false));
+        let table = Arc::new(TranspositionTable::new(4));
+        let result = thread::scope(|scope| {
+            let searching = scope.spawn(|| parallel_search(&board, &SearchLimits::default(), 3, &stop, new_search(&table), |_| {}));
+            thread::sleep(Duration::from_millis(50));
+            stop.store(true, Ordering::Relaxed);
+            searching.join().unwrap()
+        });
+        assert!(board.is_legal(result.best_move.unwrap()));
+    }
+}
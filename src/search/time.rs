@@ -0,0 +1,170 @@
+use std::time::{Duration, Instant};
+
+use crate::board::*;
+use crate::board::piece::*;
+use crate::eval::game_phase;
+
+/// Time left on the clocks, as in a UCI `go wtime btime winc binc` command.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Clock {
+    pub white: Duration,
+    pub black: Duration,
+    pub white_increment: Duration,
+    pub black_increment: Duration,
+    /// Moves until the next time control, if the control is not sudden death.
+    pub moves_to_go: Option<u32>,
+}
+
+/// Bounds on how long a strategy may think about a move. Several limits may
+/// be combined and the search stops at the first one reached. Without any
+/// limit the search runs until it is stopped.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    pub movetime: Option<Duration>,
+    pub nodes: Option<u64>,
+    pub depth: Option<u32>,
+    pub clock: Option<Clock>,
+}
+
+impl SearchLimits {
+    pub fn movetime(movetime: Duration) -> Self {
+        Self { movetime: Some(movetime), ..Default::default() }
+    }
+
+    pub fn depth(depth: u32) -> Self {
+        Self { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        Self { nodes: Some(nodes), ..Default::default() }
+    }
+
+    pub fn clock(clock: Clock) -> Self {
+        Self { clock: Some(clock), ..Default::default() }
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        self.movetime.is_none() && self.nodes.is_none() && self.depth.is_none() && self.clock.is_none()
+    }
+}
+
+// Time kept in reserve for move overhead such as printing and reading input.
+const OVERHEAD: Duration = Duration::from_millis(20);
+
+/// Decides when an iterative deepening search should stop. The soft limit is
+/// checked between iterations and stretched or shrunk depending on whether
+/// the best move keeps changing, the hard limit aborts a running iteration.
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, board: &Board) -> Self {
+        let mut soft = limits.movetime;
+        let mut hard = limits.movetime;
+
+        if let Some(clock) = limits.clock {
+            let (remaining, increment) = match board.side_to_move {
+                PieceColor::White => (clock.white, clock.white_increment),
+                PieceColor::Black => (clock.black, clock.black_increment),
+            };
+
+            // expect a longer game ahead in the opening than in the endgame
+            let moves_left = clock.moves_to_go.unwrap_or(20 + game_phase(board) as u32).max(1);
+            let usable = remaining.saturating_sub(OVERHEAD);
+            let base = usable / moves_left + increment * 3 / 4;
+            let clock_soft = base.min(usable / 2);
+            let clock_hard = (base * 4).min(usable * 2 / 5).max(clock_soft);
+
+            soft = Some(soft.map_or(clock_soft, |s| s.min(clock_soft)));
+            hard = Some(hard.map_or(clock_hard, |h| h.min(clock_hard)));
+        }
+
+        Self { start: Instant::now(), soft, hard }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.soft
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+
+    /// Whether to start another iteration. `stability` counts how many
+    /// iterations in a row returned the same best move.
+    pub fn should_start_iteration(&self, stability: u32) -> bool {
+        let soft = match self.soft {
+            Some(soft) => soft,
+            None => return true,
+        };
+
+        let scale = match stability {
+            0 => 1.5,
+            1 => 1.2,
+            2 => 1.0,
+            3 => 0.8,
+            _ => 0.6,
+        };
+
+        // the next iteration takes a few times longer than the last one, so
+        // do not start it when it would likely not finish in time
+        self.elapsed().as_secs_f64() < soft.as_secs_f64() * scale * 0.6
+            && !self.hard_limit_reached()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn movetime_is_both_limits() {
+        let manager = TimeManager::new(&SearchLimits::movetime(Duration::from_millis(500)), &Board::new());
+        assert_eq!(manager.soft_limit(), Some(Duration::from_millis(500)));
+        assert_eq!(manager.hard_limit(), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn clock_allocation_depends_on_phase() {
+        let clock = Clock { white: Duration::from_secs(60), black: Duration::from_secs(60), ..Default::default() };
+        let opening = TimeManager::new(&SearchLimits::clock(clock), &Board::new());
+        let endgame = TimeManager::new(&SearchLimits::clock(clock), &Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap());
+
+        assert!(opening.soft_limit().unwrap() < endgame.soft_limit().unwrap());
+        assert!(opening.hard_limit().unwrap() > opening.soft_limit().unwrap());
+        assert!(endgame.hard_limit().unwrap() <= Duration::from_secs(24));
+    }
+
+    #[test]
+    fn increment_and_moves_to_go() {
+        let clock = Clock {
+            white: Duration::from_secs(10),
+            black: Duration::from_secs(10),
+            white_increment: Duration::from_secs(2),
+            moves_to_go: Some(5),
+            ..Default::default()
+        };
+        let manager = TimeManager::new(&SearchLimits::clock(clock), &Board::new());
+        let soft = manager.soft_limit().unwrap();
+        assert!(soft > Duration::from_millis(3400) && soft < Duration::from_millis(3600), "{soft:?}");
+    }
+
+    #[test]
+    fn unstable_best_move_gets_more_time() {
+        let manager = TimeManager::new(&SearchLimits::movetime(Duration::from_millis(100)), &Board::new());
+        std::thread::sleep(Duration::from_millis(55));
+        assert!(manager.should_start_iteration(0));
+        assert!(!manager.should_start_iteration(4));
+    }
+}
@@ -0,0 +1,293 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::board::*;
+use crate::board::piece::*;
+
+use super::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The search failed high, the real score is at least this.
+    Lower,
+    /// The search failed low, the real score is at most this.
+    Upper,
+}
+
+/// What the table remembers about a position, with mate scores relative to
+/// the ply it was probed at.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+impl TtEntry {
+    /// The stored score if it settles a search with the given window and depth.
+    pub fn cutoff(&self, depth: u32, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth < depth { return None; }
+
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+}
+
+// An entry packs its data into one word and stores the key xor the data in
+// another, so a torn write from another thread shows up as a key mismatch
+// instead of as a corrupt entry. Data layout from the low bits: move (16),
+// score (16), depth (8), bound (2), age (6).
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+const BUCKET_SIZE: usize = 4;
+const AGE_CYCLE: u8 = 64;
+
+type Bucket = [Slot; BUCKET_SIZE];
+
+fn pack_move(mv: Option<Move>) -> u64 {
+    match mv {
+        None => 0,
+        Some(mv) => {
+            let promotion = mv.promotion.map_or(0, |piece_type| piece_type.index() as u64 + 1);
+            1 << 15 | promotion << 12 | (mv.to as u64) << 6 | mv.from as u64
+        },
+    }
+}
+
+fn unpack_move(bits: u64) -> Option<Move> {
+    if bits & 1 << 15 == 0 { return None; }
+
+    let promotion = match (bits >> 12) & 7 {
+        0 => None,
+        index => Some(PieceType::ALL[index as usize - 1]),
+    };
+    Some(Move { from: (bits & 63) as usize, to: ((bits >> 6) & 63) as usize, promotion })
+}
+
+/// Mate scores are stored as distance from the stored position rather than
+/// from the root, so they stay right when reached along another path.
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if is_mate_score(score) {
+        if score > 0 { score + ply as i32 } else { score - ply as i32 }
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if is_mate_score(score) {
+        if score > 0 { score - ply as i32 } else { score + ply as i32 }
+    } else {
+        score
+    }
+}
+
+/// Fixed-size hash table of search results keyed by Zobrist key. Entries
+/// are written without locks so that several search threads can share it.
+/// When a bucket is full the shallowest entry is replaced, with entries from
+/// earlier searches counting as shallower the older they are.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub const DEFAULT_SIZE_MB: usize = 16;
+
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb.max(1) << 20) / std::mem::size_of::<Bucket>();
+        let mut buckets = Vec::with_capacity(count);
+        buckets.resize_with(count, Default::default);
+        Self { buckets, age: AtomicU8::new(0) }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        (self.buckets.len() * std::mem::size_of::<Bucket>()) >> 20
+    }
+
+    /// Number of entries the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flatten() {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Marks the entries stored so far as belonging to an older search.
+    pub fn new_search(&self) {
+        let age = self.age.load(Ordering::Relaxed);
+        self.age.store((age + 1) % AGE_CYCLE, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        let index = ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+        &self.buckets[index]
+    }
+
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        for slot in self.bucket(key) {
+            let data = slot.data.load(Ordering::Relaxed);
+            if slot.key.load(Ordering::Relaxed) ^ data != key { continue; }
+
+            let bound = match (data >> 40) & 3 {
+                1 => Bound::Exact,
+                2 => Bound::Lower,
+                3 => Bound::Upper,
+                _ => continue,
+            };
+            return Some(TtEntry {
+                best_move: unpack_move(data & 0xffff),
+                score: score_from_table((data >> 16) as u16 as i16 as i32, ply),
+                depth: ((data >> 32) & 0xff) as u32,
+                bound,
+            });
+        }
+        None
+    }
+
+    pub fn store(&self, key: u64, ply: usize, depth: u32, score: i32, bound: Bound, best_move: Option<Move>) {
+        let age = self.age.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+
+        let existing = bucket.iter().find(|slot| slot.key.load(Ordering::Relaxed) ^ slot.data.load(Ordering::Relaxed) == key);
+        let slot = existing.unwrap_or_else(|| {
+            bucket.iter().min_by_key(|slot| {
+                let data = slot.data.load(Ordering::Relaxed);
+                if (data >> 40) & 3 == 0 { return i32::MIN; }
+                let entry_age = ((data >> 42) & 63) as u8;
+                let staleness = (age + AGE_CYCLE - entry_age) % AGE_CYCLE;
+                ((data >> 32) & 0xff) as i32 - 8 * staleness as i32
+            }).unwrap()
+        });
+
+        // keep the best move of an earlier search of the same position
+        let mut move_bits = pack_move(best_move);
+        if best_move.is_none() && existing.is_some() {
+            move_bits = slot.data.load(Ordering::Relaxed) & 0xffff;
+        }
+
+        let bound_bits: u64 = match bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let score = score_to_table(score, ply).clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16 as u64;
+        let data = (age as u64) << 42 | bound_bits << 40 | (depth.min(255) as u64) << 32 | score << 16 | move_bits;
+
+        slot.data.store(data, Ordering::Relaxed);
+        slot.key.store(key ^ data, Ordering::Relaxed);
+    }
+
+    /// Permille of sampled entries written during the current search, as
+    /// reported by UCI engines.
+    pub fn hashfull(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed) as u64;
+        let sample = self.buckets.iter().take(1000 / BUCKET_SIZE).flatten();
+        let (mut used, mut total) = (0u32, 0u32);
+        for slot in sample {
+            let data = slot.data.load(Ordering::Relaxed);
+            total += 1;
+            if (data >> 40) & 3 != 0 && (data >> 42) & 63 == age {
+                used += 1;
+            }
+        }
+        (used * 1000).checked_div(total).unwrap_or(0)
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SIZE_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn stores_and_probes() {
+        let table = TranspositionTable::new(1);
+        let mv = Move::with_promotion(12, 4, PieceType::Knight);
+        table.store(42, 0, 7, -35, Bound::Lower, Some(mv));
+
+        let entry = table.probe(42, 0).unwrap();
+        assert_eq!(entry, TtEntry { best_move: Some(mv), score: -35, depth: 7, bound: Bound::Lower });
+        assert_eq!(table.probe(43, 0), None);
+    }
+
+    #[test]
+    fn size_in_megabytes() {
+        let table = TranspositionTable::new(2);
+        assert_eq!(table.size_mb(), 2);
+        assert_eq!(table.capacity(), 2 * 1024 * 1024 / 16);
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_the_position() {
+        let table = TranspositionTable::new(1);
+        // mate in 5 plies from the root, found 3 plies deep
+        table.store(1, 3, 2, mate_in(5), Bound::Exact, None);
+        // the same position reached 1 ply deep is mate in 3 plies
+        assert_eq!(table.probe(1, 1).unwrap().score, mate_in(3));
+    }
+
+    #[test]
+    fn keeps_the_move_when_storing_without_one() {
+        let table = TranspositionTable::new(1);
+        let mv = Move::new(52, 36);
+        table.store(9, 0, 3, 10, Bound::Exact, Some(mv));
+        table.store(9, 0, 4, 20, Bound::Upper, None);
+        assert_eq!(table.probe(9, 0).unwrap().best_move, Some(mv));
+        assert_eq!(table.probe(9, 0).unwrap().depth, 4);
+    }
+
+    #[test]
+    fn replaces_old_entries_first() {
+        // small keys all land in the first bucket
+        let table = TranspositionTable::new(1);
+        for key in 1..=4 {
+            table.store(key, 0, 10, 0, Bound::Exact, None);
+        }
+        table.store(5, 0, 1, 0, Bound::Exact, None);
+        assert!(table.probe(5, 0).is_some());
+        assert_eq!((1..=4).filter(|&key| table.probe(key, 0).is_some()).count(), 4 - 1);
+
+        table.new_search();
+        table.store(6, 0, 1, 0, Bound::Exact, None);
+        // the shallow entry of the previous search went, not a deep one
+        assert!(table.probe(6, 0).is_some());
+        assert!(table.probe(5, 0).is_none());
+        assert_eq!((1..=4).filter(|&key| table.probe(key, 0).is_some()).count(), 3);
+    }
+
+    #[test]
+    fn hashfull_counts_current_entries() {
+        let table = TranspositionTable::new(1);
+        assert_eq!(table.hashfull(), 0);
+        for i in 0..table.capacity() as u64 {
+            table.store(i.wrapping_mul(0x9e3779b97f4a7c15), 0, 1, 0, Bound::Exact, None);
+        }
+        assert!(table.hashfull() > 500);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+        table.clear();
+        assert_eq!(table.probe(0, 0), None);
+    }
+}
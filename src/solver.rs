@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use crate::board::*;
+use crate::board::piece::*;
+use crate::notation::*;
+
+/// What the side to move, the solver, must achieve in a chess problem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stipulation {
+    /// Mate the opponent whatever it does.
+    Mate,
+    /// Force the opponent to mate the solver, however it tries to avoid it.
+    Selfmate,
+    /// Play together with the opponent until it mates the solver, the
+    /// solver moving first.
+    Helpmate,
+}
+
+impl Stipulation {
+    /// The usual prefix of the number of moves, e.g. `s#` in `s#3`.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Stipulation::Mate => "#",
+            Stipulation::Selfmate => "s#",
+            Stipulation::Helpmate => "h#",
+        }
+    }
+}
+
+/// A move of a solution and the moves keeping to the stipulation after it.
+/// After a solver move these are all replies, except in a helpmate where
+/// they are only the replies which lead to mate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolutionNode {
+    pub mv: Move,
+    pub children: Vec<SolutionNode>,
+}
+
+/// Every way of meeting the stipulation in at most `moves` moves of the
+/// solver. A sound mate or selfmate problem has a single key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub board: Board,
+    pub stipulation: Stipulation,
+    pub moves: u32,
+    /// The first moves which solve the problem, with their continuations.
+    pub keys: Vec<SolutionNode>,
+}
+
+impl Solution {
+    pub fn key_moves(&self) -> Vec<Move> {
+        self.keys.iter().map(|node| node.mv).collect()
+    }
+
+    pub fn is_solved(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// More than one key: the problem has a cook, unless it is a helpmate
+    /// where different first moves are different solutions.
+    pub fn is_cooked(&self) -> bool {
+        self.stipulation != Stipulation::Helpmate && self.keys.len() > 1
+    }
+
+    /// Every line of the solution tree from a key to its end.
+    pub fn lines(&self) -> Vec<Vec<Move>> {
+        fn collect(nodes: &[SolutionNode], line: &mut Vec<Move>, lines: &mut Vec<Vec<Move>>) {
+            for node in nodes {
+                line.push(node.mv);
+                if node.children.is_empty() {
+                    lines.push(line.clone());
+                } else {
+                    collect(&node.children, line, lines);
+                }
+                line.pop();
+            }
+        }
+
+        let mut lines = Vec::new();
+        collect(&self.keys, &mut Vec::new(), &mut lines);
+        lines
+    }
+
+    /// The solution tree in SAN, one move per line indented by its depth,
+    /// with keys marked `!` as problemists do.
+    pub fn to_text(&self) -> String {
+        fn write(board: &mut Board, nodes: &[SolutionNode], ply: usize, keys: bool, text: &mut String) {
+            for node in nodes {
+                let number = if board.side_to_move == PieceColor::White {
+                    format!("{}.", board.fullmove_number)
+                } else {
+                    format!("{}...", board.fullmove_number)
+                };
+                let mark = if keys && ply == 0 { "!" } else { "" };
+                text.push_str(&format!("{}{} {}{}\n", "  ".repeat(ply), number, to_san(board, node.mv), mark));
+
+                let undo = board.make(node.mv);
+                write(board, &node.children, ply + 1, keys, text);
+                board.unmake(node.mv, undo);
+            }
+        }
+
+        let mut text = String::new();
+        let keys = self.stipulation != Stipulation::Helpmate;
+        write(&mut self.board.clone(), &self.keys, 0, keys, &mut text);
+        text
+    }
+}
+
+/// Exhaustive solver of mate, selfmate and helpmate problems. Positions
+/// already decided are remembered by key and moves left, so transpositions
+/// are solved once.
+pub struct Solver {
+    stipulation: Stipulation,
+    known: HashMap<(u64, u32), bool>,
+    nodes: u64,
+}
+
+impl Solver {
+    pub fn new(stipulation: Stipulation) -> Self {
+        Self {
+            stipulation,
+            known: HashMap::new(),
+            nodes: 0,
+        }
+    }
+
+    /// Positions visited so far.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Finds every key meeting the stipulation in at most `moves` moves of
+    /// the side to move, and the full tree of play after them.
+    pub fn solve(&mut self, board: &Board, moves: u32) -> Solution {
+        let mut position = board.clone();
+        let keys = if moves == 0 { Vec::new() } else { self.solver_tree(&mut position, moves) };
+        Solution {
+            board: board.clone(),
+            stipulation: self.stipulation,
+            moves,
+            keys,
+        }
+    }
+
+    /// Whether the solver, to move with `moves` left, can keep to the
+    /// stipulation.
+    fn solver_wins(&mut self, board: &mut Board, moves: u32) -> bool {
+        let key = (board.zobrist_key(), moves);
+        if let Some(&known) = self.known.get(&key) { return known; }
+
+        let wins = board.legal_moves().into_iter().any(|mv| self.solver_move_works(board, mv, moves));
+        self.known.insert(key, wins);
+        wins
+    }
+
+    fn solver_move_works(&mut self, board: &mut Board, mv: Move, moves: u32) -> bool {
+        self.nodes += 1;
+        let undo = board.make(mv);
+        let works = self.replies_work(board, moves - 1);
+        board.unmake(mv, undo);
+        works
+    }
+
+    /// Whether the opponent, to move after a solver move leaving `moves`,
+    /// keeps to the stipulation: with every reply, or with some reply in a
+    /// helpmate.
+    fn replies_work(&mut self, board: &mut Board, moves: u32) -> bool {
+        let replies = board.legal_moves();
+        match self.stipulation {
+            Stipulation::Mate if replies.is_empty() => board.in_check(),
+            Stipulation::Mate if moves == 0 => false,
+            Stipulation::Mate | Stipulation::Selfmate => {
+                !replies.is_empty() && replies.into_iter().all(|reply| self.reply_works(board, reply, moves))
+            },
+            Stipulation::Helpmate => replies.into_iter().any(|reply| self.reply_works(board, reply, moves)),
+        }
+    }
+
+    fn reply_works(&mut self, board: &mut Board, reply: Move, moves: u32) -> bool {
+        self.nodes += 1;
+        let undo = board.make(reply);
+        let works = match self.stipulation {
+            Stipulation::Mate => self.solver_wins(board, moves),
+            Stipulation::Selfmate | Stipulation::Helpmate => {
+                board.is_checkmate() || (moves > 0 && self.solver_wins(board, moves))
+            },
+        };
+        board.unmake(reply, undo);
+        works
+    }
+
+    fn solver_tree(&mut self, board: &mut Board, moves: u32) -> Vec<SolutionNode> {
+        let mut nodes = Vec::new();
+        for mv in board.legal_moves() {
+            if !self.solver_move_works(board, mv, moves) { continue; }
+
+            let undo = board.make(mv);
+            let children = self.reply_tree(board, moves - 1);
+            board.unmake(mv, undo);
+            nodes.push(SolutionNode { mv, children });
+        }
+        nodes
+    }
+
+    fn reply_tree(&mut self, board: &mut Board, moves: u32) -> Vec<SolutionNode> {
+        let mut nodes = Vec::new();
+        for reply in board.legal_moves() {
+            if self.stipulation == Stipulation::Helpmate && !self.reply_works(board, reply, moves) { continue; }
+
+            let undo = board.make(reply);
+            let children = if moves == 0 || board.is_checkmate() { Vec::new() } else { self.solver_tree(board, moves) };
+            board.unmake(reply, undo);
+            nodes.push(SolutionNode { mv: reply, children });
+        }
+        nodes
+    }
+}
+
+/// Solves `board` for the stipulation in `moves` moves.
+pub fn solve(board: &Board, stipulation: Stipulation, moves: u32) -> Solution {
+    Solver::new(stipulation).solve(board, moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn moves(uci: &[&str]) -> Vec<Move> {
+        uci.iter().map(|mv| Move::from_uci(mv).unwrap()).collect()
+    }
+
+    #[test]
+    fn finds_a_unique_mate_in_one() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let solution = solve(&board, Stipulation::Mate, 1);
+        assert_eq!(solution.key_moves(), moves(&["a1a8"]));
+        assert!(!solution.is_cooked());
+        assert_eq!(solution.to_text(), "1. Ra8#!\n");
+    }
+
+    #[test]
+    fn finds_every_key_of_a_cooked_problem() {
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/7Q w - - 0 1").unwrap();
+        let solution = solve(&board, Stipulation::Mate, 1);
+        assert_eq!(solution.key_moves(), moves(&["h1b7", "h1h8"]));
+        assert!(solution.is_cooked());
+    }
+
+    #[test]
+    fn mate_in_two_answers_every_defence() {
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let solution = solve(&board, Stipulation::Mate, 2);
+        assert!(solution.is_solved());
+        for line in solution.lines() {
+            let mut position = board.clone();
+            for &mv in &line {
+                position.apply_move(mv).unwrap();
+            }
+            assert!(position.is_checkmate(), "{line:?}");
+            assert!(line.len() <= 3);
+        }
+        assert!(solution.to_text().contains("1. Kb6!\n  1... Kb8\n    2. Rh8#\n"));
+    }
+
+    #[test]
+    fn no_mate_in_too_few_moves() {
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        assert!(!solve(&board, Stipulation::Mate, 1).is_solved());
+    }
+
+    #[test]
+    fn selfmate_forces_the_opponent_to_mate() {
+        // any rook move along the fourth rank leaves black only g2 mate
+        let board = Board::from_fen("8/8/8/8/R7/6pk/8/6BK w - - 0 1").unwrap();
+        let solution = solve(&board, Stipulation::Selfmate, 1);
+        assert_eq!(solution.key_moves(), moves(&["a4b4", "a4c4", "a4d4", "a4e4", "a4f4"]));
+        assert!(solution.is_cooked());
+        assert!(solution.to_text().starts_with("1. Rb4!\n  1... g2#\n"));
+    }
+
+    #[test]
+    fn helpmate_lists_the_cooperative_line() {
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/7R b - - 0 1").unwrap();
+        let solution = solve(&board, Stipulation::Helpmate, 1);
+        assert_eq!(solution.lines(), vec![moves(&["a8b8", "h1h8"])]);
+        assert!(!solution.is_cooked());
+        assert_eq!(solution.to_text(), "1... Kb8\n  2. Rh8#\n");
+    }
+}
@@ -1,72 +1,86 @@
-use std::io::{stdout, Write};
+pub mod alphabeta;
+pub mod greedy;
+pub mod mcts;
+pub mod personality;
+pub mod random;
+pub mod scripted;
+pub mod skill;
+
+use std::io::{stdout, BufRead, Write};
 use crate::board::*;
+use crate::notation::*;
 
-pub struct Move {
-    pub from: usize,
-    pub to: usize,
-}
+pub use alphabeta::*;
+pub use greedy::*;
+pub use mcts::*;
+pub use personality::*;
+pub use random::*;
+pub use scripted::*;
+pub use skill::*;
+pub use crate::search::{SearchLimits, SearchOptions};
 
 pub trait Strategy {
     fn next_move(&self, board: &Board) -> Move;
+
+    /// Like `next_move` but with an error when the strategy has no move to
+    /// give, such as a script which has run out.
+    fn try_next_move(&self, board: &Board) -> Result<Move, String> {
+        Ok(self.next_move(board))
+    }
+
+    /// Like `next_move` but within the given limits. Strategies which do not
+    /// think, such as a human at the console, can ignore them.
+    fn next_move_with_limits(&self, board: &Board, _limits: &SearchLimits) -> Move {
+        self.next_move(board)
+    }
+
+    /// Tells a strategy the positions of the game up to the one of its next
+    /// move, so that it can tell which moves repeat them.
+    fn set_history(&self, _history: &GameHistory) {}
+
+    /// Asks a strategy thinking on another thread to play the best move it
+    /// has found so far.
+    fn stop(&self) {}
+
+    /// Starts thinking on the opponent's time, in the position after this
+    /// strategy's move with the opponent to move, about the reply it
+    /// expects. Returns that reply, or none when the strategy does not
+    /// ponder.
+    fn start_pondering(&self, _board: &Board) -> Option<Move> {
+        None
+    }
+
+    /// The opponent played the expected reply: the next `next_move`
+    /// continues the search started when pondering.
+    fn ponder_hit(&self) {}
+
+    /// The opponent played another move, or the game ended: the search
+    /// started when pondering is thrown away.
+    fn ponder_miss(&self) {}
 }
 
-pub struct HumanStrategy {}
+/// Reads moves typed at the console, as coordinates (`e2e4`, `e2-e4`), in
+/// algebraic notation with the configured piece letters (`Sf3` in German) or
+/// in English descriptive notation (`N-KB3`).
+pub struct HumanStrategy {
+    pub notation: PieceStyle,
+}
 
 impl HumanStrategy {
-    fn parse_move(&self, user_input: &str) -> Result<Move, String> {
-        const MOVE_LEN: usize = 4;
-        let valid_pos = match user_input {
-            _ if user_input.len() < MOVE_LEN => return Err(format!("Invalid input {user_input}")),
-            validated => validated,
-        };
-
-        // get from indices
-        let from_col = valid_pos.chars().nth(0).unwrap();
-        let from_col_idx: usize = from_col as usize;
-        let from_col_idx = match from_col_idx {
-            _ if from_col_idx >= 'a' as usize && from_col_idx <= 'h' as usize =>
-                from_col_idx - 'a' as usize,
-            _ if from_col_idx >= 'A' as usize && from_col_idx <= 'H' as usize =>
-                from_col_idx - 'A' as usize,
-            _ => return Err(format!("Invalid from column index {from_col}")),
-        };
-
-        let from_row = valid_pos.chars().nth(1).unwrap();
-        let from_row_idx: usize = from_row as usize;
-        let from_row_idx = match from_row_idx {
-            _ if from_row_idx >= '1' as usize && from_row_idx <= '8' as usize =>
-                from_row_idx - '0' as usize,
-            _ => return Err(format!("Invalid from row index {from_row}")),
-        };
-        
-
-        // get to indices
-        let to_col = valid_pos.chars().nth(2).unwrap();
-        let to_col_idx: usize = to_col as usize;
-        let to_col_idx = match to_col_idx {
-            _ if to_col_idx >= 'a' as usize && to_col_idx <= 'h' as usize =>
-                to_col_idx - 'a' as usize,
-            _ if to_col_idx >= 'A' as usize && to_col_idx <= 'H' as usize =>
-                to_col_idx - 'A' as usize,
-            _ => return Err(format!("Invalid to column index {to_col}")),
-        };
-
-        let to_row = valid_pos.chars().nth(3).unwrap();
-        let to_row_idx: usize = to_row as usize;
-        let to_row_idx = match to_row_idx {
-            _ if to_row_idx >= '1' as usize && to_row_idx <= '8' as usize =>
-                to_row_idx - '0' as usize,
-            _ => return Err(format!("Invalid to row index {to_row}")),
-        };
-
-        return Ok(Move {from: (8-from_row_idx)*8+from_col_idx, to: (8-to_row_idx)*8+to_col_idx});
+    pub fn new(notation: PieceStyle) -> Self {
+        Self { notation }
     }
-}
 
-impl Strategy for HumanStrategy {
-    fn next_move(&self, board: &Board) -> Move {
-        println!("{}", board);
+    /// Parses the typed move, or explains which moves were probably meant.
+    pub fn read_move(&self, board: &Board, user_input: &str) -> Result<Move, String> {
+        parse_user_move(board, user_input, &self.notation)
+    }
+}
 
+impl HumanStrategy {
+    /// Asks for moves on `input` until one is valid, or fails when the
+    /// input ends.
+    fn read_next_move(&self, board: &Board, input: &mut impl BufRead) -> Result<Move, String> {
         let mut user_input = String::new();
         loop {
             print!("Type here:");
@@ -74,17 +88,55 @@ impl Strategy for HumanStrategy {
                 panic!("Unable to flush the standard output. Error {:?}", e);
             }
 
-            if let Err(e) = std::io::stdin().read_line(&mut user_input) {
-                panic!("Unable to read input. Error {:?}", e);
+            user_input.clear();
+            match input.read_line(&mut user_input) {
+                Ok(0) => return Err(String::from("Standard input was closed")),
+                Ok(_) => {},
+                Err(e) => return Err(format!("Unable to read input. Error {e}")),
             }
 
-            match self.parse_move(&user_input.as_str()) {
-                Ok(m) => return m,
+            match self.read_move(board, user_input.as_str()) {
+                Ok(m) => return Ok(m),
                 Err(e) => {
-                    println!("{:?}. Please enter valid move.", e);
+                    println!("{}", e);
                     continue
                 },
             };
         }
     }
 }
+
+impl Strategy for HumanStrategy {
+    /// Leaves the program when the input ends, as the player has gone.
+    fn next_move(&self, board: &Board) -> Move {
+        self.try_next_move(board).unwrap_or_else(|e| {
+            println!("{e}");
+            std::process::exit(0)
+        })
+    }
+
+    /// The typed move, or an error when the input ends, e.g. on Ctrl-D.
+    fn try_next_move(&self, board: &Board) -> Result<Move, String> {
+        println!("{}", board);
+        self.read_next_move(board, &mut std::io::stdin().lock())
+    }
+}
+
+impl Default for HumanStrategy {
+    fn default() -> Self {
+        Self::new(PieceStyle::ENGLISH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn people_are_asked_again_until_the_input_ends() {
+        let human = HumanStrategy::default();
+        let board = Board::new();
+        assert_eq!(human.read_next_move(&board, &mut "e2e5\ne2-e4\n".as_bytes()), Ok(Move::from_uci("e2e4").unwrap()));
+        assert_eq!(human.read_next_move(&board, &mut "e2e5\n".as_bytes()), Err(String::from("Standard input was closed")));
+    }
+}
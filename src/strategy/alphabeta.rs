@@ -0,0 +1,391 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::board::*;
+use crate::eval::*;
+use crate::rng::*;
+use crate::search::*;
+use crate::syzygy::*;
+
+use super::*;
+
+/// Computer player searching with iterative deepening alpha-beta until its
+/// limits are reached or it is stopped. Its transposition table is kept from
+/// one move to the next and shared by all its search threads. With a
+/// tablebase it plays perfectly once the position is in it. Below the
+/// full skill level it plays weaker, see `Skill`. At full strength it can
+/// ponder: search the reply it expects on another thread while the
+/// opponent thinks.
+pub struct AlphaBetaStrategy {
+    pub limits: SearchLimits,
+    pub threads: usize,
+    pub skill: Skill,
+    pub options: SearchOptions,
+    table: Arc<TranspositionTable>,
+    evaluator: Arc<dyn Evaluator>,
+    tablebase: Option<Arc<Tablebase>>,
+    stop: Arc<AtomicBool>,
+    // keys of the game's positions, see `Search::set_history`
+    history: Mutex<Vec<u64>>,
+    // chooses the mistakes of a weakened player
+    rng: Mutex<Rng>,
+    // position after the last move played, with the reply the search expected
+    expected_reply: Mutex<Option<(u64, Move)>>,
+    ponder: Mutex<Option<Ponder>>,
+}
+
+/// A search running while the opponent thinks.
+struct Ponder {
+    // position after the expected reply
+    board: Board,
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    // when the opponent played the expected reply
+    hit: Option<Instant>,
+    thread: JoinHandle<SearchResult>,
+}
+
+impl Ponder {
+    fn finish(self) -> SearchResult {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join().expect("Ponder thread panicked")
+    }
+}
+
+impl AlphaBetaStrategy {
+    pub const DEFAULT_DEPTH: u32 = 4;
+
+    pub fn new(depth: u32) -> Self {
+        Self::with_limits(SearchLimits::depth(depth))
+    }
+
+    pub fn with_limits(limits: SearchLimits) -> Self {
+        Self {
+            limits,
+            threads: 1,
+            skill: Skill::default(),
+            options: SearchOptions::default(),
+            table: Arc::new(TranspositionTable::default()),
+            evaluator: Arc::new(ClassicalEvaluator::default()),
+            tablebase: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            history: Mutex::new(Vec::new()),
+            rng: Mutex::new(Rng::from_time()),
+            expected_reply: Mutex::new(None),
+            ponder: Mutex::new(None),
+        }
+    }
+
+    /// Replaces the transposition table by an empty one of `size_mb`.
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.table = Arc::new(TranspositionTable::new(size_mb));
+    }
+
+    pub fn table(&self) -> &Arc<TranspositionTable> {
+        &self.table
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+    }
+
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    /// Reseeds the choice of moves below the full skill level.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Mutex::new(Rng::new(seed));
+    }
+
+    /// Forgets earlier searches, e.g. before a new game.
+    pub fn clear_hash(&self) {
+        self.table.clear();
+    }
+
+    pub fn search(&self, board: &Board) -> SearchResult {
+        self.search_with_limits(board, &self.limits)
+    }
+
+    pub fn search_with_limits(&self, board: &Board, limits: &SearchLimits) -> SearchResult {
+        self.stop.store(false, Ordering::Relaxed);
+        parallel_search(board, limits, self.threads, &self.stop, |stop| self.new_search(stop), |_| {})
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.ponder.lock().unwrap().is_some()
+    }
+
+    /// The result of the pondering search if it was about `board` and the
+    /// opponent played the expected reply, after letting it run on for the
+    /// time `limits` give from the moment of the reply. Any other pondering
+    /// search is stopped and thrown away.
+    fn ponder_result(&self, board: &Board, limits: &SearchLimits) -> Option<SearchResult> {
+        let ponder = self.ponder.lock().unwrap().take()?;
+        let hit = match ponder.hit {
+            Some(hit) if ponder.board.zobrist_key() == board.zobrist_key() => hit,
+            _ => {
+                ponder.finish();
+                return None;
+            },
+        };
+
+        // a search without limits of its own only ends when stopped
+        let deadline = TimeManager::new(limits, board).soft_limit().map(|soft| hit + soft)
+            .or(Some(Instant::now()).filter(|_| ponder.limits.is_infinite()));
+        while !ponder.thread.is_finished() && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        Some(ponder.finish()).filter(|result| result.best_move.is_some())
+    }
+
+    /// The reply expected by the last search if `board` is the position it
+    /// led to, else the best move in the table.
+    fn expected_reply(&self, board: &Board) -> Option<Move> {
+        let key = board.zobrist_key();
+        let expected = self.expected_reply.lock().unwrap().take()
+            .filter(|&(expected_key, _)| expected_key == key)
+            .map(|(_, mv)| mv);
+        expected.or_else(|| self.table.probe(key, 0).and_then(|entry| entry.best_move))
+            .filter(|&mv| board.is_legal(mv))
+    }
+
+    /// The best `multi_pv` lines of the position, see `Search::analyze`.
+    /// The analysis runs on one thread at full strength.
+    pub fn analyze(&self, board: &Board, limits: &SearchLimits, multi_pv: usize, on_update: impl FnMut(&Analysis)) -> Analysis {
+        self.stop.store(false, Ordering::Relaxed);
+        self.new_search(&self.stop).analyze(board, limits, multi_pv, on_update)
+    }
+
+    fn new_search(&self, stop: &Arc<AtomicBool>) -> Search {
+        new_search(&self.table, &self.evaluator, &self.tablebase, self.options, &self.history.lock().unwrap(), stop)
+    }
+}
+
+fn new_search(table: &Arc<TranspositionTable>, evaluator: &Arc<dyn Evaluator>, tablebase: &Option<Arc<Tablebase>>, options: SearchOptions, history: &[u64], stop: &Arc<AtomicBool>) -> Search {
+    let mut search = Search::with_table(table.clone(), stop.clone());
+    search.set_evaluator(evaluator.clone());
+    search.set_tablebase(tablebase.clone());
+    search.set_options(options);
+    search.set_history(history);
+    search
+}
+
+impl Default for AlphaBetaStrategy {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_DEPTH)
+    }
+}
+
+impl Strategy for AlphaBetaStrategy {
+    fn next_move(&self, board: &Board) -> Move {
+        self.next_move_with_limits(board, &self.limits)
+    }
+
+    fn next_move_with_limits(&self, board: &Board, limits: &SearchLimits) -> Move {
+        let best_move = if self.skill.is_full_strength() {
+            let result = match self.ponder_result(board, limits) {
+                Some(result) => result,
+                None => self.search_with_limits(board, limits),
+            };
+            if let [mv, reply, ..] = result.pv[..] {
+                let mut next = board.clone();
+                next.make(mv);
+                *self.expected_reply.lock().unwrap() = Some((next.zobrist_key(), reply));
+            }
+            result.best_move
+        } else {
+            self.stop.store(false, Ordering::Relaxed);
+            let scored = self.new_search(&self.stop).score_root_moves(board, &self.skill.limit(limits));
+            self.skill.pick(&scored, &mut self.rng.lock().unwrap())
+        };
+        match best_move {
+            Some(mv) => mv,
+            None => panic!("No legal moves in {}", board.to_fen()),
+        }
+    }
+
+    fn set_history(&self, history: &GameHistory) {
+        *self.history.lock().unwrap() = history.keys().to_vec();
+    }
+
+    fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Searches the position after the expected reply with the depth and
+    /// node limits but no time limit, until the opponent has moved.
+    fn start_pondering(&self, board: &Board) -> Option<Move> {
+        self.ponder_miss();
+        if !self.skill.is_full_strength() { return None; }
+
+        let reply = self.expected_reply(board)?;
+        let mut ponder_board = board.clone();
+        ponder_board.make(reply);
+        if ponder_board.legal_moves().is_empty() { return None; }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits { movetime: None, clock: None, ..self.limits };
+        let (table, evaluator, tablebase) = (self.table.clone(), self.evaluator.clone(), self.tablebase.clone());
+        let (thread_board, thread_stop, threads, options) = (ponder_board.clone(), stop.clone(), self.threads, self.options);
+        // the game as it is when the search starts, after the expected reply
+        let mut history = self.history.lock().unwrap().clone();
+        if history.last() != Some(&board.zobrist_key()) { history.push(board.zobrist_key()); }
+        let thread = thread::spawn(move || {
+            parallel_search(&thread_board, &limits, threads, &thread_stop,
+                |stop| new_search(&table, &evaluator, &tablebase, options, &history, stop), |_| {})
+        });
+
+        *self.ponder.lock().unwrap() = Some(Ponder { board: ponder_board, limits, stop, hit: None, thread });
+        Some(reply)
+    }
+
+    fn ponder_hit(&self) {
+        if let Some(ponder) = self.ponder.lock().unwrap().as_mut() {
+            ponder.hit = Some(Instant::now());
+        }
+    }
+
+    fn ponder_miss(&self) {
+        let ponder = self.ponder.lock().unwrap().take();
+        if let Some(ponder) = ponder {
+            ponder.finish();
+        }
+    }
+}
+
+impl Drop for AlphaBetaStrategy {
+    fn drop(&mut self) {
+        self.ponder_miss();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn plays_the_mating_move() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        assert_eq!(AlphaBetaStrategy::new(1).next_move(&board), Move::from_uci("a1a8").unwrap());
+    }
+
+    #[test]
+    fn plays_a_legal_move_in_the_start_position() {
+        let board = Board::new();
+        assert!(board.is_legal(AlphaBetaStrategy::new(3).next_move(&board)));
+    }
+
+    #[test]
+    fn weak_levels_play_legal_moves_quickly() {
+        let mut strategy = AlphaBetaStrategy::new(8);
+        strategy.skill = Skill::new(0);
+        strategy.set_seed(3);
+        let mut board = Board::new();
+        for _ in 0..10 {
+            let mv = strategy.next_move(&board);
+            assert!(board.is_legal(mv));
+            board.apply_move(mv).unwrap();
+        }
+    }
+
+    #[test]
+    fn weak_levels_still_take_a_free_queen() {
+        let board = Board::from_fen("4k3/8/8/2q5/3P4/8/8/7K w - - 0 1").unwrap();
+        let mut strategy = AlphaBetaStrategy::new(4);
+        strategy.skill = Skill::new(0);
+        assert_eq!(strategy.next_move(&board), Move::from_uci("d4c5").unwrap());
+    }
+
+    #[test]
+    fn ponder_hit_continues_the_search() {
+        let strategy = AlphaBetaStrategy::new(3);
+        let mut board = Board::new();
+        board.apply_move(strategy.next_move(&board)).unwrap();
+
+        let reply = strategy.start_pondering(&board).unwrap();
+        assert!(strategy.is_pondering());
+        board.apply_move(reply).unwrap();
+        strategy.ponder_hit();
+        assert!(board.is_legal(strategy.next_move(&board)));
+        assert!(!strategy.is_pondering());
+    }
+
+    #[test]
+    fn ponder_miss_discards_the_search() {
+        let strategy = AlphaBetaStrategy::with_limits(SearchLimits::movetime(Duration::from_millis(50)));
+        let mut board = Board::new();
+        board.apply_move(strategy.next_move(&board)).unwrap();
+
+        let reply = strategy.start_pondering(&board).unwrap();
+        let other = board.legal_moves().into_iter().find(|&mv| mv != reply).unwrap();
+        board.apply_move(other).unwrap();
+        strategy.ponder_miss();
+        assert!(!strategy.is_pondering());
+        assert!(board.is_legal(strategy.next_move(&board)));
+    }
+
+    #[test]
+    fn weak_levels_do_not_ponder() {
+        let mut strategy = AlphaBetaStrategy::new(2);
+        strategy.skill = Skill::new(3);
+        let mut board = Board::new();
+        board.apply_move(Move::from_uci("e2e4").unwrap()).unwrap();
+        assert_eq!(strategy.start_pondering(&board), None);
+    }
+
+    #[test]
+    fn hash_size_is_configurable() {
+        let mut strategy = AlphaBetaStrategy::new(3);
+        strategy.set_hash_size(2);
+        assert_eq!(strategy.table().size_mb(), 2);
+
+        let key = Board::new().zobrist_key();
+        strategy.search(&Board::new());
+        assert!(strategy.table().probe(key, 0).is_some());
+        strategy.clear_hash();
+        assert!(strategy.table().probe(key, 0).is_none());
+    }
+
+    #[test]
+    fn searches_with_several_threads() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let mut strategy = AlphaBetaStrategy::new(3);
+        strategy.threads = 4;
+        assert_eq!(strategy.next_move(&board), Move::from_uci("a1a8").unwrap());
+    }
+
+    #[test]
+    fn plain_alpha_beta_finds_the_same_mate() {
+        let board = Board::from_fen("8/7k/4R3/8/8/K7/2R5/8 w - - 0 1").unwrap();
+        let mut strategy = AlphaBetaStrategy::new(4);
+        strategy.options = SearchOptions::none();
+        assert_eq!(strategy.next_move(&board), Move::from_uci("c2c7").unwrap());
+    }
+
+    #[test]
+    fn respects_the_movetime() {
+        let board = Board::new();
+        let strategy = AlphaBetaStrategy::default();
+        let start = Instant::now();
+        let mv = strategy.next_move_with_limits(&board, &SearchLimits::movetime(Duration::from_millis(100)));
+        assert!(start.elapsed() < Duration::from_millis(600));
+        assert!(board.is_legal(mv));
+    }
+
+    #[test]
+    fn can_be_stopped_from_another_thread() {
+        let board = Board::new();
+        let strategy = AlphaBetaStrategy::with_limits(SearchLimits::default());
+        let mv = std::thread::scope(|scope| {
+            let thinking = scope.spawn(|| strategy.next_move(&board));
+            std::thread::sleep(Duration::from_millis(50));
+            strategy.stop();
+            thinking.join().unwrap()
+        });
+        assert!(board.is_legal(mv));
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+
+use crate::board::*;
+use crate::rng::*;
+use crate::search::*;
+
+use super::*;
+
+/// Plays the move winning the most material right away, mating when it can,
+/// without looking at the reply. Equally good moves are chosen at random.
+pub struct GreedyStrategy {
+    rng: Mutex<Rng>,
+}
+
+impl GreedyStrategy {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Mutex::new(Rng::new(seed)) }
+    }
+
+    /// Material gained by the move for the side playing it, `MATE` for a
+    /// checkmate.
+    pub fn gain(board: &Board, mv: Move) -> i32 {
+        let before = material(board);
+        let mut child = board.clone();
+        child.make(mv);
+        if child.is_checkmate() { return MATE; }
+        -material(&child) - before
+    }
+}
+
+impl Strategy for GreedyStrategy {
+    fn next_move(&self, board: &Board) -> Move {
+        let scored: Vec<(Move, i32)> = board.legal_moves().into_iter()
+            .map(|mv| (mv, Self::gain(board, mv)))
+            .collect();
+        let best = scored.iter().map(|&(_, gain)| gain).max()
+            .unwrap_or_else(|| panic!("No legal moves in {}", board.to_fen()));
+
+        let moves: Vec<Move> = scored.into_iter().filter(|&(_, gain)| gain == best).map(|(mv, _)| mv).collect();
+        moves[self.rng.lock().unwrap().below(moves.len() as u64) as usize]
+    }
+}
+
+impl Default for GreedyStrategy {
+    fn default() -> Self {
+        Self { rng: Mutex::new(Rng::from_time()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_biggest_piece() {
+        let board = Board::from_fen("4k3/8/8/2q1r3/3P4/8/8/7K w - - 0 1").unwrap();
+        assert_eq!(GreedyStrategy::new(1).next_move(&board), Move::from_uci("d4c5").unwrap());
+    }
+
+    #[test]
+    fn prefers_mate_to_material() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/4n3/5PPP/R5K1 w - - 0 1").unwrap();
+        assert_eq!(GreedyStrategy::gain(&board, Move::from_uci("a1a8").unwrap()), MATE);
+        assert_eq!(GreedyStrategy::new(1).next_move(&board), Move::from_uci("a1a8").unwrap());
+    }
+
+    #[test]
+    fn counts_promotions() {
+        let board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(GreedyStrategy::new(1).next_move(&board), Move::from_uci("a7a8q").unwrap());
+    }
+}
@@ -0,0 +1,417 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::arena::expected_score;
+use crate::board::*;
+use crate::eval::*;
+use crate::rng::*;
+use crate::search::*;
+
+use super::*;
+
+/// How a node chooses which child to explore next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    /// UCB1: every move is tried once, then the average result plus
+    /// `exploration * sqrt(ln N / n)`.
+    Uct,
+    /// As in AlphaZero: the average result plus
+    /// `exploration * prior * sqrt(N) / (1 + n)`, with priors from the
+    /// evaluation of the moves, so good looking moves are tried first.
+    Puct,
+}
+
+impl Selection {
+    pub fn default_exploration(&self) -> f64 {
+        match self {
+            Selection::Uct => std::f64::consts::SQRT_2,
+            Selection::Puct => 1.5,
+        }
+    }
+}
+
+/// How a newly reached position is valued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeafValue {
+    /// The static evaluation, as a winning probability.
+    Evaluation,
+    /// A game of random moves, valued by its result, or by the evaluation
+    /// where it is cut off after `max_plies`.
+    Rollout { max_plies: usize },
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    // the move leading here, none at the root
+    mv: Option<Move>,
+    children: Vec<usize>,
+    expanded: bool,
+    visits: u32,
+    // sum of the results for the player who made `mv`, from 0 to 1
+    value: f64,
+    prior: f64,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, prior: f64) -> Self {
+        Self { mv, children: Vec::new(), expanded: false, visits: 0, value: 0.0, prior }
+    }
+
+    fn mean(&self) -> f64 {
+        self.value / self.visits as f64
+    }
+}
+
+/// Search tree with the root at index 0.
+struct Tree {
+    board: Board,
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    fn new(board: &Board) -> Self {
+        Self { board: board.clone(), nodes: vec![Node::new(None, 1.0)] }
+    }
+
+    /// The subtree of the position reached by at most two moves from the
+    /// root, with its statistics, if it has been explored.
+    fn reroot(self, board: &Board) -> Option<Tree> {
+        let key = board.zobrist_key();
+        if self.board.zobrist_key() == key { return Some(self); }
+
+        let mut found = None;
+        'search: for &child in &self.nodes[0].children {
+            let mut child_board = self.board.clone();
+            child_board.make(self.nodes[child].mv?);
+            if child_board.zobrist_key() == key {
+                found = Some(child);
+                break;
+            }
+            for &grandchild in &self.nodes[child].children {
+                let mut grandchild_board = child_board.clone();
+                grandchild_board.make(self.nodes[grandchild].mv?);
+                if grandchild_board.zobrist_key() == key {
+                    found = Some(grandchild);
+                    break 'search;
+                }
+            }
+        }
+
+        let root = found?;
+        let mut nodes = Vec::new();
+        // old index of each node to copy and the new index of its parent
+        let mut queue: Vec<(usize, Option<usize>)> = vec![(root, None)];
+        while let Some((old, parent)) = queue.pop() {
+            let index = nodes.len();
+            let mut node = self.nodes[old].clone();
+            node.children.clear();
+            nodes.push(node);
+            if let Some(parent) = parent {
+                nodes[parent].children.push(index);
+            }
+            queue.extend(self.nodes[old].children.iter().rev().map(|&child| (child, Some(index))));
+        }
+        Some(Tree { board: board.clone(), nodes })
+    }
+}
+
+/// What a Monte Carlo tree search found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MctsResult {
+    /// The most visited move.
+    pub best_move: Option<Move>,
+    /// Expected result of the best move for the side to move, from 0 for a
+    /// loss to 1 for a win.
+    pub win_probability: f64,
+    pub iterations: u64,
+    /// Visits of the root kept from the search of an earlier move.
+    pub reused: u32,
+}
+
+/// Computer player growing a Monte Carlo search tree: each iteration walks
+/// down the tree choosing children by UCT or PUCT, adds the children of the
+/// position it reaches, values that position by its evaluation or by a
+/// random game and updates the averages on the way back. The most visited
+/// move is played. The tree is kept for the next move, so the part below the
+/// moves actually played is not searched again.
+///
+/// The search stops after `limits.nodes` iterations or when the time given
+/// by `limits` runs out. Depth limits are ignored.
+pub struct MctsStrategy {
+    pub limits: SearchLimits,
+    pub selection: Selection,
+    pub exploration: f64,
+    pub leaf_value: LeafValue,
+    pub reuse_tree: bool,
+    evaluator: Arc<dyn Evaluator>,
+    tree: Mutex<Option<Tree>>,
+    rng: Mutex<Rng>,
+    stop: AtomicBool,
+}
+
+impl MctsStrategy {
+    pub const DEFAULT_ITERATIONS: u64 = 5000;
+    // centipawns by which a move must look better to be twice as likely
+    // to be tried first, roughly
+    const PRIOR_TEMPERATURE: f64 = 150.0;
+
+    pub fn new(selection: Selection) -> Self {
+        Self {
+            limits: SearchLimits::nodes(Self::DEFAULT_ITERATIONS),
+            selection,
+            exploration: selection.default_exploration(),
+            leaf_value: LeafValue::Evaluation,
+            reuse_tree: true,
+            evaluator: Arc::new(ClassicalEvaluator::default()),
+            tree: Mutex::new(None),
+            rng: Mutex::new(Rng::from_time()),
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+    }
+
+    /// Reseeds the random games of rollouts.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Mutex::new(Rng::new(seed));
+    }
+
+    /// Forgets the tree of the previous move.
+    pub fn clear_tree(&self) {
+        *self.tree.lock().unwrap() = None;
+    }
+
+    pub fn search(&self, board: &Board) -> MctsResult {
+        self.search_with_limits(board, &self.limits)
+    }
+
+    pub fn search_with_limits(&self, board: &Board, limits: &SearchLimits) -> MctsResult {
+        self.stop.store(false, Ordering::Relaxed);
+        let mut saved = self.tree.lock().unwrap();
+        let mut tree = saved.take()
+            .filter(|_| self.reuse_tree)
+            .and_then(|tree| tree.reroot(board))
+            .unwrap_or_else(|| Tree::new(board));
+        let reused = tree.nodes[0].visits;
+
+        let time = TimeManager::new(limits, board);
+        let max_iterations = limits.nodes
+            .or((limits.movetime.is_none() && limits.clock.is_none()).then_some(Self::DEFAULT_ITERATIONS));
+        let mut rng = self.rng.lock().unwrap();
+        let mut iterations = 0;
+        while max_iterations.is_none_or(|max| iterations < max) {
+            // an iteration takes a tiny part of the time, so all of it is used
+            let out_of_time = time.soft_limit().is_some_and(|soft| time.elapsed() >= soft);
+            if self.stop.load(Ordering::Relaxed) || out_of_time { break; }
+            self.iterate(&mut tree, &mut rng);
+            iterations += 1;
+            // nothing to think about with a single move on the clock
+            if limits.clock.is_some() && tree.nodes[0].children.len() == 1 { break; }
+        }
+
+        let best = tree.nodes[0].children.iter()
+            .max_by_key(|&&child| tree.nodes[child].visits)
+            .map(|&child| &tree.nodes[child]);
+        let result = MctsResult {
+            best_move: best.and_then(|node| node.mv),
+            win_probability: best.filter(|node| node.visits > 0).map_or(0.5, |node| node.mean()),
+            iterations,
+            reused,
+        };
+        *saved = Some(tree);
+        result
+    }
+
+    fn iterate(&self, tree: &mut Tree, rng: &mut Rng) {
+        let mut board = tree.board.clone();
+        let mut path = vec![0];
+        let mut node = 0;
+        while tree.nodes[node].expanded && !tree.nodes[node].children.is_empty() {
+            node = self.select(tree, node);
+            board.make(tree.nodes[node].mv.expect("Child without a move"));
+            path.push(node);
+        }
+
+        // result for the side to move in the leaf
+        let mut value = match terminal_value(&board) {
+            Some(value) => value,
+            None => {
+                self.expand(tree, node, &board);
+                self.leaf_value(&board, rng)
+            },
+        };
+        for &index in path.iter().rev() {
+            let node = &mut tree.nodes[index];
+            node.visits += 1;
+            node.value += 1.0 - value;
+            value = 1.0 - value;
+        }
+    }
+
+    fn select(&self, tree: &Tree, node: usize) -> usize {
+        let parent = &tree.nodes[node];
+        let score = |child: &Node| match self.selection {
+            Selection::Uct if child.visits == 0 => f64::INFINITY,
+            Selection::Uct => child.mean() + self.exploration * ((parent.visits as f64).ln() / child.visits as f64).sqrt(),
+            Selection::Puct => {
+                let mean = if child.visits == 0 { 0.5 } else { child.mean() };
+                mean + self.exploration * child.prior * (parent.visits as f64).sqrt() / (1 + child.visits) as f64
+            },
+        };
+
+        *parent.children.iter()
+            .max_by(|&&a, &&b| score(&tree.nodes[a]).total_cmp(&score(&tree.nodes[b])))
+            .expect("Selecting among no children")
+    }
+
+    fn expand(&self, tree: &mut Tree, node: usize, board: &Board) {
+        let moves = board.legal_moves();
+        let priors = match self.selection {
+            Selection::Uct => vec![1.0 / moves.len() as f64; moves.len()],
+            Selection::Puct => self.priors(board, &moves),
+        };
+
+        for (mv, prior) in moves.into_iter().zip(priors) {
+            let index = tree.nodes.len();
+            tree.nodes.push(Node::new(Some(mv), prior));
+            tree.nodes[node].children.push(index);
+        }
+        tree.nodes[node].expanded = true;
+    }
+
+    /// Softmax of the evaluations after each move.
+    fn priors(&self, board: &Board, moves: &[Move]) -> Vec<f64> {
+        let mut board = board.clone();
+        let scores: Vec<f64> = moves.iter().map(|&mv| {
+            let undo = board.make(mv);
+            let score = -self.evaluator.evaluate(&board);
+            board.unmake(mv, undo);
+            score as f64
+        }).collect();
+
+        let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = scores.iter().map(|score| ((score - max) / Self::PRIOR_TEMPERATURE).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        weights.iter().map(|weight| weight / total).collect()
+    }
+
+    /// Expected result for the side to move.
+    fn leaf_value(&self, board: &Board, rng: &mut Rng) -> f64 {
+        match self.leaf_value {
+            LeafValue::Evaluation => expected_score(self.evaluator.evaluate(board) as f64),
+            LeafValue::Rollout { max_plies } => {
+                let mut board = board.clone();
+                // whether the side to move in the rollout is the leaf's
+                let mut same_side = true;
+                for _ in 0..max_plies {
+                    let moves = board.legal_moves();
+                    if moves.is_empty() || board.halfmove_clock >= 100 { break; }
+                    board.make(moves[rng.below(moves.len() as u64) as usize]);
+                    same_side = !same_side;
+                }
+
+                let value = terminal_value(&board)
+                    .unwrap_or_else(|| expected_score(self.evaluator.evaluate(&board) as f64));
+                if same_side { value } else { 1.0 - value }
+            },
+        }
+    }
+}
+
+/// The result for the side to move if the game is over.
+fn terminal_value(board: &Board) -> Option<f64> {
+    if board.legal_moves().is_empty() {
+        Some(if board.in_check() { 0.0 } else { 0.5 })
+    } else if board.halfmove_clock >= 100 {
+        Some(0.5)
+    } else {
+        None
+    }
+}
+
+impl Default for MctsStrategy {
+    fn default() -> Self {
+        Self::new(Selection::Puct)
+    }
+}
+
+impl Strategy for MctsStrategy {
+    fn next_move(&self, board: &Board) -> Move {
+        self.next_move_with_limits(board, &self.limits)
+    }
+
+    fn next_move_with_limits(&self, board: &Board, limits: &SearchLimits) -> Move {
+        match self.search_with_limits(board, limits).best_move {
+            Some(mv) => mv,
+            None => panic!("No legal moves in {}", board.to_fen()),
+        }
+    }
+
+    fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn finds_mate_in_one() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        for selection in [Selection::Uct, Selection::Puct] {
+            let strategy = MctsStrategy::new(selection);
+            let result = strategy.search_with_limits(&board, &SearchLimits::nodes(2000));
+            assert_eq!(result.best_move, Some(Move::from_uci("a1a8").unwrap()));
+            assert!(result.win_probability > 0.9);
+        }
+    }
+
+    #[test]
+    fn takes_a_free_queen_with_rollouts() {
+        let board = Board::from_fen("4k3/8/8/2q5/3P4/8/8/7K w - - 0 1").unwrap();
+        let mut strategy = MctsStrategy::new(Selection::Uct);
+        strategy.leaf_value = LeafValue::Rollout { max_plies: 8 };
+        strategy.set_seed(1);
+        assert_eq!(strategy.search_with_limits(&board, &SearchLimits::nodes(1500)).best_move, Some(Move::from_uci("d4c5").unwrap()));
+    }
+
+    #[test]
+    fn stops_after_the_iterations() {
+        let strategy = MctsStrategy::default();
+        assert_eq!(strategy.search_with_limits(&Board::new(), &SearchLimits::nodes(100)).iterations, 100);
+    }
+
+    #[test]
+    fn uses_its_time() {
+        let strategy = MctsStrategy::default();
+        let start = Instant::now();
+        let result = strategy.search_with_limits(&Board::new(), &SearchLimits::movetime(Duration::from_millis(100)));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(result.iterations > 0);
+    }
+
+    #[test]
+    fn reuses_the_tree() {
+        let strategy = MctsStrategy::default();
+        let mut board = Board::new();
+        let first = strategy.search_with_limits(&board, &SearchLimits::nodes(3000));
+        assert_eq!(first.reused, 0);
+
+        // the reply and then the next move are both in the tree
+        board.apply_move(first.best_move.unwrap()).unwrap();
+        let reply = strategy.search_with_limits(&board, &SearchLimits::nodes(1000));
+        assert!(reply.reused > 0);
+        board.apply_move(reply.best_move.unwrap()).unwrap();
+        let second = strategy.search_with_limits(&board, &SearchLimits::nodes(100));
+        assert!(second.reused > 0);
+        assert!(board.is_legal(second.best_move.unwrap()));
+
+        strategy.clear_tree();
+        assert_eq!(strategy.search_with_limits(&board, &SearchLimits::nodes(10)).reused, 0);
+    }
+}
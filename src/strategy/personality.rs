@@ -0,0 +1,247 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::board::*;
+use crate::book::*;
+use crate::eval::*;
+use crate::notation::*;
+
+use super::*;
+
+/// The profiles of `Personality::builtin`, in the format of
+/// `Personality::parse_all`.
+pub const BUILTIN_PERSONALITIES: &str = "\
+# Plays for the king: sharp gambits, active pieces, no early draws
+[attacker]
+contempt 40
+risk 40
+opening e4 e5 f4
+opening e4 e5 Nf3 Nc6 Bc4 Bc5 b4
+opening d4 d5 c4 e5
+mobility 0,0 2,3 3,5 6,5 6,5 0,0
+king_zone_attack -14,-4
+pawn_shield 6,0
+
+# Keeps a sound structure and a safe king and is happy to trade down
+[solid]
+contempt -10
+risk -30
+opening d4 d5 c4 e6 Nc3 Nf6
+opening e4 c6
+opening Nf3 d5 g3
+doubled_pawn -18,-26
+isolated_pawn -18,-22
+pawn_shield 18,0
+king_zone_attack -10,-3
+
+# Takes whatever pawns are offered and counts on them in the endgame
+[pawn-grabber]
+contempt 15
+opening d4 d5 c4 dxc4
+opening e4 e5 d4 exd4 c3 dxc3
+opening e4 d5 exd5
+material 0,0 1025,936 477,512 365,297 337,281 105,125
+passed_pawn 0,0 8,14 8,20 14,32 26,56 42,85 70,130 0,0
+";
+
+/// A character for the computer player: changes to the evaluation weights,
+/// how much it dislikes draws, how keen it is to keep the position complicated
+/// and the openings it prefers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Personality {
+    pub name: String,
+    /// Weights replacing those of the evaluation it plays with, in the
+    /// format of `EvalWeights::to_config`.
+    pub weight_overrides: String,
+    /// See `SearchOptions::contempt`.
+    pub contempt: i32,
+    /// See `SearchOptions::risk`.
+    pub risk: i32,
+    /// Lines from the start position the personality follows while the
+    /// game does, for either colour.
+    pub openings: Vec<Vec<Move>>,
+}
+
+impl Personality {
+    /// A personality playing like the plain computer player.
+    pub fn new(name: &str) -> Self {
+        Self { name: String::from(name), weight_overrides: String::new(), contempt: 0, risk: 0, openings: Vec::new() }
+    }
+
+    /// Reads personalities from sections starting with `[name]`. A section
+    /// holds `contempt n` and `risk n` lines, `opening` lines of moves in
+    /// SAN from the start position and weight overrides in the format of
+    /// `EvalWeights::to_config`. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse_all(text: &str) -> Result<Vec<Self>, String> {
+        let mut personalities: Vec<Self> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let error = |message: String| format!("Line {}: {message}", number + 1);
+            if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                let name = name.trim();
+                if name.is_empty() { return Err(error(String::from("Missing personality name"))); }
+                if personalities.iter().any(|personality| personality.name == name) {
+                    return Err(error(format!("Personality {name} is defined twice")));
+                }
+                personalities.push(Self::new(name));
+                continue;
+            }
+
+            let personality = personalities.last_mut()
+                .ok_or_else(|| error(String::from("Expected a [name] section")))?;
+            let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match key {
+                "contempt" | "risk" => {
+                    let parsed = value.trim().parse().map_err(|_| error(format!("Invalid {key} {}", value.trim())))?;
+                    if key == "contempt" { personality.contempt = parsed } else { personality.risk = parsed }
+                },
+                "opening" => {
+                    let sans: Vec<&str> = value.split_whitespace().collect();
+                    let moves = parse_san_line(&Board::new(), &sans).map_err(error)?;
+                    if moves.is_empty() { return Err(error(String::from("Empty opening"))); }
+                    personality.openings.push(moves);
+                },
+                // the line is alone, so its errors are all on line 1
+                _ => {
+                    EvalWeights::default().apply_config(line)
+                        .map_err(|e| error(e.trim_start_matches("Line 1: ").to_string()))?;
+                    personality.weight_overrides.push_str(line);
+                    personality.weight_overrides.push('\n');
+                },
+            }
+        }
+        Ok(personalities)
+    }
+
+    pub fn load_all(path: &Path) -> Result<Vec<Self>, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}. Error {e}", path.display()))?;
+        Self::parse_all(&text)
+    }
+
+    /// The attacker, the solid player and the pawn-grabber.
+    pub fn builtin() -> Vec<Self> {
+        Self::parse_all(BUILTIN_PERSONALITIES).expect("Invalid built-in personalities")
+    }
+
+    /// `base` with the personality's weight overrides.
+    pub fn weights(&self, base: &EvalWeights) -> EvalWeights {
+        let mut weights = base.clone();
+        weights.apply_config(&self.weight_overrides).expect("Overrides are checked when parsed");
+        weights
+    }
+
+    /// Gives `strategy` the evaluation of `base` with the personality's
+    /// overrides, and its contempt and risk, replacing its evaluator.
+    pub fn apply(&self, strategy: &mut AlphaBetaStrategy, base: &EvalWeights) {
+        strategy.set_evaluator(Arc::new(ClassicalEvaluator::new(self.weights(base))));
+        strategy.options.contempt = self.contempt;
+        strategy.options.risk = self.risk;
+    }
+
+    /// The preferred openings as a book, each move weighted by the number
+    /// of lines playing it.
+    pub fn book(&self) -> PolyglotBook {
+        let mut entries: Vec<BookEntry> = Vec::new();
+        for line in &self.openings {
+            let mut board = Board::new();
+            for &mv in line {
+                if !board.is_legal(mv) { break; }
+
+                let (key, raw_move) = (polyglot_key(&board), encode_move(&board, mv));
+                match entries.iter_mut().find(|entry| entry.key == key && entry.raw_move == raw_move) {
+                    Some(entry) => entry.weight += 1,
+                    None => entries.push(BookEntry { key, raw_move, weight: 1, learn: 0 }),
+                }
+                board.make(mv);
+            }
+        }
+        PolyglotBook::from_entries(entries)
+    }
+
+    /// `computer` playing with this personality on top of `base`, its
+    /// preferred openings first.
+    pub fn strategy(&self, mut computer: AlphaBetaStrategy, base: &EvalWeights) -> BookStrategy<AlphaBetaStrategy> {
+        self.apply(&mut computer, base);
+        BookStrategy::new(self.book(), computer)
+    }
+}
+
+/// The personality called `name`.
+pub fn find_personality<'a>(personalities: &'a [Personality], name: &str) -> Option<&'a Personality> {
+    personalities.iter().find(|personality| personality.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn moves(uci: &[&str]) -> Vec<Move> {
+        uci.iter().map(|mv| Move::from_uci(mv).unwrap()).collect()
+    }
+
+    #[test]
+    fn sections_override_only_what_they_name() {
+        let text = "# club opponents\n[gambiteer]\ncontempt 25\nrisk -5\nopening e4 e5 f4\nbishop_pair 45,60\n\n[plain]\n";
+        let personalities = Personality::parse_all(text).unwrap();
+        assert_eq!(personalities.len(), 2);
+
+        let gambiteer = &personalities[0];
+        assert_eq!((gambiteer.contempt, gambiteer.risk), (25, -5));
+        assert_eq!(gambiteer.openings, vec![moves(&["e2e4", "e7e5", "f2f4"])]);
+        let weights = gambiteer.weights(&EvalWeights::default());
+        assert_eq!(weights.bishop_pair, Score::new(45, 60));
+        assert_eq!(weights.pawn_shield, EvalWeights::default().pawn_shield);
+        assert_eq!(personalities[1], Personality::new("plain"));
+    }
+
+    #[test]
+    fn overrides_apply_on_top_of_the_given_weights() {
+        let personality = Personality::parse_all("[a]\nbishop_pair 45,60").unwrap().remove(0);
+        let base = EvalWeights { pawn_shield: Score::new(99, 1), bishop_pair: Score::new(1, 1), ..EvalWeights::default() };
+        let weights = personality.weights(&base);
+        assert_eq!((weights.bishop_pair, weights.pawn_shield), (Score::new(45, 60), Score::new(99, 1)));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(Personality::parse_all("risk 5").unwrap_err(), "Line 1: Expected a [name] section");
+        assert_eq!(Personality::parse_all("[a]\n\nrisk high").unwrap_err(), "Line 3: Invalid risk high");
+        assert_eq!(Personality::parse_all("[a]\nflair 1,1").unwrap_err(), "Line 2: Unknown weight flair");
+        assert_eq!(Personality::parse_all("[a]\n[a]").unwrap_err(), "Line 2: Personality a is defined twice");
+        assert!(Personality::parse_all("[a]\nopening e4 e4").unwrap_err().starts_with("Line 2: "));
+    }
+
+    #[test]
+    fn builtin_personalities_differ() {
+        let personalities = Personality::builtin();
+        let names: Vec<&str> = personalities.iter().map(|personality| personality.name.as_str()).collect();
+        assert_eq!(names, ["attacker", "solid", "pawn-grabber"]);
+
+        let attacker = find_personality(&personalities, "attacker").unwrap();
+        let solid = find_personality(&personalities, "solid").unwrap();
+        assert!(attacker.contempt > 0 && solid.contempt < 0);
+        assert!(attacker.risk > 0 && solid.risk < 0);
+        assert!(find_personality(&personalities, "coward").is_none());
+    }
+
+    #[test]
+    fn plays_its_openings_with_either_colour() {
+        let personality = Personality::parse_all("[a]\nopening e4 e5 Nf3\nopening e4 c5\nrisk 10").unwrap().remove(0);
+        let strategy = personality.strategy(AlphaBetaStrategy::new(1), &EvalWeights::default());
+        assert_eq!(strategy.strategy.options.risk, 10);
+        assert_eq!(strategy.next_move(&Board::new()), Move::from_uci("e2e4").unwrap());
+
+        let mut board = Board::new();
+        board.make(Move::from_uci("e2e4").unwrap());
+        assert!(moves(&["e7e5", "c7c5"]).contains(&strategy.next_move(&board)));
+        assert_eq!(strategy.book.moves(&board).len(), 2);
+
+        board.make(Move::from_uci("c7c5").unwrap());
+        assert_eq!(strategy.book_move(&board), None);
+    }
+}
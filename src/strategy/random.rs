@@ -0,0 +1,52 @@
+use std::sync::Mutex;
+
+use crate::board::*;
+use crate::rng::*;
+
+use super::*;
+
+/// Plays a uniformly random legal move. With a fixed seed the same game is
+/// played every time, which makes it a cheap opponent for testing.
+pub struct RandomStrategy {
+    rng: Mutex<Rng>,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Mutex::new(Rng::new(seed)) }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn next_move(&self, board: &Board) -> Move {
+        let moves = board.legal_moves();
+        if moves.is_empty() {
+            panic!("No legal moves in {}", board.to_fen());
+        }
+        moves[self.rng.lock().unwrap().below(moves.len() as u64) as usize]
+    }
+}
+
+impl Default for RandomStrategy {
+    fn default() -> Self {
+        Self { rng: Mutex::new(Rng::from_time()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_plays_the_same_moves() {
+        let (a, b) = (RandomStrategy::new(7), RandomStrategy::new(7));
+        let mut board = Board::new();
+        for _ in 0..20 {
+            if board.legal_moves().is_empty() { break; }
+            let mv = a.next_move(&board);
+            assert_eq!(b.next_move(&board), mv);
+            assert!(board.is_legal(mv));
+            board.apply_move(mv).unwrap();
+        }
+    }
+}
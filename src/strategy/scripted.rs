@@ -0,0 +1,131 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::board::*;
+use crate::notation::*;
+use crate::pgn::*;
+
+use super::*;
+
+/// Plays a fixed list of moves in order, e.g. to replay a game or drive the
+/// engine from a test. One instance can play both sides of a game. The moves
+/// may be written in any notation a person could type.
+pub struct ScriptedStrategy {
+    moves: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl ScriptedStrategy {
+    pub fn new(moves: Vec<String>) -> Self {
+        Self { moves, next: AtomicUsize::new(0) }
+    }
+
+    /// Moves separated by whitespace. Move numbers (`1.`, `1...`) and game
+    /// results are skipped.
+    pub fn from_text(text: &str) -> Self {
+        let moves = text.split_whitespace()
+            .filter(|token| !RESULTS.contains(token))
+            .map(strip_move_number)
+            .filter(|token| !token.is_empty())
+            .map(String::from)
+            .collect();
+        Self::new(moves)
+    }
+
+    /// The moves of the first game of a PGN text.
+    pub fn from_pgn(text: &str) -> Result<Self, String> {
+        let game = parse_pgn(text)?.into_iter().next().ok_or("No game in the PGN")?;
+        Ok(Self::new(game.moves.iter().map(|mv| mv.to_string()).collect()))
+    }
+
+    /// Reads a PGN file, recognised by its extension or its tags, or else a
+    /// plain list of moves.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}. Error {e}", path.display()))?;
+        let is_pgn = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pgn"))
+            || text.trim_start().starts_with('[');
+        if is_pgn { Self::from_pgn(&text) } else { Ok(Self::from_text(&text)) }
+    }
+
+    /// Moves not played yet.
+    pub fn remaining(&self) -> usize {
+        self.moves.len().saturating_sub(self.next.load(Ordering::Relaxed))
+    }
+}
+
+/// The token without a leading move number such as `12.` or `12...`, which
+/// may be written apart from the move or joined to it (`12.Nf3`). Castling
+/// with zeros (`0-0`) has no dot and is kept whole.
+fn strip_move_number(token: &str) -> &str {
+    let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = &token[digits..];
+    if digits > 0 && rest.starts_with('.') { rest.trim_start_matches('.') } else { token }
+}
+
+impl Strategy for ScriptedStrategy {
+    fn next_move(&self, board: &Board) -> Move {
+        self.try_next_move(board).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// The next move of the script, or an error when it has run out or the
+    /// move is not legal in the position.
+    fn try_next_move(&self, board: &Board) -> Result<Move, String> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        let text = self.moves.get(index)
+            .ok_or_else(|| format!("Script exhausted after {} moves", self.moves.len()))?;
+        parse_user_move(board, text, &PieceStyle::ENGLISH)
+            .map_err(|e| format!("Move {} of the script, {text}: {e}", index + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_the_moves_in_order() {
+        let script = ScriptedStrategy::from_text("1. e4 e5 2. Nf3 1-0");
+        assert_eq!(script.remaining(), 3);
+
+        let mut board = Board::new();
+        for expected in ["e2e4", "e7e5", "g1f3"] {
+            let mv = script.try_next_move(&board).unwrap();
+            assert_eq!(mv, Move::from_uci(expected).unwrap());
+            board.apply_move(mv).unwrap();
+        }
+        assert_eq!(script.remaining(), 0);
+        assert_eq!(script.try_next_move(&board), Err(String::from("Script exhausted after 3 moves")));
+    }
+
+    #[test]
+    fn castling_with_zeros_is_not_a_move_number() {
+        let script = ScriptedStrategy::from_text("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 Nf6 5.d3 0-0");
+        assert_eq!(script.remaining(), 10);
+
+        let mut board = Board::new();
+        while script.remaining() > 0 {
+            board.apply_move(script.try_next_move(&board).unwrap()).unwrap();
+        }
+        assert_eq!(board.to_fen(), "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQ1RK1 w - - 1 6");
+    }
+
+    #[test]
+    fn reads_the_first_game_of_a_pgn() {
+        let script = ScriptedStrategy::from_pgn("[White \"a\"]\n\n1. d4 d5 *\n\n[White \"b\"]\n\n1. c4 *\n").unwrap();
+        assert_eq!(script.try_next_move(&Board::new()), Ok(Move::from_uci("d2d4").unwrap()));
+        assert_eq!(script.remaining(), 1);
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let script = ScriptedStrategy::new(vec![String::from("e2e5")]);
+        assert!(script.try_next_move(&Board::new()).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Script exhausted")]
+    fn panics_when_exhausted() {
+        ScriptedStrategy::new(Vec::new()).next_move(&Board::new());
+    }
+}
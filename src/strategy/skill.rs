@@ -0,0 +1,150 @@
+use crate::board::*;
+use crate::board::piece::*;
+use crate::rng::*;
+use crate::search::*;
+
+/// Playing strength from level 0, a beginner, to 20, the full strength of
+/// the computer player. Lower levels search fewer plies and nodes and choose
+/// among the best few moves with a random bias towards the worse ones, so
+/// their mistakes are plausible inaccuracies rather than blunders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Skill {
+    level: u32,
+}
+
+impl Skill {
+    pub const MAX_LEVEL: u32 = 20;
+    /// Nominal ratings of level 0 and level 20, in between the levels are
+    /// spaced evenly. The calibration command measures the real differences.
+    pub const MIN_ELO: u32 = 600;
+    pub const MAX_ELO: u32 = 2000;
+    // moves considered by a weakened player
+    const CANDIDATES: usize = 4;
+
+    /// The level, at most `MAX_LEVEL`.
+    pub fn new(level: u32) -> Self {
+        Self { level: level.min(Self::MAX_LEVEL) }
+    }
+
+    /// The level closest to a target rating, as the `UCI_Elo` option.
+    pub fn from_elo(elo: u32) -> Self {
+        let elo = elo.clamp(Self::MIN_ELO, Self::MAX_ELO);
+        let range = Self::MAX_ELO - Self::MIN_ELO;
+        Self::new(((elo - Self::MIN_ELO) * Self::MAX_LEVEL + range / 2) / range)
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Nominal rating of the level.
+    pub fn elo(&self) -> u32 {
+        Self::MIN_ELO + self.level * (Self::MAX_ELO - Self::MIN_ELO) / Self::MAX_LEVEL
+    }
+
+    pub fn is_full_strength(&self) -> bool {
+        self.level == Self::MAX_LEVEL
+    }
+
+    /// Plies searched at most, one at level 0 and five at level 19.
+    pub fn max_depth(&self) -> Option<u32> {
+        (!self.is_full_strength()).then_some(1 + self.level / 4)
+    }
+
+    /// Nodes searched at most, from 400 at level 0 doubling every other level.
+    pub fn max_nodes(&self) -> Option<u64> {
+        (!self.is_full_strength()).then_some(400 << (self.level / 2))
+    }
+
+    /// `limits` further restricted by the depth and nodes of the level.
+    pub fn limit(&self, limits: &SearchLimits) -> SearchLimits {
+        fn min<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+
+        SearchLimits {
+            depth: min(limits.depth, self.max_depth()),
+            nodes: min(limits.nodes, self.max_nodes()),
+            ..*limits
+        }
+    }
+
+    /// How much a worse move is favoured, from 120 at level 0 to 82 at
+    /// level 19, out of 128.
+    fn weakness(&self) -> i32 {
+        120 - 2 * self.level as i32
+    }
+
+    /// Centipawns a chosen move may lose against the best one, from two
+    /// pawns at level 0 to 40 at level 19.
+    pub fn max_loss(&self) -> i32 {
+        30 + 8 * (Self::MAX_LEVEL - self.level) as i32
+    }
+
+    /// Chooses among moves scored best first. Each of the best few moves
+    /// gets a bonus growing with how much worse it is and a random one of up
+    /// to the spread of their scores (at most a pawn), and the highest total
+    /// wins. Moves losing more than `max_loss` are never chosen.
+    pub fn pick(&self, scored: &[(Move, i32)], rng: &mut Rng) -> Option<Move> {
+        let &(best, top) = scored.first()?;
+        if self.is_full_strength() || is_mate_score(top) { return Some(best); }
+
+        let candidates: Vec<(Move, i32)> = scored.iter().take(Self::CANDIDATES)
+            .copied()
+            .filter(|&(_, score)| top - score <= self.max_loss())
+            .collect();
+        let spread = (top - candidates.last().map_or(top, |&(_, score)| score)).min(piece_value(PieceType::Pawn));
+        let weakness = self.weakness();
+
+        candidates.into_iter()
+            .map(|(mv, score)| {
+                let push = (weakness * (top - score) + spread * rng.below(weakness as u64) as i32) / 128;
+                (score + push, mv)
+            })
+            .max_by_key(|&(total, _)| total)
+            .map(|(_, mv)| mv)
+    }
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self::new(Self::MAX_LEVEL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_maps_to_levels() {
+        assert_eq!(Skill::from_elo(0), Skill::new(0));
+        assert_eq!(Skill::from_elo(1300), Skill::new(10));
+        assert_eq!(Skill::from_elo(3000), Skill::new(20));
+        assert_eq!(Skill::new(10).elo(), 1300);
+        assert_eq!(Skill::new(25).level(), 20);
+    }
+
+    #[test]
+    fn limits_shrink_with_the_level() {
+        let limits = Skill::new(0).limit(&SearchLimits::depth(6));
+        assert_eq!((limits.depth, limits.nodes), (Some(1), Some(400)));
+        assert_eq!(Skill::new(20).limit(&SearchLimits::depth(6)), SearchLimits::depth(6));
+    }
+
+    #[test]
+    fn weak_levels_vary_but_never_blunder() {
+        let moves: Vec<Move> = Board::new().legal_moves();
+        let scored = [(moves[0], 50), (moves[1], 40), (moves[2], 20), (moves[3], -300), (moves[4], -400)];
+        let skill = Skill::new(0);
+        let mut rng = Rng::new(1);
+        let picked: Vec<Move> = (0..200).filter_map(|_| skill.pick(&scored, &mut rng)).collect();
+
+        assert!(picked.iter().all(|mv| scored[..3].iter().any(|(good, _)| good == mv)));
+        assert!(picked.iter().any(|&mv| mv != moves[0]));
+        assert_eq!(Skill::new(20).pick(&scored, &mut rng), Some(moves[0]));
+    }
+}
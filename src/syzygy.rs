@@ -0,0 +1,647 @@
+mod table;
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::board::*;
+use crate::board::piece::*;
+
+use table::*;
+
+/// Outcome of a tablebase position for the side to move. Cursed wins and
+/// blessed losses are wins and losses which take longer than the fifty
+/// move rule allows, so they are draws in play.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    fn value(self) -> i32 {
+        self as i32 - 2
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::from_value(-self.value())
+    }
+}
+
+// Distance to zeroing of a capture or pawn move with the given result.
+const WDL_TO_DTZ: [i32; 5] = [-1, -101, 0, 101, 1];
+
+const PIECE_LETTERS: [(PieceType, char); 6] = [
+    (PieceType::King, 'K'),
+    (PieceType::Queen, 'Q'),
+    (PieceType::Rook, 'R'),
+    (PieceType::Bishop, 'B'),
+    (PieceType::Knight, 'N'),
+    (PieceType::Pawn, 'P'),
+];
+
+/// Name of the table holding the position, white's pieces first, e.g.
+/// KRPvKR.
+pub fn material_key(board: &Board) -> String {
+    let side = |color: PieceColor| -> String {
+        PIECE_LETTERS.iter().flat_map(|&(piece_type, letter)| {
+            let count = board.cells.iter().filter(|cell| cell.piece == Some(Piece { piece_type, color })).count();
+            std::iter::repeat_n(letter, count)
+        }).collect()
+    };
+    format!("{}v{}", side(PieceColor::White), side(PieceColor::Black))
+}
+
+fn mirrored_key(key: &str) -> String {
+    let (white, black) = key.split_once('v').unwrap_or((key, ""));
+    format!("{black}v{white}")
+}
+
+fn is_table_name(name: &str) -> bool {
+    let Some((white, black)) = name.split_once('v') else { return false; };
+    let side = |part: &str| part.starts_with('K')
+        && part.chars().all(|c| PIECE_LETTERS.iter().any(|&(_, letter)| letter == c));
+    side(white) && side(black)
+}
+
+fn piece_count(board: &Board) -> usize {
+    board.cells.iter().filter(|cell| cell.piece.is_some()).count()
+}
+
+fn is_en_passant(board: &Board, mv: Move) -> bool {
+    Some(mv.to) == board.en_passant
+        && board.piece_at(mv.from).is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+        && board.piece_at(mv.to).is_none()
+}
+
+/// The WDL and DTZ files of one material combination, read on first use.
+struct TableFiles {
+    name: String,
+    wdl: Option<PathBuf>,
+    dtz: Option<PathBuf>,
+    wdl_table: OnceLock<Result<Table, String>>,
+    dtz_table: OnceLock<Result<Table, String>>,
+}
+
+impl TableFiles {
+    fn table(&self, kind: TableKind) -> Result<&Table, String> {
+        let (path, table) = match kind {
+            TableKind::Wdl => (&self.wdl, &self.wdl_table),
+            TableKind::Dtz => (&self.dtz, &self.dtz_table),
+        };
+        let path = path.as_ref()
+            .ok_or_else(|| format!("Missing table {}.{}", self.name, kind.extension()))?;
+        table.get_or_init(|| {
+            let data = std::fs::read(path).map_err(|e| format!("Unable to read {}. Error {e}", path.display()))?;
+            Table::parse(data, &self.name, kind)
+        }).as_ref().map_err(|e| e.clone())
+    }
+}
+
+/// A root move ranked by the tablebase.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootMove {
+    pub mv: Move,
+    /// Result after the move, counting the fifty move rule.
+    pub wdl: Wdl,
+    /// Plies from the root to the next capture or pawn move with best play,
+    /// positive when winning and zero for draws.
+    pub dtz: i32,
+}
+
+impl RootMove {
+    /// Higher for better moves: better results, then quicker wins and
+    /// slower losses.
+    pub fn rank(&self) -> (Wdl, i32) {
+        (self.wdl, -self.dtz)
+    }
+}
+
+/// Syzygy endgame tablebases: win/draw/loss (`.rtbw`) and distance to
+/// zeroing (`.rtbz`) tables found in local directories, for positions with
+/// up to as many pieces as the largest table and no castling rights.
+#[derive(Default)]
+pub struct Tablebase {
+    tables: HashMap<String, Arc<TableFiles>>,
+    max_pieces: usize,
+}
+
+impl Tablebase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tables from the directories of `paths`, separated like the
+    /// directories of `PATH`.
+    pub fn open(paths: &str) -> Result<Self, String> {
+        let mut tablebase = Self::new();
+        for dir in std::env::split_paths(paths) {
+            tablebase.add_directory(&dir)?;
+        }
+        Ok(tablebase)
+    }
+
+    /// Adds the tables of `dir`, returning how many were found.
+    pub fn add_directory(&mut self, dir: &Path) -> Result<usize, String> {
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("Unable to read {}. Error {e}", dir.display()))?;
+
+        let mut found: HashMap<String, (Option<PathBuf>, Option<PathBuf>)> = HashMap::new();
+        for entry in entries {
+            let path = entry.map_err(|e| format!("Unable to read {}. Error {e}", dir.display()))?.path();
+            let (Some(name), Some(extension)) = (path.file_stem().and_then(|s| s.to_str()), path.extension()) else {
+                continue;
+            };
+            if !is_table_name(name) { continue; }
+
+            let files = found.entry(name.to_string()).or_default();
+            if extension == TableKind::Wdl.extension() {
+                files.0 = Some(path);
+            } else if extension == TableKind::Dtz.extension() {
+                files.1 = Some(path);
+            }
+        }
+
+        let mut count = 0;
+        for (name, (wdl, dtz)) in found {
+            if wdl.is_none() && dtz.is_none() { continue; }
+            let files = Arc::new(TableFiles { name: name.clone(), wdl, dtz, wdl_table: OnceLock::new(), dtz_table: OnceLock::new() });
+            self.max_pieces = self.max_pieces.max(name.len() - 1);
+            self.tables.insert(mirrored_key(&name), files.clone());
+            self.tables.insert(name, files);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Pieces, kings included, of the largest tables.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether the position may be probed: few enough pieces and no
+    /// castling rights.
+    pub fn covers(&self, board: &Board) -> bool {
+        board.castling.0 == 0 && piece_count(board) <= self.max_pieces
+    }
+
+    /// Win, draw or loss for the side to move.
+    pub fn probe_wdl(&self, board: &Board) -> Result<Wdl, String> {
+        self.check_covered(board)?;
+        self.wdl(&mut board.clone()).map(Wdl::from_value)
+    }
+
+    /// Plies to the next capture or pawn move, positive when the side to
+    /// move wins and beyond 100 for cursed wins and blessed losses. Zero
+    /// for draws.
+    pub fn probe_dtz(&self, board: &Board) -> Result<i32, String> {
+        self.check_covered(board)?;
+        self.dtz(&mut board.clone())
+    }
+
+    /// The legal moves ranked from best to worst, so that playing the first
+    /// one is perfect play.
+    pub fn probe_root(&self, board: &Board) -> Result<Vec<RootMove>, String> {
+        self.check_covered(board)?;
+
+        let mut board = board.clone();
+        let clock = board.halfmove_clock as i32;
+        let mut moves = Vec::new();
+        for mv in board.legal_moves() {
+            let undo = board.make(mv);
+            let dtz = if board.is_checkmate() {
+                Ok(1)
+            } else if board.halfmove_clock == 0 {
+                self.wdl(&mut board).map(|wdl| WDL_TO_DTZ[(2 - wdl) as usize])
+            } else {
+                self.dtz(&mut board).map(|dtz| -dtz - dtz.signum())
+            };
+            board.unmake(mv, undo);
+
+            let dtz = dtz?;
+            let wdl = match dtz {
+                0 => Wdl::Draw,
+                dtz if dtz > 0 => if dtz + clock <= 100 { Wdl::Win } else { Wdl::CursedWin },
+                dtz => if clock - dtz <= 100 { Wdl::Loss } else { Wdl::BlessedLoss },
+            };
+            moves.push(RootMove { mv, wdl, dtz });
+        }
+
+        moves.sort_by_key(|root_move| Reverse(root_move.rank()));
+        Ok(moves)
+    }
+
+    fn check_covered(&self, board: &Board) -> Result<(), String> {
+        if self.covers(board) {
+            Ok(())
+        } else {
+            Err(format!("Position not in the tablebase: {}", board.to_fen()))
+        }
+    }
+
+    fn files(&self, board: &Board) -> Result<(&TableFiles, String), String> {
+        let key = material_key(board);
+        match self.tables.get(&key) {
+            Some(files) => Ok((files, key)),
+            None => Err(format!("Missing table {key}")),
+        }
+    }
+
+    // Whether the colors of the position are swapped in the table, and the
+    // table's side to move.
+    fn orientation(files: &TableFiles, key: &str, board: &Board) -> (bool, usize, bool) {
+        let white = board.side_to_move == PieceColor::White;
+        let symmetric = mirrored_key(&files.name) == files.name;
+        if symmetric {
+            (!white, 0, true)
+        } else {
+            let flip = key != files.name;
+            (flip, (white == flip) as usize, false)
+        }
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Result<i32, String> {
+        if piece_count(board) == 2 { return Ok(0); }
+
+        let (files, key) = self.files(board)?;
+        let (flip, side, symmetric) = Self::orientation(files, &key, board);
+        files.table(TableKind::Wdl)?.wdl(board, flip, side, symmetric)
+    }
+
+    fn probe_dtz_table(&self, board: &Board, wdl: i32) -> Result<Option<i32>, String> {
+        let (files, key) = self.files(board)?;
+        let (flip, side, symmetric) = Self::orientation(files, &key, board);
+        files.table(TableKind::Dtz)?.dtz(board, flip, side, symmetric, wdl)
+    }
+
+    // Resolves captures, which the tables leave out, by a search between
+    // alpha and beta. Also tells whether the score is due to a capture.
+    fn probe_ab(&self, board: &mut Board, mut alpha: i32, beta: i32) -> Result<(i32, bool), String> {
+        for mv in board.legal_moves() {
+            if board.piece_at(mv.to).is_none() { continue; }
+
+            let undo = board.make(mv);
+            let score = self.probe_ab(board, -beta, -alpha).map(|(score, _)| -score);
+            board.unmake(mv, undo);
+
+            let score = score?;
+            if score > alpha {
+                if score >= beta { return Ok((score, true)); }
+                alpha = score;
+            }
+        }
+
+        let score = self.probe_wdl_table(board)?;
+        Ok(if alpha >= score { (alpha, alpha > 0) } else { (score, false) })
+    }
+
+    // Best result of the en passant captures, if there are any.
+    fn en_passant(&self, board: &mut Board) -> Result<Option<i32>, String> {
+        let mut best = None;
+        for mv in board.legal_moves() {
+            if !is_en_passant(board, mv) { continue; }
+
+            let undo = board.make(mv);
+            let score = self.probe_ab(board, -2, 2).map(|(score, _)| -score);
+            board.unmake(mv, undo);
+            best = best.max(Some(score?));
+        }
+        Ok(best)
+    }
+
+    fn only_en_passant(board: &Board) -> bool {
+        board.legal_moves().into_iter().all(|mv| is_en_passant(board, mv))
+    }
+
+    fn wdl(&self, board: &mut Board) -> Result<i32, String> {
+        let (mut score, _) = self.probe_ab(board, -2, 2)?;
+
+        if let Some(en_passant) = self.en_passant(board)? {
+            if en_passant >= score || (score == 0 && Self::only_en_passant(board)) {
+                score = en_passant;
+            }
+        }
+        Ok(score)
+    }
+
+    fn dtz(&self, board: &mut Board) -> Result<i32, String> {
+        let mut dtz = self.dtz_no_en_passant(board)?;
+
+        if let Some(en_passant) = self.en_passant(board)? {
+            let capture = WDL_TO_DTZ[(en_passant + 2) as usize];
+            let better = if dtz < -100 {
+                capture >= 0
+            } else if dtz < 0 {
+                !(-100..0).contains(&capture)
+            } else if dtz > 100 {
+                capture > 0
+            } else if dtz > 0 {
+                capture == 1
+            } else {
+                capture >= 0 || Self::only_en_passant(board)
+            };
+            if better { dtz = capture; }
+        }
+        Ok(dtz)
+    }
+
+    fn dtz_no_en_passant(&self, board: &mut Board) -> Result<i32, String> {
+        let (wdl, by_capture) = self.probe_ab(board, -2, 2)?;
+        if wdl == 0 { return Ok(0); }
+        if by_capture { return Ok(WDL_TO_DTZ[(wdl + 2) as usize]); }
+
+        let is_pawn = |board: &Board, mv: Move| board.piece_at(mv.from).is_some_and(|piece| piece.piece_type == PieceType::Pawn);
+        let quiet = |board: &Board, mv: Move| board.piece_at(mv.to).is_none() && !is_en_passant(board, mv);
+
+        if wdl > 0 {
+            // a pawn move keeping the win zeroes the count at once
+            for mv in board.legal_moves() {
+                if !is_pawn(board, mv) || !quiet(board, mv) { continue; }
+
+                let undo = board.make(mv);
+                let score = self.wdl(board).map(|score| -score);
+                board.unmake(mv, undo);
+                if score? == wdl { return Ok(WDL_TO_DTZ[(wdl + 2) as usize]); }
+            }
+        }
+
+        if let Some(dtz) = self.probe_dtz_table(board, wdl)? {
+            return Ok(WDL_TO_DTZ[(wdl + 2) as usize] + if wdl > 0 { dtz } else { -dtz });
+        }
+
+        // the table only has the other side to move, so look one ply ahead
+        if wdl > 0 {
+            let mut best = i32::MAX;
+            for mv in board.legal_moves() {
+                if is_pawn(board, mv) || !quiet(board, mv) { continue; }
+
+                let undo = board.make(mv);
+                let result = self.dtz(board).map(|dtz| (-dtz, board.is_checkmate()));
+                board.unmake(mv, undo);
+
+                let (dtz, mate) = result?;
+                if dtz == 1 && mate {
+                    best = 1;
+                } else if dtz > 0 && dtz + 1 < best {
+                    best = dtz + 1;
+                }
+            }
+            Ok(best)
+        } else {
+            let mut best = -1;
+            for mv in board.legal_moves() {
+                let undo = board.make(mv);
+                let dtz = if board.halfmove_clock == 0 {
+                    if wdl == -2 {
+                        Ok(-1)
+                    } else {
+                        self.probe_ab(board, 1, 2).map(|(score, _)| if score == 2 { 0 } else { -101 })
+                    }
+                } else {
+                    self.dtz(board).map(|dtz| -dtz - 1)
+                };
+                board.unmake(mv, undo);
+                best = best.min(dtz?);
+            }
+            Ok(best)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("chess-syzygy-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn test_tablebase(name: &str) -> (TempDir, Tablebase) {
+        let dir = TempDir::new(name);
+        std::fs::write(dir.0.join("KQvK.rtbw"), table::tests::test_table()).unwrap();
+        std::fs::write(dir.0.join("README.txt"), "not a table").unwrap();
+        let mut tablebase = Tablebase::new();
+        assert_eq!(tablebase.add_directory(&dir.0), Ok(1));
+        (dir, tablebase)
+    }
+
+    /// The test tables with the DTZ table for white to move.
+    fn test_tablebase_with_dtz(name: &str) -> (TempDir, Tablebase) {
+        let dir = TempDir::new(name);
+        std::fs::write(dir.0.join("KQvK.rtbw"), table::tests::test_table()).unwrap();
+        std::fs::write(dir.0.join("KQvK.rtbz"), table::tests::test_dtz_table()).unwrap();
+        let mut tablebase = Tablebase::new();
+        assert_eq!(tablebase.add_directory(&dir.0), Ok(1));
+        (dir, tablebase)
+    }
+
+    /// Tables generated by the Syzygy generator, at least KQvK and KRvK
+    /// with their DTZ files, from the directory in `SYZYGY_TEST_PATH`.
+    /// They are too large to keep in the repository, so the tests reading
+    /// them are ignored unless run with `--ignored`.
+    fn real_tablebase() -> Tablebase {
+        let path = std::env::var("SYZYGY_TEST_PATH").expect("SYZYGY_TEST_PATH is not set");
+        Tablebase::open(&path).expect("Unable to open SYZYGY_TEST_PATH")
+    }
+
+    #[test]
+    fn material_keys() {
+        let board = Board::from_fen("8/8/4k3/3r4/8/3P4/2RK4/8 w - - 0 1").unwrap();
+        assert_eq!(material_key(&board), "KRPvKR");
+        assert_eq!(mirrored_key("KRPvKR"), "KRvKRP");
+        assert!(is_table_name("KBNvK"));
+        assert!(!is_table_name("KQvKX"));
+        assert!(!is_table_name("QvK"));
+    }
+
+    #[test]
+    fn wdl_negation() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::BlessedLoss, Wdl::CursedWin);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert!(Wdl::Win > Wdl::CursedWin);
+    }
+
+    #[test]
+    fn empty_tablebase_covers_nothing() {
+        let tablebase = Tablebase::new();
+        let board = Board::from_fen("8/8/8/4k3/8/8/8/4K2Q w - - 0 1").unwrap();
+        assert_eq!(tablebase.max_pieces(), 0);
+        assert!(!tablebase.covers(&board));
+        assert!(tablebase.probe_wdl(&board).is_err());
+        assert!(Tablebase::open("/nonexistent/syzygy").is_err());
+    }
+
+    #[test]
+    fn probes_the_table_for_either_side() {
+        let (_dir, tablebase) = test_tablebase("sides");
+        assert_eq!(tablebase.max_pieces(), 3);
+
+        // black to move loses in the test table, as does white with the
+        // colors swapped
+        let black = Board::from_fen("8/8/8/4k3/8/8/8/Q3K3 b - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&black), Ok(Wdl::Loss));
+        let white = Board::from_fen("q3k3/8/8/8/4K3/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&white), Ok(Wdl::Loss));
+
+        // castling rights and missing tables
+        let castling = Board::from_fen("8/8/8/4k3/8/8/8/Q3K2R w K - 0 1").unwrap();
+        assert!(!tablebase.covers(&castling));
+        let rook = Board::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&rook), Err(String::from("Missing table KRvK")));
+    }
+
+    #[test]
+    fn white_values_follow_the_table() {
+        let (_dir, tablebase) = test_tablebase("white");
+        let mut values = Vec::new();
+        for fen in ["8/8/8/4k3/8/8/8/1Q2K3 w - - 0 1", "7k/8/8/8/8/1Q6/8/K7 w - - 0 1", "8/1k6/8/8/8/8/5Q2/6K1 w - - 0 1"] {
+            let board = Board::from_fen(fen).unwrap();
+            let wdl = tablebase.probe_wdl(&board).unwrap();
+            assert!(wdl == Wdl::Win || wdl == Wdl::Draw);
+            values.push(wdl);
+        }
+        // the same positions with colors swapped and black to move
+        for (fen, wdl) in ["1q2k3/8/8/8/4K3/8/8/8 b - - 0 1", "k7/8/1q6/8/8/8/8/7K b - - 0 1", "6k1/5q2/8/8/8/8/1K6/8 b - - 0 1"].into_iter().zip(values) {
+            assert_eq!(tablebase.probe_wdl(&Board::from_fen(fen).unwrap()), Ok(wdl));
+        }
+    }
+
+    #[test]
+    fn captures_are_resolved_by_search() {
+        let (_dir, tablebase) = test_tablebase("captures");
+        // the black king takes the undefended queen, leaving a bare draw
+        let board = Board::from_fen("8/8/8/8/8/8/3Qk3/7K b - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&board), Ok(Wdl::Draw));
+    }
+
+    #[test]
+    fn search_scores_positions_in_the_tablebase() {
+        use crate::eval::MaterialEvaluator;
+        use crate::search::*;
+
+        let (_dir, tablebase) = test_tablebase("search");
+        let mut search = Search::new();
+        search.set_evaluator(Arc::new(MaterialEvaluator));
+        search.set_tablebase(Some(Arc::new(tablebase)));
+
+        // taking the rook reaches a lost position for black in the table
+        let board = Board::from_fen("r7/8/8/4k3/8/8/8/Q3K3 w - - 0 1").unwrap();
+        let result = search.search(&board, 1);
+        assert_eq!(result.best_move, Some(Move::from_uci("a1a8").unwrap()));
+        assert_eq!(result.score, TB_WIN - 1);
+    }
+
+    #[test]
+    fn dtz_is_read_from_the_table_or_one_ply_ahead() {
+        let (_dir, tablebase) = test_tablebase_with_dtz("dtz-values");
+        // a win stored as 4 plies after the zeroing move
+        let white = Board::from_fen("7k/8/8/8/8/1Q6/8/K7 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&white), Ok(Wdl::Win));
+        assert_eq!(tablebase.probe_dtz(&white), Ok(5));
+        let draw = Board::from_fen("8/8/8/4k3/8/8/8/1Q2K3 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_dtz(&draw), Ok(0));
+
+        // black to move is not in the table, so its replies are probed
+        let black = Board::from_fen("8/8/8/4k3/8/8/8/Q3K3 b - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_dtz(&black), Ok(-6));
+    }
+
+    #[test]
+    fn root_moves_rank_mates_first_and_draws_last() {
+        let (_dir, tablebase) = test_tablebase_with_dtz("root");
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/Q7 w - - 0 1").unwrap();
+        let moves = tablebase.probe_root(&board).unwrap();
+        assert_eq!(moves.len(), board.legal_moves().len());
+
+        let mut mate = board.clone();
+        mate.make(moves[0].mv);
+        assert!(mate.is_checkmate());
+        assert_eq!((moves[0].wdl, moves[0].dtz), (Wdl::Win, 1));
+        assert!(moves.windows(2).all(|pair| pair[0].rank() >= pair[1].rank()));
+
+        // Qh8+ is met by Kxh8
+        let last = moves.last().unwrap();
+        assert_eq!((last.mv, last.wdl, last.dtz), (Move::from_uci("a1h8").unwrap(), Wdl::Draw, 0));
+    }
+
+    #[test]
+    #[ignore = "needs SYZYGY_TEST_PATH"]
+    fn reads_real_tables() {
+        let tablebase = real_tablebase();
+        let probe = |fen: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            (tablebase.probe_wdl(&board).unwrap(), tablebase.probe_dtz(&board).unwrap())
+        };
+
+        for fen in ["8/8/8/4k3/8/8/8/Q3K3 w - - 0 1", "8/8/8/4k3/8/8/8/R3K3 w - - 0 1"] {
+            let (wdl, dtz) = probe(fen);
+            assert_eq!(wdl, Wdl::Win, "{fen}");
+            assert!((2..=100).contains(&dtz), "{fen} {dtz}");
+        }
+        for fen in ["8/8/8/4k3/8/8/8/Q3K3 b - - 0 1", "8/8/8/4k3/8/8/8/R3K3 b - - 0 1"] {
+            let (wdl, dtz) = probe(fen);
+            assert_eq!(wdl, Wdl::Loss, "{fen}");
+            assert!((-100..=-1).contains(&dtz), "{fen} {dtz}");
+        }
+        // the black king takes the undefended piece
+        assert_eq!(probe("8/8/8/8/8/8/3Rk3/7K b - - 0 1"), (Wdl::Draw, 0));
+        assert_eq!(probe("8/8/8/8/8/8/3Qk3/7K b - - 0 1"), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    #[ignore = "needs SYZYGY_TEST_PATH"]
+    fn real_tables_rank_the_mate_first() {
+        let tablebase = real_tablebase();
+        for fen in ["7k/8/6K1/8/8/8/8/Q7 w - - 0 1", "7k/8/6K1/8/8/8/8/R7 w - - 0 1"] {
+            let board = Board::from_fen(fen).unwrap();
+            let moves = tablebase.probe_root(&board).unwrap();
+            assert_eq!((moves[0].wdl, moves[0].dtz), (Wdl::Win, 1), "{fen}");
+            for root_move in moves.iter().filter(|root_move| root_move.dtz == 1) {
+                let mut after = board.clone();
+                after.make(root_move.mv);
+                assert!(after.is_checkmate(), "{fen} {}", root_move.mv);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_dtz_tables_are_errors() {
+        let (_dir, tablebase) = test_tablebase("dtz");
+        let board = Board::from_fen("8/8/8/4k3/8/8/8/Q3K3 b - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_dtz(&board), Err(String::from("Missing table KQvK.rtbz")));
+        assert!(tablebase.probe_root(&board).is_err());
+    }
+}
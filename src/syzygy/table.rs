@@ -0,0 +1,958 @@
+use std::sync::OnceLock;
+
+use crate::board::*;
+use crate::board::piece::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TableKind::Wdl => "rtbw",
+            TableKind::Dtz => "rtbz",
+        }
+    }
+
+    fn magic(&self) -> u32 {
+        match self {
+            TableKind::Wdl => 0x5d23e871,
+            TableKind::Dtz => 0xa50c66d7,
+        }
+    }
+}
+
+// The tables number squares from a1 upwards, the mapping tables below are
+// laid out the same way.
+
+// Squares of the a1-d1-d4 triangle, the off-diagonal ones first.
+const TRIANGLE: [u64; 64] = [
+    6, 0, 1, 2, 2, 1, 0, 6,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    6, 0, 1, 2, 2, 1, 0, 6,
+];
+
+// Squares below the a1-h8 diagonal, then the diagonal itself.
+const LOWER: [u64; 64] = [
+    28,  0,  1,  2,  3,  4,  5,  6,
+     0, 29,  7,  8,  9, 10, 11, 12,
+     1,  7, 30, 13, 14, 15, 16, 17,
+     2,  8, 13, 31, 18, 19, 20, 21,
+     3,  9, 14, 18, 32, 22, 23, 24,
+     4, 10, 15, 19, 22, 33, 25, 26,
+     5, 11, 16, 20, 23, 25, 34, 27,
+     6, 12, 17, 21, 24, 26, 27, 35,
+];
+
+const DIAG: [u64; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  8,
+     0,  1,  0,  0,  0,  0,  9,  0,
+     0,  0,  2,  0,  0, 10,  0,  0,
+     0,  0,  0,  3, 11,  0,  0,  0,
+     0,  0,  0, 12,  4,  0,  0,  0,
+     0,  0, 13,  0,  0,  5,  0,  0,
+     0, 14,  0,  0,  0,  0,  6,  0,
+    15,  0,  0,  0,  0,  0,  0,  7,
+];
+
+// Pawn squares of the a-d files, file by file.
+const FLAP: [usize; 64] = [
+    0,  0,  0,  0,  0,  0,  0, 0,
+    0,  6, 12, 18, 18, 12,  6, 0,
+    1,  7, 13, 19, 19, 13,  7, 1,
+    2,  8, 14, 20, 20, 14,  8, 2,
+    3,  9, 15, 21, 21, 15,  9, 3,
+    4, 10, 16, 22, 22, 16, 10, 4,
+    5, 11, 17, 23, 23, 17, 11, 5,
+    0,  0,  0,  0,  0,  0,  0, 0,
+];
+
+const PAWN_TWIST: [usize; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    47, 35, 23, 11, 10, 22, 34, 46,
+    45, 33, 21,  9,  8, 20, 32, 44,
+    43, 31, 19,  7,  6, 18, 30, 42,
+    41, 29, 17,  5,  4, 16, 28, 40,
+    39, 27, 15,  3,  2, 14, 26, 38,
+    37, 25, 13,  1,  0, 12, 24, 36,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const INV_FLAP: [usize; 24] = [
+     8, 16, 24, 32, 40, 48,
+     9, 17, 25, 33, 41, 49,
+    10, 18, 26, 34, 42, 50,
+    11, 19, 27, 35, 43, 51,
+];
+
+const FILE_TO_FILE: [usize; 8] = [0, 1, 2, 3, 3, 2, 1, 0];
+
+// Positions of three unique pieces and of two kings after using the
+// symmetries of the board.
+const PIECE_POSITIONS: u64 = 31332;
+const KING_POSITIONS: u64 = 462;
+
+// Which of the four DTZ maps applies to each result, and the flags telling
+// whether the map stores plies rather than moves.
+const WDL_TO_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+const PA_FLAGS: [u8; 5] = [8, 0, 0, 0, 4];
+
+fn off_diag(sq: usize) -> i32 {
+    (sq / 8) as i32 - (sq % 8) as i32
+}
+
+fn flip_diag(sq: usize) -> usize {
+    ((sq >> 3) | (sq << 3)) & 63
+}
+
+struct Indices {
+    // binomial[k][n] is n choose k
+    binomial: [[u64; 64]; 7],
+    pawn_idx: [[u64; 24]; 6],
+    pawn_factor: [[u64; 4]; 6],
+    kings: [[u64; 64]; 10],
+}
+
+impl Indices {
+    fn new() -> Self {
+        let mut binomial = [[0; 64]; 7];
+        for (k, row) in binomial.iter_mut().enumerate() {
+            for (n, value) in row.iter_mut().enumerate() {
+                let (mut f, mut l) = (1u64, 1u64);
+                for i in 0..k as u64 {
+                    f *= (n as u64).saturating_sub(i);
+                    l *= i + 1;
+                }
+                *value = f / l;
+            }
+        }
+
+        let mut pawn_idx = [[0; 24]; 6];
+        let mut pawn_factor = [[0; 4]; 6];
+        for i in 0..6 {
+            let mut s = 0;
+            for j in 0..24 {
+                pawn_idx[i][j] = s;
+                s += binomial[i][PAWN_TWIST[INV_FLAP[j]]];
+                if (j + 1) % 6 == 0 {
+                    pawn_factor[i][j / 6] = s;
+                    s = 0;
+                }
+            }
+        }
+
+        // The first king is in the a1-d1-d4 triangle and, when on the
+        // diagonal, the second is not above it. Both on the diagonal come last.
+        let mut kings = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for (triangle, row) in kings.iter_mut().enumerate() {
+            let first = (0..64)
+                .find(|&sq| sq % 8 < 4 && off_diag(sq) <= 0 && TRIANGLE[sq] == triangle as u64)
+                .unwrap();
+            for (second, code_of_pair) in row.iter_mut().enumerate() {
+                let adjacent = (first / 8).abs_diff(second / 8) <= 1 && (first % 8).abs_diff(second % 8) <= 1;
+                if adjacent || (off_diag(first) == 0 && off_diag(second) > 0) { continue; }
+                if off_diag(first) == 0 && off_diag(second) == 0 {
+                    both_on_diagonal.push((triangle, second));
+                } else {
+                    *code_of_pair = code;
+                    code += 1;
+                }
+            }
+        }
+        for (triangle, second) in both_on_diagonal {
+            kings[triangle][second] = code;
+            code += 1;
+        }
+
+        Self { binomial, pawn_idx, pawn_factor, kings }
+    }
+}
+
+fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(Indices::new)
+}
+
+fn corrupt() -> String {
+    String::from("Corrupted tablebase file")
+}
+
+fn read_u8(data: &[u8], at: usize) -> Result<usize, String> {
+    data.get(at).map(|&b| b as usize).ok_or_else(corrupt)
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<usize, String> {
+    Ok(read_u8(data, at)? | read_u8(data, at + 1)? << 8)
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<usize, String> {
+    Ok(read_u16(data, at)? | read_u16(data, at + 2)? << 16)
+}
+
+// Big-endian bits of the compressed blocks, zero past the end of the file.
+fn read_be(data: &[u8], at: usize, bytes: usize) -> u64 {
+    (0..bytes).fold(0, |value, i| value << 8 | data.get(at + i).copied().unwrap_or(0) as u64)
+}
+
+/// Huffman-coded values of one table, each symbol standing for a single
+/// value or a pair of symbols.
+struct Pairs {
+    // the value of every position when there is no index
+    min_len: u32,
+    idx_bits: u32,
+    block_size: u32,
+    offset: usize,
+    sym_pat: usize,
+    sym_len: Vec<u32>,
+    base: Vec<u64>,
+    index_table: usize,
+    size_table: usize,
+    data: usize,
+}
+
+impl Pairs {
+    /// Reads the header at `pos`, moving past it. Returns the sizes of the
+    /// index table, size table and data which follow later in the file, and
+    /// the flags of the table.
+    fn parse(data: &[u8], pos: &mut usize, positions: u64, kind: TableKind) -> Result<(Self, [usize; 3], u8), String> {
+        let at = *pos;
+        let flags = read_u8(data, at)? as u8;
+        let mut pairs = Self {
+            min_len: 0,
+            idx_bits: 0,
+            block_size: 0,
+            offset: 0,
+            sym_pat: 0,
+            sym_len: Vec::new(),
+            base: Vec::new(),
+            index_table: 0,
+            size_table: 0,
+            data: 0,
+        };
+
+        if flags & 0x80 != 0 {
+            if kind == TableKind::Wdl {
+                pairs.min_len = read_u8(data, at + 1)? as u32;
+            }
+            *pos += 2;
+            return Ok((pairs, [0; 3], flags));
+        }
+
+        pairs.block_size = read_u8(data, at + 1)? as u32;
+        pairs.idx_bits = read_u8(data, at + 2)? as u32;
+        let real_num_blocks = read_u32(data, at + 4)?;
+        let num_blocks = real_num_blocks + read_u8(data, at + 3)?;
+        let max_len = read_u8(data, at + 8)? as u32;
+        pairs.min_len = read_u8(data, at + 9)? as u32;
+        if pairs.idx_bits == 0 || pairs.idx_bits > 32 || pairs.block_size > 32
+            || max_len < pairs.min_len || max_len >= 64 {
+            return Err(corrupt());
+        }
+
+        let h = (max_len - pairs.min_len + 1) as usize;
+        let num_syms = read_u16(data, at + 10 + 2 * h)?;
+        pairs.offset = at + 10;
+        pairs.sym_pat = at + 12 + 2 * h;
+        *pos = pairs.sym_pat + 3 * num_syms + (num_syms & 1);
+        if *pos > data.len() { return Err(corrupt()); }
+
+        let num_indices = positions.div_ceil(1 << pairs.idx_bits) as usize;
+        let sizes = [6 * num_indices, 2 * num_blocks, (1 << pairs.block_size) * real_num_blocks];
+
+        pairs.sym_len = vec![0; num_syms];
+        let mut done = vec![false; num_syms];
+        for sym in 0..num_syms {
+            pairs.symbol_length(data, sym, &mut done)?;
+        }
+
+        let mut base = vec![0u64; h];
+        for i in (0..h - 1).rev() {
+            let first = read_u16(data, pairs.offset + 2 * i)? as u64;
+            let next = read_u16(data, pairs.offset + 2 * i + 2)? as u64;
+            base[i] = base[i + 1].wrapping_add(first).wrapping_sub(next) / 2;
+        }
+        for (i, value) in base.iter_mut().enumerate() {
+            *value = value.checked_shl(64 - (pairs.min_len + i as u32)).unwrap_or(0);
+        }
+        pairs.base = base;
+
+        Ok((pairs, sizes, flags))
+    }
+
+    fn symbol(&self, data: &[u8], sym: usize) -> Result<(usize, usize), String> {
+        let w = self.sym_pat + 3 * sym;
+        let (b0, b1, b2) = (read_u8(data, w)?, read_u8(data, w + 1)?, read_u8(data, w + 2)?);
+        Ok(((b1 & 0xf) << 8 | b0, b2 << 4 | b1 >> 4))
+    }
+
+    // Number of values a symbol stands for, less one.
+    fn symbol_length(&mut self, data: &[u8], sym: usize, done: &mut [bool]) -> Result<u32, String> {
+        if *done.get(sym).ok_or_else(corrupt)? { return Ok(self.sym_len[sym]); }
+
+        let (left, right) = self.symbol(data, sym)?;
+        if right != 0xfff {
+            done[sym] = true;
+            self.sym_len[sym] = self.symbol_length(data, left, done)? + self.symbol_length(data, right, done)? + 1;
+        }
+        done[sym] = true;
+        Ok(self.sym_len[sym])
+    }
+
+    fn length(&self, sym: usize) -> Result<i64, String> {
+        self.sym_len.get(sym).map(|&len| len as i64 + 1).ok_or_else(corrupt)
+    }
+
+    /// The value stored for position `idx`.
+    fn value(&self, data: &[u8], idx: u64) -> Result<usize, String> {
+        if self.idx_bits == 0 { return Ok(self.min_len as usize); }
+
+        let main = (idx >> self.idx_bits) as usize;
+        let mut lit = (idx & ((1 << self.idx_bits) - 1)) as i64 - (1 << (self.idx_bits - 1));
+        let entry = self.index_table + 6 * main;
+        let mut block = read_u32(data, entry)?;
+        lit += read_u16(data, entry + 4)? as i64;
+
+        while lit < 0 {
+            block = block.checked_sub(1).ok_or_else(corrupt)?;
+            lit += read_u16(data, self.size_table + 2 * block)? as i64 + 1;
+        }
+        loop {
+            let size = read_u16(data, self.size_table + 2 * block)? as i64;
+            if lit <= size { break; }
+            lit -= size + 1;
+            block += 1;
+        }
+
+        let mut ptr = self.data + (block << self.block_size);
+        let mut code = read_be(data, ptr, 8);
+        ptr += 8;
+        let mut bits = 0;
+        let mut sym;
+        loop {
+            let mut l = self.min_len;
+            while code < self.base[(l - self.min_len) as usize] {
+                l += 1;
+            }
+            let base = self.base[(l - self.min_len) as usize];
+            sym = read_u16(data, self.offset + 2 * (l - self.min_len) as usize)? + ((code - base) >> (64 - l)) as usize;
+            let length = self.length(sym)?;
+            if lit < length { break; }
+            lit -= length;
+            code <<= l;
+            bits += l;
+            if bits >= 32 {
+                bits -= 32;
+                code |= read_be(data, ptr, 4) << bits;
+                ptr += 4;
+            }
+        }
+
+        while self.length(sym)? > 1 {
+            let (left, right) = self.symbol(data, sym)?;
+            let length = self.length(left)?;
+            if lit < length {
+                sym = left;
+            } else {
+                lit -= length;
+                sym = right;
+            }
+        }
+        Ok(self.symbol(data, sym)?.0)
+    }
+}
+
+/// How the positions of one table are numbered: the pieces in the order
+/// they are placed, the sizes of the groups of like pieces and the factor
+/// each group's index is multiplied by.
+struct Encoding {
+    pieces: Vec<u8>,
+    norm: Vec<usize>,
+    factor: Vec<u64>,
+    size: u64,
+    pairs: Pairs,
+}
+
+/// A WDL or DTZ table file read into memory.
+pub struct Table {
+    data: Vec<u8>,
+    kind: TableKind,
+    num: usize,
+    // pawns of the leading color, then of the other one
+    pawns: [usize; 2],
+    kk_enc: bool,
+    // by file of the leading pawn, one for tables without pawns, then by
+    // side to move, only one for DTZ tables
+    encodings: Vec<Vec<Encoding>>,
+    dtz_flags: Vec<u8>,
+    dtz_map: usize,
+    dtz_map_idx: Vec<[usize; 4]>,
+}
+
+impl Table {
+    /// Parses the file of the table `name`, e.g. KRPvKR.
+    pub fn parse(data: Vec<u8>, name: &str, kind: TableKind) -> Result<Self, String> {
+        if read_u32(&data, 0).ok() != Some(kind.magic() as usize) {
+            return Err(format!("{name}.{} is not a Syzygy table", kind.extension()));
+        }
+        Self::parse_layout(data, name, kind)
+            .map_err(|e| format!("{e} {name}.{}", kind.extension()))
+    }
+
+    fn parse_layout(data: Vec<u8>, name: &str, kind: TableKind) -> Result<Self, String> {
+        let (white, black) = name.split_once('v').ok_or_else(corrupt)?;
+        let count = |side: &str, c: char| side.chars().filter(|&p| p == c).count();
+        let mut pawns = [count(white, 'P'), count(black, 'P')];
+        if pawns[1] > 0 && (pawns[0] == 0 || pawns[1] < pawns[0]) {
+            pawns.swap(0, 1);
+        }
+        let unique = "KQRBN".chars()
+            .map(|c| (count(white, c) == 1) as usize + (count(black, c) == 1) as usize)
+            .sum::<usize>();
+
+        let mut table = Self {
+            data: Vec::new(),
+            kind,
+            num: white.len() + black.len(),
+            pawns,
+            kk_enc: pawns[0] == 0 && unique == 2,
+            encodings: Vec::new(),
+            dtz_flags: Vec::new(),
+            dtz_map: 0,
+            dtz_map_idx: Vec::new(),
+        };
+
+        let split = kind == TableKind::Wdl && read_u8(&data, 4)? & 1 != 0;
+        let files = if table.has_pawns() { 4 } else { 1 };
+        let sides = if split { 2 } else { 1 };
+        let more_pawns = (table.pawns[1] > 0) as usize;
+
+        let mut pos = 5;
+        let mut layouts = Vec::new();
+        for file in 0..files {
+            let header = data.get(pos..pos + table.num + 1 + more_pawns).ok_or_else(corrupt)?;
+            layouts.push((0..sides).map(|side| table.encoding(header, 4 * side as u32, file)).collect::<Vec<_>>());
+            pos += table.num + 1 + more_pawns;
+        }
+        pos += pos & 1;
+
+        let mut sizes = Vec::new();
+        for layout in layouts {
+            let mut file_encodings = Vec::new();
+            for (pieces, norm, factor, size) in layout {
+                let (pairs, pair_sizes, flags) = Pairs::parse(&data, &mut pos, size, kind)?;
+                if kind == TableKind::Dtz { table.dtz_flags.push(flags); }
+                sizes.push(pair_sizes);
+                file_encodings.push(Encoding { pieces, norm, factor, size, pairs });
+            }
+            table.encodings.push(file_encodings);
+        }
+
+        if kind == TableKind::Dtz {
+            table.dtz_map = pos;
+            for &flags in &table.dtz_flags {
+                let mut map_idx = [0; 4];
+                if flags & 2 != 0 {
+                    if flags & 16 == 0 {
+                        for idx in map_idx.iter_mut() {
+                            *idx = pos + 1 - table.dtz_map;
+                            pos += 1 + read_u8(&data, pos)?;
+                        }
+                    } else {
+                        pos += pos & 1;
+                        for idx in map_idx.iter_mut() {
+                            *idx = (pos + 2 - table.dtz_map) / 2;
+                            pos += 2 + 2 * read_u16(&data, pos)?;
+                        }
+                    }
+                }
+                table.dtz_map_idx.push(map_idx);
+            }
+            pos += pos & 1;
+        }
+
+        let encodings = table.encodings.iter_mut().flatten().zip(&sizes);
+        for (encoding, size) in encodings {
+            encoding.pairs.index_table = pos;
+            pos += size[0];
+        }
+        for (encoding, size) in table.encodings.iter_mut().flatten().zip(&sizes) {
+            encoding.pairs.size_table = pos;
+            pos += size[1];
+        }
+        for (encoding, size) in table.encodings.iter_mut().flatten().zip(&sizes) {
+            pos = (pos + 0x3f) & !0x3f;
+            encoding.pairs.data = pos;
+            pos += size[2];
+        }
+        if pos > data.len() { return Err(corrupt()); }
+
+        table.data = data;
+        Ok(table)
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.pawns[0] > 0
+    }
+
+    // The pieces, group sizes, factors and number of positions of one side
+    // and file, read from the nibbles at `shift` of the piece header.
+    fn encoding(&self, header: &[u8], shift: u32, file: usize) -> (Vec<u8>, Vec<usize>, Vec<u64>, u64) {
+        let indices = indices();
+        let num = self.num;
+        let more_pawns = self.pawns[1] > 0;
+        let pieces: Vec<u8> = (0..num).map(|i| (header[i + 1 + more_pawns as usize] >> shift) & 0x0f).collect();
+        let order = ((header[0] >> shift) & 0x0f) as usize;
+        let order2 = if more_pawns { ((header[1] >> shift) & 0x0f) as usize } else { 0x0f };
+
+        let mut norm = vec![0; num];
+        let mut k = if self.has_pawns() { self.pawns[0] } else if self.kk_enc { 2 } else { 3 };
+        norm[0] = k;
+        if more_pawns {
+            norm[k] = self.pawns[1];
+            k += norm[k];
+        }
+        let mut i = k;
+        while i < num {
+            let mut j = i;
+            while j < num && pieces[j] == pieces[i] {
+                norm[i] += 1;
+                j += 1;
+            }
+            i += norm[i];
+        }
+
+        let mut factor = vec![0; num];
+        let mut n = 64 - k;
+        let mut f = 1u64;
+        let mut i = 0;
+        while k < num || i == order || i == order2 {
+            if i == order {
+                factor[0] = f;
+                f *= if self.has_pawns() {
+                    indices.pawn_factor[norm[0] - 1][file]
+                } else if self.kk_enc {
+                    KING_POSITIONS
+                } else {
+                    PIECE_POSITIONS
+                };
+            } else if i == order2 {
+                factor[norm[0]] = f;
+                f *= indices.binomial[norm[norm[0]]][48 - norm[0]];
+            } else {
+                factor[k] = f;
+                f *= indices.binomial[norm[k]][n];
+                n -= norm[k];
+                k += norm[k];
+            }
+            i += 1;
+        }
+
+        (pieces, norm, factor, f)
+    }
+
+    /// Index of the position with the squares `p` of the encoding's pieces,
+    /// after mirroring it into the part of the board the table covers.
+    fn index(&self, encoding: &Encoding, p: &mut [usize]) -> u64 {
+        let indices = indices();
+        let n = self.num;
+        if p[0] & 0x04 != 0 {
+            p.iter_mut().for_each(|sq| *sq ^= 0x07);
+        }
+
+        let mut idx;
+        let mut k;
+        if !self.has_pawns() {
+            if p[0] & 0x20 != 0 {
+                p.iter_mut().for_each(|sq| *sq ^= 0x38);
+            }
+            let leading = if self.kk_enc { 2 } else { 3 };
+            if let Some(i) = p.iter().position(|&sq| off_diag(sq) != 0) {
+                if i < leading && off_diag(p[i]) > 0 {
+                    p.iter_mut().for_each(|sq| *sq = flip_diag(*sq));
+                }
+            }
+
+            if self.kk_enc {
+                idx = indices.kings[TRIANGLE[p[0]] as usize][p[1]];
+                k = 2;
+            } else {
+                let s1 = (p[1] > p[0]) as u64;
+                let s2 = (p[2] > p[0]) as u64 + (p[2] > p[1]) as u64;
+                let (p0, p1, p2) = (p[0], p[1] as u64, p[2] as u64);
+                idx = if off_diag(p[0]) != 0 {
+                    TRIANGLE[p0] * 63 * 62 + (p1 - s1) * 62 + (p2 - s2)
+                } else if off_diag(p[1]) != 0 {
+                    6 * 63 * 62 + DIAG[p0] * 28 * 62 + LOWER[p[1]] * 62 + p2 - s2
+                } else if off_diag(p[2]) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + DIAG[p0] * 7 * 28 + (DIAG[p[1]] - s1) * 28 + LOWER[p[2]]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + DIAG[p0] * 7 * 6 + (DIAG[p[1]] - s1) * 6 + (DIAG[p[2]] - s2)
+                };
+                k = 3;
+            }
+            idx *= encoding.factor[0];
+        } else {
+            k = self.pawns[0];
+            p[1..k].sort_unstable_by(|a, b| PAWN_TWIST[*b].cmp(&PAWN_TWIST[*a]));
+            idx = indices.pawn_idx[k - 1][FLAP[p[0]]];
+            for i in 1..k {
+                idx += indices.binomial[k - i][PAWN_TWIST[p[i]]];
+            }
+            idx *= encoding.factor[0];
+
+            if self.pawns[1] > 0 {
+                let t = k + self.pawns[1];
+                idx += Self::group_index(p, k, t, 8) * encoding.factor[k];
+                k = t;
+            }
+        }
+
+        while k < n {
+            let t = k + encoding.norm[k];
+            idx += Self::group_index(p, k, t, 0) * encoding.factor[k];
+            k = t;
+        }
+        idx
+    }
+
+    // Index of the like pieces `p[k..t]` among the squares not taken by the
+    // pieces before them, less `skip` squares at the bottom of the board.
+    fn group_index(p: &mut [usize], k: usize, t: usize, skip: usize) -> u64 {
+        let indices = indices();
+        p[k..t].sort_unstable();
+        (k..t).map(|i| {
+            let below = p[..k].iter().filter(|&&sq| p[i] > sq).count();
+            indices.binomial[i - k + 1][p[i] - below - skip]
+        }).sum()
+    }
+
+    // Squares of the pieces with code `code` from the table's point of view,
+    // in which `flip` swaps the colors.
+    fn squares(board: &Board, code: u8, flip: bool) -> Vec<usize> {
+        let color = if (code & 8 != 0) != flip { PieceColor::Black } else { PieceColor::White };
+        let piece_type = match code & 7 {
+            1 => PieceType::Pawn,
+            2 => PieceType::Knight,
+            3 => PieceType::Bishop,
+            4 => PieceType::Rook,
+            5 => PieceType::Queen,
+            _ => PieceType::King,
+        };
+        let mut squares: Vec<usize> = board.cells.iter().enumerate()
+            .filter(|(_, cell)| cell.piece == Some(Piece { piece_type, color }))
+            .map(|(idx, _)| idx ^ 56)
+            .collect();
+        squares.sort_unstable();
+        squares
+    }
+
+    // Places the pieces of `pieces` from `i` on.
+    fn place(&self, board: &Board, pieces: &[u8], mut i: usize, flip: bool, p: &mut [usize]) -> Result<(), String> {
+        let mirror = if flip { 0x38 } else { 0 };
+        while i < self.num {
+            let squares = Self::squares(board, pieces[i], flip);
+            if squares.is_empty() || i + squares.len() > self.num {
+                return Err(String::from("Position does not match the table"));
+            }
+            for sq in squares {
+                p[i] = sq ^ mirror;
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up the position, with colors swapped when `flip` is set and
+    /// `side` the side to move in the table. Returns the file of the
+    /// leading pawn and the stored value, nothing when a DTZ table only
+    /// covers the other side to move.
+    fn lookup(&self, board: &Board, flip: bool, side: usize, symmetric: bool) -> Result<Option<(usize, usize)>, String> {
+        let mut p = vec![0; self.num];
+        let file = if self.has_pawns() {
+            let leading = self.encodings[0][0].pieces[0];
+            let squares = Self::squares(board, leading, flip);
+            if squares.len() != self.pawns[0] {
+                return Err(String::from("Position does not match the table"));
+            }
+            let mirror = if flip { 0x38 } else { 0 };
+            for (i, sq) in squares.into_iter().enumerate() {
+                p[i] = sq ^ mirror;
+            }
+            for i in 1..self.pawns[0] {
+                if FLAP[p[0]] > FLAP[p[i]] { p.swap(0, i); }
+            }
+            FILE_TO_FILE[p[0] & 7]
+        } else {
+            0
+        };
+
+        if self.kind == TableKind::Dtz && (self.dtz_flags[file] & 1) as usize != side && !symmetric {
+            return Ok(None);
+        }
+
+        let sides = &self.encodings[file];
+        let encoding = &sides[side.min(sides.len() - 1)];
+        let placed = if self.has_pawns() { self.pawns[0] } else { 0 };
+        self.place(board, &encoding.pieces, placed, flip, &mut p)?;
+
+        let idx = self.index(encoding, &mut p);
+        if idx >= encoding.size { return Err(corrupt()); }
+        Ok(Some((file, encoding.pairs.value(&self.data, idx)?)))
+    }
+
+    /// Win, draw or loss from -2 to 2 for the side to move.
+    pub fn wdl(&self, board: &Board, flip: bool, side: usize, symmetric: bool) -> Result<i32, String> {
+        let (_, value) = self.lookup(board, flip, side, symmetric)?.ok_or_else(corrupt)?;
+        Ok(value as i32 - 2)
+    }
+
+    /// Plies to the next capture or pawn move for a position with result
+    /// `wdl`, nothing when the table is for the other side to move.
+    pub fn dtz(&self, board: &Board, flip: bool, side: usize, symmetric: bool, wdl: i32) -> Result<Option<i32>, String> {
+        let Some((file, mut value)) = self.lookup(board, flip, side, symmetric)? else {
+            return Ok(None);
+        };
+
+        let flags = self.dtz_flags[file];
+        let result = (wdl + 2) as usize;
+        if flags & 2 != 0 {
+            let map = self.dtz_map_idx[file][WDL_TO_MAP[result]];
+            value = if flags & 16 == 0 {
+                read_u8(&self.data, self.dtz_map + map + value)?
+            } else {
+                read_u16(&self.data, self.dtz_map + 2 * (map + value))?
+            };
+        }
+        if flags & PA_FLAGS[result] == 0 || wdl & 1 != 0 {
+            value *= 2;
+        }
+        Ok(Some(value as i32))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Writes the bits of a table file.
+    #[derive(Default)]
+    struct Bits {
+        bytes: Vec<u8>,
+        used: usize,
+    }
+
+    impl Bits {
+        fn push(&mut self, value: u8, bits: usize) {
+            for i in (0..bits).rev() {
+                if self.used.is_multiple_of(8) { self.bytes.push(0); }
+                if value >> i & 1 != 0 {
+                    *self.bytes.last_mut().unwrap() |= 0x80 >> (self.used % 8);
+                }
+                self.used += 1;
+            }
+        }
+    }
+
+    /// Value of position `idx` in the white to move half of the test table.
+    pub fn test_value(idx: u64) -> usize {
+        if idx.is_multiple_of(5) || idx % 7 == 3 { 2 } else { 4 }
+    }
+
+    /// A KQvK table in which white to move wins or draws by `test_value`
+    /// and black to move always loses. White's values are coded by
+    /// symbols of two bits: 0 a draw, 1 a win and 2 a win then a draw.
+    pub fn test_table() -> Vec<u8> {
+        compressed_table(TableKind::Wdl)
+    }
+
+    /// A KQvK DTZ table for white to move only, storing `test_value` for
+    /// every position, wins exactly rather than in moves.
+    pub fn test_dtz_table() -> Vec<u8> {
+        compressed_table(TableKind::Dtz)
+    }
+
+    fn compressed_table(kind: TableKind) -> Vec<u8> {
+        let positions = PIECE_POSITIONS;
+        let mut file = Vec::new();
+        file.extend_from_slice(&kind.magic().to_le_bytes());
+        file.push((kind == TableKind::Wdl) as u8);
+        // white king, white queen, black king for both sides to move
+        file.extend_from_slice(&[0x00, 0x66, 0x55, 0xee]);
+        file.push(0);
+
+        let block_size = 13;
+        let idx_bits = 15;
+        // the first byte holds the flags of a DTZ table
+        let flags = if kind == TableKind::Dtz { PA_FLAGS[4] } else { 0 };
+        file.extend_from_slice(&[flags, block_size, idx_bits, 0]);
+        file.extend_from_slice(&1u32.to_le_bytes());
+        file.extend_from_slice(&[2, 2]);
+        file.extend_from_slice(&0u16.to_le_bytes());
+        file.extend_from_slice(&3u16.to_le_bytes());
+        file.extend_from_slice(&[0x02, 0xf0, 0xff, 0x04, 0xf0, 0xff, 0x01, 0x00, 0x00]);
+        file.push(0);
+        if kind == TableKind::Wdl {
+            file.extend_from_slice(&[0x80, 0]);
+        }
+
+        file.extend_from_slice(&0u32.to_le_bytes());
+        file.extend_from_slice(&(1u16 << (idx_bits - 1)).to_le_bytes());
+        file.extend_from_slice(&((positions - 1) as u16).to_le_bytes());
+        while file.len() % 64 != 0 { file.push(0); }
+
+        let mut bits = Bits::default();
+        let mut idx = 0;
+        while idx < positions {
+            if test_value(idx) == 4 && idx + 1 < positions && test_value(idx + 1) == 2 {
+                bits.push(2, 2);
+                idx += 2;
+            } else {
+                bits.push((test_value(idx) == 4) as u8, 2);
+                idx += 1;
+            }
+        }
+        bits.bytes.resize(1 << block_size, 0);
+        file.extend_from_slice(&bits.bytes);
+        file
+    }
+
+    /// A KPvK table in which white to move has the value 4 - file when
+    /// the pawn is on the file or its mirror, and black to move always
+    /// draws.
+    fn pawn_table() -> Vec<u8> {
+        let mut file = Vec::new();
+        file.extend_from_slice(&TableKind::Wdl.magic().to_le_bytes());
+        file.push(1);
+        // white pawn, white king, black king for both sides to move
+        for _ in 0..4 {
+            file.extend_from_slice(&[0x00, 0x11, 0x66, 0xee]);
+        }
+        file.push(0);
+        for value in [4, 3, 2, 1] {
+            file.extend_from_slice(&[0x80, value, 0x80, 2]);
+        }
+        while file.len() % 64 != 0 { file.push(0); }
+        file
+    }
+
+    #[test]
+    fn pawn_positions_have_an_index_each() {
+        let table = Table::parse(pawn_table(), "KPvK", TableKind::Wdl).unwrap();
+        for leading in 0..4 {
+            let encoding = &table.encodings[leading][0];
+            assert_eq!(encoding.size, 6 * 63 * 62);
+            let mut seen = vec![false; encoding.size as usize];
+            for pawn in (8..56).filter(|sq| sq % 8 == leading) {
+                for wk in (0..64).filter(|&sq| sq != pawn) {
+                    for bk in (0..64).filter(|&sq| sq != pawn && sq != wk) {
+                        let idx = table.index(encoding, &mut [pawn, wk, bk]);
+                        assert!(!seen[idx as usize], "pawn {pawn} kings {wk} {bk}");
+                        seen[idx as usize] = true;
+                        // the other side of the board is mirrored
+                        assert_eq!(table.index(encoding, &mut [pawn ^ 7, wk ^ 7, bk ^ 7]), idx);
+                    }
+                }
+            }
+            assert!(seen.iter().all(|&seen| seen));
+        }
+    }
+
+    #[test]
+    fn pawn_tables_are_read_by_the_file_of_the_pawn() {
+        let table = Table::parse(pawn_table(), "KPvK", TableKind::Wdl).unwrap();
+        for (fen, wdl) in [
+            ("4k3/8/8/8/P7/8/8/4K3 w - - 0 1", 2),
+            ("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1", 1),
+            ("4k3/8/2P5/8/8/8/8/4K3 w - - 0 1", 0),
+            ("4k3/8/8/8/8/8/3P4/4K3 w - - 0 1", -1),
+            ("4k3/8/8/8/6P1/8/8/4K3 w - - 0 1", 1),
+            ("4k3/8/8/8/7P/8/8/4K3 w - - 0 1", 2),
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(table.wdl(&board, false, 0, false), Ok(wdl), "{fen}");
+        }
+        let board = Board::from_fen("4k3/8/8/8/7P/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(table.wdl(&board, false, 1, false), Ok(0));
+    }
+
+    #[test]
+    fn king_pairs_are_numbered_consecutively() {
+        let kings = &indices().kings;
+        let mut codes: Vec<u64> = Vec::new();
+        for (triangle, row) in kings.iter().enumerate() {
+            let first = (0..64).find(|&sq| sq % 8 < 4 && off_diag(sq) <= 0 && TRIANGLE[sq] == triangle as u64).unwrap();
+            for (second, &code) in row.iter().enumerate() {
+                let adjacent = (first / 8).abs_diff(second / 8) <= 1 && (first % 8).abs_diff(second % 8) <= 1;
+                if !(adjacent || off_diag(first) == 0 && off_diag(second) > 0) {
+                    codes.push(code);
+                }
+            }
+        }
+        codes.sort_unstable();
+        assert_eq!(codes, (0..KING_POSITIONS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn binomials() {
+        let binomial = &indices().binomial;
+        assert_eq!(binomial[0][5], 1);
+        assert_eq!(binomial[2][5], 10);
+        assert_eq!(binomial[3][62], 37820);
+        assert_eq!(binomial[4][3], 0);
+    }
+
+    #[test]
+    fn symmetric_positions_share_an_index() {
+        let table = Table::parse(test_table(), "KQvK", TableKind::Wdl).unwrap();
+        let encoding = &table.encodings[0][0];
+        let transforms: [fn(usize) -> usize; 4] = [|sq| sq, |sq| sq ^ 7, |sq| sq ^ 0x38, flip_diag];
+        for (wk, wq, bk) in [(0, 9, 63), (12, 40, 33), (27, 28, 2), (4, 60, 7)] {
+            let mut indices = transforms.iter().map(|transform| {
+                let mut p = [transform(wk), transform(wq), transform(bk)];
+                table.index(encoding, &mut p)
+            });
+            let first = indices.next().unwrap();
+            assert!(first < encoding.size);
+            assert!(indices.all(|idx| idx == first));
+        }
+    }
+
+    #[test]
+    fn decodes_compressed_values() {
+        let table = Table::parse(test_table(), "KQvK", TableKind::Wdl).unwrap();
+        let white = &table.encodings[0][0];
+        assert_eq!(white.size, PIECE_POSITIONS);
+        for idx in (0..PIECE_POSITIONS).step_by(97).chain([PIECE_POSITIONS - 1]) {
+            assert_eq!(white.pairs.value(&table.data, idx).unwrap(), test_value(idx), "position {idx}");
+        }
+        assert_eq!(table.encodings[0][1].pairs.value(&table.data, 1234).unwrap(), 0);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(Table::parse(vec![0; 64], "KQvK", TableKind::Wdl).is_err());
+        let mut truncated = test_table();
+        truncated.truncate(200);
+        assert!(Table::parse(truncated, "KQvK", TableKind::Wdl).is_err());
+        assert!(Table::parse(test_table(), "KQvK", TableKind::Dtz).is_err());
+    }
+
+    #[test]
+    fn dtz_tables_cover_one_side() {
+        let table = Table::parse(test_dtz_table(), "KQvK", TableKind::Dtz).unwrap();
+        assert_eq!(table.dtz_flags, [PA_FLAGS[4]]);
+        let board = Board::from_fen("8/8/8/4k3/8/8/8/Q3K3 b - - 0 1").unwrap();
+        assert_eq!(table.dtz(&board, false, 1, false, -2), Ok(None));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::board::*;
+use crate::eval::*;
+
+/// A position with the result of the game it was taken from, 1 for a white
+/// win, 0.5 for a draw and 0 for a black win.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabeledPosition {
+    pub board: Board,
+    pub result: f64,
+}
+
+fn parse_result(token: &str) -> Option<f64> {
+    match token {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" | "1/2" => Some(0.5),
+        _ => token.parse().ok().filter(|result| (0.0..=1.0).contains(result)),
+    }
+}
+
+/// Reads one position per line: a FEN followed by the result, written as
+/// `1-0`, `0-1`, `1/2-1/2` or a number from 0 to 1, optionally in brackets
+/// or quotes and after a separator such as `;`, `|` or the EPD `c9` opcode.
+/// Fields between `|` separators, such as the search scores of `chess
+/// datagen`, are ignored. Blank lines and lines starting with `#` are
+/// skipped.
+pub fn parse_labeled_positions(text: &str) -> Result<Vec<LabeledPosition>, String> {
+    let mut positions = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let error = |message: &str| format!("Line {}: {message}", number + 1);
+        let (fen, result) = line.trim_end_matches(';').rsplit_once(char::is_whitespace)
+            .ok_or_else(|| error("Missing result"))?;
+        let result = parse_result(result.trim_matches(|c| matches!(c, '[' | ']' | '"' | ';')))
+            .ok_or_else(|| error(&format!("Invalid result {result}")))?;
+
+        let mut fen = fen.split('|').next().unwrap_or(fen).trim();
+        for separator in [";", "|", ",", "c9"] {
+            fen = fen.strip_suffix(separator).unwrap_or(fen).trim_end();
+        }
+        let board = Board::from_fen(fen).map_err(|e| error(&e))?;
+        positions.push(LabeledPosition { board, result });
+    }
+    Ok(positions)
+}
+
+/// Texel tuning of the classical evaluation: the weights are fitted so that
+/// `1 / (1 + 10^(-k * eval / 400))` predicts the game results of a set of
+/// quiet positions as well as possible, by Adam gradient descent on the mean
+/// squared error. The evaluation is linear in its weights, so each position
+/// is reduced to its trace once and never evaluated again.
+pub struct Tuner {
+    samples: Vec<(Trace, f64)>,
+    // middlegame and endgame value of every weight
+    weights: Vec<[f64; 2]>,
+    k: f64,
+    rate: f64,
+    moments: Vec<[f64; 2]>,
+    velocities: Vec<[f64; 2]>,
+    steps: i32,
+}
+
+impl Tuner {
+    pub const DEFAULT_RATE: f64 = 1.0;
+
+    pub fn new(positions: &[LabeledPosition], start: &EvalWeights) -> Self {
+        Self {
+            samples: positions.iter().map(|p| (ClassicalEvaluator::trace(&p.board), p.result)).collect(),
+            weights: start.to_vec().iter().map(|score| [score.mg as f64, score.eg as f64]).collect(),
+            k: 1.0,
+            rate: Self::DEFAULT_RATE,
+            moments: vec![[0.0; 2]; Param::COUNT],
+            velocities: vec![[0.0; 2]; Param::COUNT],
+            steps: 0,
+        }
+    }
+
+    /// Centipawns a weight may move in one step.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    pub fn k(&self) -> f64 {
+        self.k
+    }
+
+    fn evaluate(&self, trace: &Trace) -> f64 {
+        let phase = trace.phase as f64 / 24.0;
+        trace.terms.iter().map(|&(index, count)| {
+            let [mg, eg] = self.weights[index];
+            count as f64 * (mg * phase + eg * (1.0 - phase))
+        }).sum()
+    }
+
+    fn sigmoid(&self, eval: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-self.k * eval / 400.0))
+    }
+
+    /// Mean squared difference between predicted and actual results.
+    pub fn error(&self) -> f64 {
+        if self.samples.is_empty() { return 0.0; }
+
+        let total: f64 = self.samples.iter()
+            .map(|(trace, result)| (result - self.sigmoid(self.evaluate(trace))).powi(2))
+            .sum();
+        total / self.samples.len() as f64
+    }
+
+    /// Chooses the scaling constant `k` which best fits the current weights,
+    /// by golden section search.
+    pub fn fit_k(&mut self) {
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let (mut low, mut high) = (0.05, 5.0);
+        for _ in 0..40 {
+            let a = high - ratio * (high - low);
+            let b = low + ratio * (high - low);
+            self.k = a;
+            let error_a = self.error();
+            self.k = b;
+            if error_a < self.error() { high = b; } else { low = a; }
+        }
+        self.k = (low + high) / 2.0;
+    }
+
+    /// One step of gradient descent over all positions.
+    pub fn step(&mut self) {
+        let mut gradient = vec![[0.0; 2]; Param::COUNT];
+        let scale = std::f64::consts::LN_10 * self.k / 400.0;
+        for (trace, result) in &self.samples {
+            let predicted = self.sigmoid(self.evaluate(trace));
+            let slope = (predicted - result) * predicted * (1.0 - predicted) * scale;
+            let phase = trace.phase as f64 / 24.0;
+            for &(index, count) in &trace.terms {
+                gradient[index][0] += slope * count as f64 * phase;
+                gradient[index][1] += slope * count as f64 * (1.0 - phase);
+            }
+        }
+
+        const BETA1: f64 = 0.9;
+        const BETA2: f64 = 0.999;
+        self.steps += 1;
+        let weights = self.weights.iter_mut().flatten();
+        let moments = self.moments.iter_mut().flatten().zip(self.velocities.iter_mut().flatten());
+        for ((weight, &g), (m, v)) in weights.zip(gradient.iter().flatten()).zip(moments) {
+            *m = BETA1 * *m + (1.0 - BETA1) * g;
+            *v = BETA2 * *v + (1.0 - BETA2) * g * g;
+            let m = *m / (1.0 - BETA1.powi(self.steps));
+            let v = *v / (1.0 - BETA2.powi(self.steps));
+            *weight -= self.rate * m / (v.sqrt() + 1e-8);
+        }
+    }
+
+    /// The current weights, rounded to whole centipawns.
+    pub fn weights(&self) -> EvalWeights {
+        let scores: Vec<Score> = self.weights.iter()
+            .map(|[mg, eg]| Score::new(mg.round() as i32, eg.round() as i32))
+            .collect();
+        let mut weights = EvalWeights::default();
+        weights.set_from_slice(&scores);
+        weights
+    }
+}
+
+/// Tunes `start` on the positions for the given number of steps, reporting
+/// the error after every step.
+pub fn tune(positions: &[LabeledPosition], start: &EvalWeights, iterations: u32, rate: f64, mut on_step: impl FnMut(u32, f64)) -> EvalWeights {
+    let mut tuner = Tuner::new(positions, start);
+    tuner.set_rate(rate);
+    tuner.fit_k();
+    for iteration in 1..=iterations {
+        tuner.step();
+        on_step(iteration, tuner.error());
+    }
+    tuner.weights()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reads_results_in_several_formats() {
+        let text = "\
+# comment
+4k3/8/8/8/8/8/8/4K3 w - - 0 1 1/2-1/2
+4k3/8/8/8/8/8/8/3QK3 w - - [1.0]
+4k3/8/8/8/8/8/8/3QK3 b - - 0 12 | 1-0
+3qk3/8/8/8/8/8/8/4K3 w - - c9 \"0-1\";
+3qk3/8/8/8/8/8/8/4K3 w - - 0.25
+4k3/8/8/8/8/8/8/3QK3 w - - 0 1 | 912 | 1.0
+";
+        let positions = parse_labeled_positions(text).unwrap();
+        let results: Vec<f64> = positions.iter().map(|p| p.result).collect();
+        assert_eq!(results, vec![0.5, 1.0, 1.0, 0.0, 0.25, 1.0]);
+        assert_eq!(positions[2].board.fullmove_number, 12);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        assert_eq!(parse_labeled_positions("4k3/8/8/8/8/8/8/4K3 w - - 2-0").unwrap_err(), "Line 1: Invalid result 2-0");
+        assert!(parse_labeled_positions("\n4k3/8/8 1-0").unwrap_err().starts_with("Line 2: "));
+    }
+
+    #[test]
+    fn learns_that_knights_win_games() {
+        // games with an extra knight were won, equal ones drawn
+        let fens = [
+            ("4k3/pp6/8/8/8/8/PP2N3/4K3 w - - 0 1", 1.0),
+            ("4k3/pp6/8/8/8/3N4/PP6/4K3 b - - 0 1", 1.0),
+            ("4k3/pp3n2/8/8/8/8/PP6/4K3 w - - 0 1", 0.0),
+            ("4k3/pp6/2n5/8/8/8/PP6/4K3 b - - 0 1", 0.0),
+            ("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1", 0.5),
+            ("4k3/1p6/p7/8/8/P7/1P6/4K3 w - - 0 1", 0.5),
+        ];
+        let positions: Vec<LabeledPosition> = fens.iter()
+            .map(|&(fen, result)| LabeledPosition { board: Board::from_fen(fen).unwrap(), result })
+            .collect();
+
+        let mut start = EvalWeights::default();
+        start.material[4] = Score::new(10, 10);
+        let mut tuner = Tuner::new(&positions, &start);
+        tuner.fit_k();
+        let before = tuner.error();
+        for _ in 0..200 {
+            tuner.step();
+        }
+
+        assert!(tuner.error() < before / 2.0, "{} then {}", before, tuner.error());
+        let knight_up = &positions[0].board;
+        let before_eval = ClassicalEvaluator::new(start).evaluate(knight_up);
+        let after_eval = ClassicalEvaluator::new(tuner.weights()).evaluate(knight_up);
+        assert!(after_eval > before_eval + 100, "{before_eval} then {after_eval}");
+    }
+
+    #[test]
+    fn tuned_weights_can_be_loaded() {
+        let positions = parse_labeled_positions("4k3/8/8/8/8/8/8/3QK3 w - - 1-0").unwrap();
+        let mut errors = Vec::new();
+        let weights = tune(&positions, &EvalWeights::default(), 3, 1.0, |_, error| errors.push(error));
+        assert_eq!(errors.len(), 3);
+        assert_eq!(EvalWeights::from_config(&weights.to_config()).unwrap(), weights);
+    }
+}
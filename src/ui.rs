@@ -7,6 +7,9 @@ pub trait UI {
     fn display_cell(&self, cell: &BoardCell);
     fn display_piece(&self, piece: &Piece);
     fn show_winner(&self, winner: bool);
+    fn show_draw(&self);
+    /// The game ended before mate or stalemate, e.g. by the tablebase.
+    fn show_adjudication(&self, winner: Option<bool>, reason: &str);
 }
 
 pub struct ConsoleUI {}
@@ -21,7 +24,7 @@ impl UI for ConsoleUI {
             // print new line for each row
             if i%8 == 0 { 
                 if i < 8 {
-                    println!(""); 
+                    println!(); 
                 } else {
                     println!(" {}", 8-(i/8)+1); 
                 }
@@ -33,6 +36,18 @@ impl UI for ConsoleUI {
     }
 
     fn show_winner(&self, winner: bool) {
+        println!("Checkmate. {} wins", if winner {"WHITE"} else {"BLACK"});
+    }
+
+    fn show_draw(&self) {
+        println!("Draw");
+    }
+
+    fn show_adjudication(&self, winner: Option<bool>, reason: &str) {
+        match winner {
+            Some(winner) => println!("{reason}. {} wins", if winner {"WHITE"} else {"BLACK"}),
+            None => println!("{reason}. Draw"),
+        }
     }
 
     fn display_cell(&self, cell: &BoardCell) {